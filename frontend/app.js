@@ -7,22 +7,71 @@ const ui = {
   watchFolders: document.getElementById("watchFolders"),
   addWatchFolderButton: document.getElementById("addWatchFolderButton"),
   recursiveWatch: document.getElementById("recursiveWatch"),
+  normalizeOrientation: document.getElementById("normalizeOrientation"),
+  verifyOutputs: document.getElementById("verifyOutputs"),
+  outputPolicy: document.getElementById("outputPolicy"),
+  replaceFields: document.getElementById("replaceFields"),
   replaceMode: document.getElementById("replaceMode"),
+  confirmPermanentDeleteRow: document.getElementById("confirmPermanentDeleteRow"),
+  confirmPermanentDelete: document.getElementById("confirmPermanentDelete"),
+  archiveFields: document.getElementById("archiveFields"),
+  archiveRoot: document.getElementById("archiveRoot"),
+  archiveLayout: document.getElementById("archiveLayout"),
+  livePhotoPolicy: document.getElementById("livePhotoPolicy"),
   jpegQuality: document.getElementById("jpegQuality"),
   rescanIntervalSecs: document.getElementById("rescanIntervalSecs"),
+  minFreeSpaceMb: document.getElementById("minFreeSpaceMb"),
+  metricsEnabled: document.getElementById("metricsEnabled"),
+  metricsPort: document.getElementById("metricsPort"),
+  notificationsEnabled: document.getElementById("notificationsEnabled"),
+  notificationCoalesceSecs: document.getElementById("notificationCoalesceSecs"),
   saveButton: document.getElementById("saveButton"),
   status: document.getElementById("status"),
+  batchProgress: document.getElementById("batchProgress"),
+  batchProgressText: document.getElementById("batchProgressText"),
+  batchProgressEta: document.getElementById("batchProgressEta"),
+  batchProgressBar: document.getElementById("batchProgressBar"),
+  batchProgressFile: document.getElementById("batchProgressFile"),
   appTitle: document.getElementById("appTitle"),
   appSubtitle: document.getElementById("appSubtitle"),
   watchFoldersLabel: document.getElementById("watchFoldersLabel"),
   watchFoldersHint: document.getElementById("watchFoldersHint"),
   jpegQualityLabel: document.getElementById("jpegQualityLabel"),
   rescanIntervalLabel: document.getElementById("rescanIntervalLabel"),
+  minFreeSpaceLabel: document.getElementById("minFreeSpaceLabel"),
+  metricsPortLabel: document.getElementById("metricsPortLabel"),
+  notificationCoalesceLabel: document.getElementById("notificationCoalesceLabel"),
   recursiveWatchLabel: document.getElementById("recursiveWatchLabel"),
+  normalizeOrientationLabel: document.getElementById("normalizeOrientationLabel"),
+  verifyOutputsLabel: document.getElementById("verifyOutputsLabel"),
+  metricsEnabledLabel: document.getElementById("metricsEnabledLabel"),
+  notificationsEnabledLabel: document.getElementById("notificationsEnabledLabel"),
+  outputPolicyLabel: document.getElementById("outputPolicyLabel"),
+  outputPolicyCoexist: document.getElementById("outputPolicyCoexist"),
+  outputPolicyReplace: document.getElementById("outputPolicyReplace"),
+  outputPolicyArchive: document.getElementById("outputPolicyArchive"),
+  replaceModeNote: document.getElementById("replaceModeNote"),
   replaceModeLabel: document.getElementById("replaceModeLabel"),
-  replaceModeNote: document.getElementById("replaceModeNote")
+  replaceModeTrash: document.getElementById("replaceModeTrash"),
+  replaceModeDelete: document.getElementById("replaceModeDelete"),
+  replaceModeSecureDelete: document.getElementById("replaceModeSecureDelete"),
+  confirmPermanentDeleteLabel: document.getElementById("confirmPermanentDeleteLabel"),
+  archiveRootLabel: document.getElementById("archiveRootLabel"),
+  archiveLayoutLabel: document.getElementById("archiveLayoutLabel"),
+  archiveLayoutFlat: document.getElementById("archiveLayoutFlat"),
+  archiveLayoutMirror: document.getElementById("archiveLayoutMirror"),
+  archiveLayoutDate: document.getElementById("archiveLayoutDate"),
+  livePhotoPolicyLabel: document.getElementById("livePhotoPolicyLabel"),
+  livePhotoKeep: document.getElementById("livePhotoKeep"),
+  livePhotoTrash: document.getElementById("livePhotoTrash"),
+  livePhotoRename: document.getElementById("livePhotoRename"),
+  livePhotoSidecar: document.getElementById("livePhotoSidecar")
 };
 
+const ARCHIVE_LAYOUTS = ["flat", "mirror_subfolders", "date_buckets"];
+const REPLACE_MODES = ["trash", "delete", "secure_delete"];
+const LIVE_PHOTO_POLICIES = ["keep", "trash_alongside", "rename_to_output", "move_to_sidecar_folder"];
+
 const I18N = window.HEIC_READY_I18N?.settings || {};
 
 let baselineConfig = null;
@@ -30,6 +79,8 @@ let isSaving = false;
 let statusTimer = null;
 let statusFxTimer = null;
 let locale = "en";
+let batchProgress = null;
+let currentFile = "";
 
 function normalizeLocale(value) {
   return value === "ja" ? "ja" : "en";
@@ -52,10 +103,79 @@ function applyStaticText() {
   ui.watchFoldersHint.textContent = t("watchFoldersHint");
   ui.jpegQualityLabel.textContent = t("jpegQualityLabel");
   ui.rescanIntervalLabel.textContent = t("rescanIntervalLabel");
+  ui.minFreeSpaceLabel.textContent = t("minFreeSpaceLabel");
+  ui.metricsPortLabel.textContent = t("metricsPortLabel");
+  ui.notificationCoalesceLabel.textContent = t("notificationCoalesceLabel");
   ui.recursiveWatchLabel.textContent = t("recursiveWatchLabel");
+  ui.normalizeOrientationLabel.textContent = t("normalizeOrientationLabel");
+  ui.verifyOutputsLabel.textContent = t("verifyOutputsLabel");
+  ui.metricsEnabledLabel.textContent = t("metricsEnabledLabel");
+  ui.notificationsEnabledLabel.textContent = t("notificationsEnabledLabel");
+  ui.outputPolicyLabel.textContent = t("outputPolicyLabel");
+  ui.outputPolicyCoexist.textContent = t("outputPolicyCoexist");
+  ui.outputPolicyReplace.textContent = t("outputPolicyReplace");
+  ui.outputPolicyArchive.textContent = t("outputPolicyArchive");
   ui.replaceModeLabel.textContent = t("replaceModeLabel");
-  ui.replaceModeNote.innerHTML = t("replaceModeNote");
+  ui.replaceModeTrash.textContent = t("replaceModeTrash");
+  ui.replaceModeDelete.textContent = t("replaceModeDelete");
+  ui.replaceModeSecureDelete.textContent = t("replaceModeSecureDelete");
+  ui.confirmPermanentDeleteLabel.textContent = t("confirmPermanentDelete");
+  refreshOutputPolicyFields();
+  ui.archiveRootLabel.textContent = t("archiveRootLabel");
+  ui.archiveLayoutLabel.textContent = t("archiveLayoutLabel");
+  ui.archiveLayoutFlat.textContent = t("archiveLayoutFlat");
+  ui.archiveLayoutMirror.textContent = t("archiveLayoutMirror");
+  ui.archiveLayoutDate.textContent = t("archiveLayoutDate");
+  ui.livePhotoPolicyLabel.textContent = t("livePhotoPolicyLabel");
+  ui.livePhotoKeep.textContent = t("livePhotoKeep");
+  ui.livePhotoTrash.textContent = t("livePhotoTrash");
+  ui.livePhotoRename.textContent = t("livePhotoRename");
+  ui.livePhotoSidecar.textContent = t("livePhotoSidecar");
   ui.saveButton.textContent = t("saveButton");
+  renderBatchProgress();
+}
+
+function formatEta(totalSecs) {
+  const minutes = Math.floor(totalSecs / 60);
+  const seconds = totalSecs % 60;
+  return minutes > 0
+    ? tr(t("etaMinutes"), { minutes, seconds })
+    : tr(t("etaSeconds"), { seconds });
+}
+
+function renderBatchProgress() {
+  const progress = batchProgress;
+  const active = Boolean(progress) && progress.queued + progress.running > 0;
+  ui.batchProgress.hidden = !active;
+  if (!active) {
+    currentFile = "";
+    return;
+  }
+  ui.batchProgressText.textContent = tr(t("batchProgress"), {
+    completed: progress.completed,
+    total: progress.total
+  });
+  ui.batchProgressEta.textContent =
+    progress.eta_secs == null
+      ? t("batchEtaUnknown")
+      : tr(t("batchEta"), { eta: formatEta(progress.eta_secs) });
+  ui.batchProgressBar.max = Math.max(progress.total, 1);
+  ui.batchProgressBar.value = progress.completed;
+  ui.batchProgressFile.textContent = currentFile
+    ? tr(t("batchCurrentFile"), { file: currentFile })
+    : "";
+}
+
+async function loadBatchProgress() {
+  if (!invoke) {
+    return;
+  }
+  try {
+    batchProgress = await invoke("get_batch_progress");
+    renderBatchProgress();
+  } catch (_) {
+    // the next batch-progress event shows it
+  }
 }
 
 function normalizeWatchFolderValue(value) {
@@ -83,15 +203,67 @@ function normalizeWatchFolderList(values) {
   return folders;
 }
 
+// "coexist" / "replace", or { archive: { root, layout } }.
+function normalizeOutputPolicy(raw) {
+  if (raw === "replace") {
+    return "replace";
+  }
+  if (raw && typeof raw === "object" && raw.archive) {
+    return {
+      archive: {
+        root: String(raw.archive.root ?? "").trim(),
+        layout: ARCHIVE_LAYOUTS.includes(raw.archive.layout) ? raw.archive.layout : "flat"
+      }
+    };
+  }
+  return "coexist";
+}
+
+function readOutputPolicyFromForm() {
+  if (ui.outputPolicy.value !== "archive") {
+    return ui.outputPolicy.value;
+  }
+  return { archive: { root: ui.archiveRoot.value, layout: ui.archiveLayout.value } };
+}
+
+function refreshOutputPolicyFields() {
+  const replace = ui.outputPolicy.value === "replace";
+  const permanent = ui.replaceMode.value !== "trash";
+  ui.archiveFields.hidden = ui.outputPolicy.value !== "archive";
+  ui.replaceFields.hidden = !replace;
+  ui.confirmPermanentDeleteRow.hidden = !permanent;
+  ui.replaceModeNote.hidden = !replace;
+  ui.replaceModeNote.innerHTML = t(permanent ? "replaceModeNoteDelete" : "replaceModeNote");
+}
+
 function normalizeConfig(raw) {
   return {
     watch_folders: normalizeWatchFolderList(raw.watch_folders || []),
     recursive_watch: Boolean(raw.recursive_watch),
-    output_policy: raw.output_policy || "coexist",
+    output_policy: normalizeOutputPolicy(raw.output_policy),
+    replace_mode: REPLACE_MODES.includes(raw.replace_mode) ? raw.replace_mode : "trash",
+    confirm_permanent_delete: Boolean(raw.confirm_permanent_delete),
     jpeg_quality: Number(raw.jpeg_quality ?? 92),
+    normalize_orientation: Boolean(raw.normalize_orientation),
+    verify_outputs: Boolean(raw.verify_outputs),
     rescan_interval_secs: Number(raw.rescan_interval_secs ?? 60),
+    min_free_space_mb: Number(raw.min_free_space_mb ?? 1024),
     paused: Boolean(raw.paused),
-    locale: normalizeLocale(raw.locale)
+    locale: normalizeLocale(raw.locale),
+    folder_options: Array.isArray(raw.folder_options) ? raw.folder_options : [],
+    // Edited in config.json only; undefined lets the backend keep its defaults.
+    log_file: raw.log_file && typeof raw.log_file === "object" ? raw.log_file : undefined,
+    metrics: {
+      enabled: Boolean(raw.metrics?.enabled),
+      port: Number(raw.metrics?.port ?? 9464)
+    },
+    notifications: {
+      enabled: Boolean(raw.notifications?.enabled),
+      coalesce_secs: Number(raw.notifications?.coalesce_secs ?? 10)
+    },
+    live_photo_policy: LIVE_PHOTO_POLICIES.includes(raw.live_photo_policy)
+      ? raw.live_photo_policy
+      : "keep"
   };
 }
 
@@ -146,20 +318,50 @@ function readConfigFromForm() {
   return normalizeConfig({
     watch_folders: ui.watchFolders.value.split("\n"),
     recursive_watch: ui.recursiveWatch.checked,
-    output_policy: ui.replaceMode.checked ? "replace" : "coexist",
+    output_policy: readOutputPolicyFromForm(),
+    replace_mode: ui.replaceMode.value,
+    confirm_permanent_delete: ui.confirmPermanentDelete.checked,
     jpeg_quality: Number(ui.jpegQuality.value),
+    normalize_orientation: ui.normalizeOrientation.checked,
+    verify_outputs: ui.verifyOutputs.checked,
     rescan_interval_secs: Number(ui.rescanIntervalSecs.value),
+    min_free_space_mb: Number(ui.minFreeSpaceMb.value),
     paused: baselineConfig?.paused ?? false,
-    locale: baselineConfig?.locale ?? locale
+    locale: baselineConfig?.locale ?? locale,
+    folder_options: baselineConfig?.folder_options ?? [],
+    log_file: baselineConfig?.log_file,
+    metrics: {
+      enabled: ui.metricsEnabled.checked,
+      port: Number(ui.metricsPort.value)
+    },
+    notifications: {
+      enabled: ui.notificationsEnabled.checked,
+      coalesce_secs: Number(ui.notificationCoalesceSecs.value)
+    },
+    live_photo_policy: ui.livePhotoPolicy.value
   });
 }
 
 function writeConfigToForm(config) {
   ui.watchFolders.value = (config.watch_folders || []).join("\n");
   ui.recursiveWatch.checked = Boolean(config.recursive_watch);
-  ui.replaceMode.checked = (config.output_policy || "coexist") === "replace";
+  const archive = config.output_policy?.archive;
+  ui.outputPolicy.value = archive ? "archive" : config.output_policy || "coexist";
+  ui.archiveRoot.value = archive?.root ?? "";
+  ui.archiveLayout.value = archive?.layout ?? "flat";
+  ui.replaceMode.value = config.replace_mode || "trash";
+  ui.confirmPermanentDelete.checked = Boolean(config.confirm_permanent_delete);
+  refreshOutputPolicyFields();
   ui.jpegQuality.value = Number(config.jpeg_quality ?? 92);
+  ui.normalizeOrientation.checked = Boolean(config.normalize_orientation);
+  ui.verifyOutputs.checked = Boolean(config.verify_outputs);
+  ui.livePhotoPolicy.value = config.live_photo_policy || "keep";
   ui.rescanIntervalSecs.value = Number(config.rescan_interval_secs ?? 60);
+  ui.minFreeSpaceMb.value = Number(config.min_free_space_mb ?? 1024);
+  ui.metricsEnabled.checked = Boolean(config.metrics?.enabled);
+  ui.metricsPort.value = Number(config.metrics?.port ?? 9464);
+  ui.notificationsEnabled.checked = Boolean(config.notifications?.enabled);
+  ui.notificationCoalesceSecs.value = Number(config.notifications?.coalesce_secs ?? 10);
 }
 
 function isDirty() {
@@ -180,18 +382,32 @@ function refreshFormState() {
 }
 
 function handleFormEdited() {
+  refreshOutputPolicyFields();
   refreshFormState();
 }
 
 function validateConfig(config) {
   return (
     config.watch_folders.every((path) => path.startsWith("/")) &&
+    (!config.output_policy.archive || config.output_policy.archive.root.startsWith("/")) &&
+    (config.output_policy !== "replace" ||
+      config.replace_mode === "trash" ||
+      config.confirm_permanent_delete) &&
     Number.isFinite(config.jpeg_quality) &&
     config.jpeg_quality >= 0 &&
     config.jpeg_quality <= 100 &&
     Number.isFinite(config.rescan_interval_secs) &&
     config.rescan_interval_secs >= 15 &&
-    config.rescan_interval_secs <= 3600
+    config.rescan_interval_secs <= 3600 &&
+    Number.isInteger(config.min_free_space_mb) &&
+    config.min_free_space_mb >= 0 &&
+    config.min_free_space_mb <= 1048576 &&
+    Number.isInteger(config.metrics.port) &&
+    config.metrics.port >= 1024 &&
+    config.metrics.port <= 65535 &&
+    Number.isInteger(config.notifications.coalesce_secs) &&
+    config.notifications.coalesce_secs >= 1 &&
+    config.notifications.coalesce_secs <= 3600
   );
 }
 
@@ -290,14 +506,39 @@ if (listen) {
     }
     refreshFormState();
   });
+
+  listen("batch-progress", (event) => {
+    batchProgress = event.payload;
+    renderBatchProgress();
+  });
+
+  listen("job-progress", (event) => {
+    if (event.payload?.stage !== "started") {
+      return;
+    }
+    currentFile = String(event.payload.path).split("/").pop();
+    renderBatchProgress();
+  });
 }
 
 [
   ui.watchFolders,
   ui.recursiveWatch,
+  ui.normalizeOrientation,
+  ui.verifyOutputs,
+  ui.metricsEnabled,
+  ui.notificationsEnabled,
+  ui.outputPolicy,
   ui.replaceMode,
+  ui.confirmPermanentDelete,
+  ui.archiveRoot,
+  ui.archiveLayout,
+  ui.livePhotoPolicy,
   ui.jpegQuality,
-  ui.rescanIntervalSecs
+  ui.rescanIntervalSecs,
+  ui.minFreeSpaceMb,
+  ui.metricsPort,
+  ui.notificationCoalesceSecs
 ].forEach((element) => {
   element.addEventListener("input", handleFormEdited);
   element.addEventListener("change", handleFormEdited);
@@ -308,3 +549,4 @@ ui.addWatchFolderButton.addEventListener("click", addWatchFolder);
 
 applyStaticText();
 loadConfig();
+loadBatchProgress();
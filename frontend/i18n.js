@@ -9,22 +9,55 @@ window.HEIC_READY_I18N = {
       watchFoldersHint: "One absolute path per line.",
       jpegQualityLabel: "JPEG quality",
       rescanIntervalLabel: "Rescan interval (sec)",
+      minFreeSpaceLabel: "Minimum free space (MB)",
+      metricsPortLabel: "Metrics port",
+      notificationCoalesceLabel: "Notification window (sec)",
       recursiveWatchLabel: "Recursive watch",
-      replaceModeLabel: "Replace source HEIC (move to Trash)",
+      normalizeOrientationLabel: "Apply orientation to pixels",
+      verifyOutputsLabel: "Verify JPEG before finalizing",
+      metricsEnabledLabel: "Serve Prometheus metrics on localhost",
+      notificationsEnabledLabel: "Notify when batches finish or fail",
+      outputPolicyLabel: "Source HEIC after conversion",
+      outputPolicyCoexist: "Keep next to the JPEG",
+      outputPolicyReplace: "Replace (remove the HEIC)",
+      outputPolicyArchive: "Move to archive folder",
       replaceModeNote:
-        "Requires delete permission on the watch folder and write permission for <code>~/.Trash</code>.",
+        "Requires delete permission on the watch folder and write permission for the Trash (<code>~/.Trash</code> on macOS).",
+      replaceModeNoteDelete:
+        "Requires delete permission on the watch folder. The JPEG is always verified before the HEIC is deleted.",
+      replaceModeLabel: "How the HEIC is removed",
+      replaceModeTrash: "Move to Trash",
+      replaceModeDelete: "Delete permanently",
+      replaceModeSecureDelete: "Overwrite, then delete permanently",
+      confirmPermanentDelete: "I understand the HEIC skips the Trash and cannot be restored",
+      archiveRootLabel: "Archive folder",
+      archiveLayoutLabel: "Archive layout",
+      archiveLayoutFlat: "All in one folder",
+      archiveLayoutMirror: "Mirror subfolders",
+      archiveLayoutDate: "Year/month of capture",
+      livePhotoPolicyLabel: "Live Photo video",
+      livePhotoKeep: "Keep in place",
+      livePhotoTrash: "Move to Trash or archive with the HEIC",
+      livePhotoRename: "Rename to match the JPEG",
+      livePhotoSidecar: "Move into \"Live Photos\" folder",
       saveButton: "Save",
       statusLoading: "Loading...",
       statusPaused: "Paused",
       statusReady: "Ready",
       statusDirty: "Unsaved changes",
       statusSaved: "Saved",
+      batchProgress: "Converting {completed} / {total}",
+      batchEta: "About {eta} left",
+      batchEtaUnknown: "Estimating time left...",
+      batchCurrentFile: "Now: {file}",
+      etaMinutes: "{minutes} min {seconds} s",
+      etaSeconds: "{seconds} s",
       tauriUnavailable: "Tauri API is not available.",
       folderPickFailed: "Folder pick failed: {error}",
       loadFailed: "Load failed: {error}",
       saveFailed: "Save failed: {error}",
       validateMessage:
-        "watch_folders: absolute paths only\njpeg_quality: 0-100\nrescan_interval_secs: 15-3600",
+        "watch_folders: absolute paths only\narchive root: absolute path\npermanent delete: confirmation required\njpeg_quality: 0-100\nrescan_interval_secs: 15-3600\nmin_free_space_mb: 0-1048576\nmetrics port: 1024-65535\nnotification window: 1-3600",
     },
     ja: {
       pageTitle: "HEIC Ready 設定",
@@ -35,42 +68,237 @@ window.HEIC_READY_I18N = {
       watchFoldersHint: "行ごとに絶対パスを入力してください",
       jpegQualityLabel: "JPEG 品質",
       rescanIntervalLabel: "再スキャン間隔（秒）",
+      minFreeSpaceLabel: "最小空き容量（MB）",
+      metricsPortLabel: "メトリクスのポート",
+      notificationCoalesceLabel: "通知をまとめる間隔（秒）",
       recursiveWatchLabel: "再帰的にフォルダを監視",
-      replaceModeLabel: "元の HEIC を置換（ゴミ箱へ移動）",
+      normalizeOrientationLabel: "画像の向きを画素に反映",
+      verifyOutputsLabel: "確定前に JPEG を検証",
+      metricsEnabledLabel: "Prometheus メトリクスを localhost で公開",
+      notificationsEnabledLabel: "変換の完了と失敗を通知",
+      outputPolicyLabel: "変換後の元 HEIC",
+      outputPolicyCoexist: "JPEG と並べて残す",
+      outputPolicyReplace: "置換（HEIC を削除）",
+      outputPolicyArchive: "アーカイブフォルダへ移動",
       replaceModeNote:
-        "監視フォルダでの削除権限と <code>~/.Trash</code> への書き込み権限が必要です",
+        "監視フォルダでの削除権限とゴミ箱（macOS では <code>~/.Trash</code>）への書き込み権限が必要です",
+      replaceModeNoteDelete:
+        "監視フォルダでの削除権限が必要です。HEIC を削除する前に JPEG を必ず検証します",
+      replaceModeLabel: "HEIC の削除方法",
+      replaceModeTrash: "ゴミ箱へ移動",
+      replaceModeDelete: "完全に削除",
+      replaceModeSecureDelete: "上書きしてから完全に削除",
+      confirmPermanentDelete: "HEIC はゴミ箱を経由せず、復元できないことを理解しました",
+      archiveRootLabel: "アーカイブフォルダ",
+      archiveLayoutLabel: "アーカイブの構成",
+      archiveLayoutFlat: "1 つのフォルダにまとめる",
+      archiveLayoutMirror: "サブフォルダ構成を再現",
+      archiveLayoutDate: "撮影年/月ごと",
+      livePhotoPolicyLabel: "Live Photo の動画",
+      livePhotoKeep: "そのまま残す",
+      livePhotoTrash: "HEIC と一緒にゴミ箱・アーカイブへ移動",
+      livePhotoRename: "JPEG と同じ名前に変更",
+      livePhotoSidecar: "「Live Photos」フォルダへ移動",
       saveButton: "保存",
       statusLoading: "読み込み中...",
       statusPaused: "一時停止中",
       statusReady: "Ready",
       statusDirty: "未保存の変更があります",
       statusSaved: "保存しました",
+      batchProgress: "変換中 {completed} / {total}",
+      batchEta: "残り約 {eta}",
+      batchEtaUnknown: "残り時間を計算中...",
+      batchCurrentFile: "処理中: {file}",
+      etaMinutes: "{minutes}分{seconds}秒",
+      etaSeconds: "{seconds}秒",
       tauriUnavailable: "Tauri API が利用できません。",
       folderPickFailed: "フォルダ選択に失敗しました: {error}",
       loadFailed: "読み込みに失敗しました: {error}",
       saveFailed: "保存に失敗しました: {error}",
       validateMessage:
-        "watch_folders: 絶対パスのみ\njpeg_quality: 0-100\nrescan_interval_secs: 15-3600",
+        "watch_folders: 絶対パスのみ\narchive root: 絶対パスのみ\n完全に削除: 確認が必要\njpeg_quality: 0-100\nrescan_interval_secs: 15-3600\nmin_free_space_mb: 0-1048576\nmetrics port: 1024-65535\nnotification window: 1-3600",
     },
   },
   logs: {
     en: {
-      pageTitle: "HEIC Ready recent logs",
-      logsTitle: "Recent Logs",
-      logsSubtitle: "Latest 10 conversion/status records",
+      pageTitle: "HEIC Ready history",
+      logsTitle: "Conversion History",
+      logsSubtitle: "Every conversion/status record kept in the history files",
       refresh: "Refresh",
-      noLogs: "No logs yet.",
+      noLogs: "No matching records.",
       tauriUnavailable: "Tauri API is not available.",
       loadFailed: "Failed to load logs: {error}",
+      fileNamePlaceholder: "File name contains",
+      filterAllResults: "All results",
+      resultSuccess: "Success",
+      resultFailure: "Failure",
+      resultSkip: "Skip",
+      resultInfo: "Info",
+      filterAllCodes: "All categories",
+      filterAllFolders: "All folders",
+      filterAnyTime: "Any time",
+      filterLastHour: "Last hour",
+      filterLast24h: "Last 24 hours",
+      filterLast7d: "Last 7 days",
+      filterLast30d: "Last 30 days",
+      filterNotified: "Notified records",
+      sortNewest: "Newest first",
+      sortOldest: "Oldest first",
+      previousPage: "Previous",
+      nextPage: "Next",
+      pageInfo: "{start}–{end} of {total}",
+      exportLabel: "Export",
+      exportButton: "Export…",
+      exportResult: "Exported {count} record(s) to {path}.",
+      exportFailed: "Export failed: {error}",
+      undoTitle: "Moved to Trash",
+      undoSubtitle: "Originals trashed in replace mode can be put back where they were",
+      undoDeleteOutputs: "Also delete the generated JPEGs",
+      undoRange15m: "Last 15 minutes",
+      undoRange1h: "Last hour",
+      undoRange24h: "Last 24 hours",
+      undoRangeAll: "Everything listed",
+      undoRangeButton: "Undo range",
+      undoJobButton: "Undo",
+      noTrashHistory: "Nothing was moved to the Trash.",
+      undoConfirm: "Put {count} job(s) back from the Trash?",
+      undoConfirmDelete: "Put {count} job(s) back from the Trash and delete their JPEGs?",
+      undoResult: "Restored {restored}, deleted {deleted}, failed {failed}.",
+      undoFailed: "Undo failed: {error}",
+      trashedAs: "in Trash as {path}",
+      "code.stabilize": "Still being written",
+      "code.decode": "Decode failed",
+      "code.encode": "Encode failed",
+      "code.color": "Color conversion failed",
+      "code.finalize": "Write failed",
+      "code.verify": "Verification failed",
+      "code.trash": "Trash failed",
+      "code.archive": "Archive failed",
+      "code.delete": "Delete failed",
+      "code.permission": "Permission denied",
+      "code.disk_full": "Disk full",
+      "code.unsupported": "Unsupported",
+      "code.corrupt": "Corrupt file",
+      "code.io": "I/O error",
     },
     ja: {
-      pageTitle: "HEIC Ready 最近のログ",
-      logsTitle: "最近のログ",
-      logsSubtitle: "最新10件の変換/状態ログ",
+      pageTitle: "HEIC Ready 履歴",
+      logsTitle: "変換履歴",
+      logsSubtitle: "履歴ファイルに残っているすべての変換/状態ログ",
       refresh: "更新",
-      noLogs: "ログはまだありません。",
+      noLogs: "該当するログはありません。",
       tauriUnavailable: "Tauri API が利用できません。",
       loadFailed: "ログの読み込みに失敗しました: {error}",
+      fileNamePlaceholder: "ファイル名に含む文字",
+      filterAllResults: "すべての結果",
+      resultSuccess: "成功",
+      resultFailure: "失敗",
+      resultSkip: "スキップ",
+      resultInfo: "情報",
+      filterAllCodes: "すべての分類",
+      filterAllFolders: "すべてのフォルダ",
+      filterAnyTime: "全期間",
+      filterLastHour: "直近1時間",
+      filterLast24h: "直近24時間",
+      filterLast7d: "直近7日",
+      filterLast30d: "直近30日",
+      filterNotified: "通知の記録",
+      sortNewest: "新しい順",
+      sortOldest: "古い順",
+      previousPage: "前へ",
+      nextPage: "次へ",
+      pageInfo: "{total} 件中 {start}–{end} 件",
+      exportLabel: "書き出し",
+      exportButton: "書き出す…",
+      exportResult: "{count} 件を {path} に書き出しました。",
+      exportFailed: "書き出しに失敗しました: {error}",
+      undoTitle: "ゴミ箱へ移動したファイル",
+      undoSubtitle: "replace モードでゴミ箱へ移動した元ファイルを元の場所へ戻せます",
+      undoDeleteOutputs: "生成した JPEG も削除する",
+      undoRange15m: "直近15分",
+      undoRange1h: "直近1時間",
+      undoRange24h: "直近24時間",
+      undoRangeAll: "一覧のすべて",
+      undoRangeButton: "範囲を元に戻す",
+      undoJobButton: "元に戻す",
+      noTrashHistory: "ゴミ箱へ移動したファイルはありません。",
+      undoConfirm: "{count} 件をゴミ箱から元に戻しますか？",
+      undoConfirmDelete: "{count} 件をゴミ箱から元に戻し、生成した JPEG を削除しますか？",
+      undoResult: "復元 {restored} 件、削除 {deleted} 件、失敗 {failed} 件。",
+      undoFailed: "元に戻せませんでした: {error}",
+      trashedAs: "ゴミ箱内: {path}",
+      "code.stabilize": "書き込み中",
+      "code.decode": "デコード失敗",
+      "code.encode": "エンコード失敗",
+      "code.color": "色変換失敗",
+      "code.finalize": "書き出し失敗",
+      "code.verify": "検証失敗",
+      "code.trash": "ゴミ箱への移動失敗",
+      "code.archive": "アーカイブ失敗",
+      "code.delete": "削除失敗",
+      "code.permission": "権限なし",
+      "code.disk_full": "ディスク容量不足",
+      "code.unsupported": "非対応",
+      "code.corrupt": "破損ファイル",
+      "code.io": "I/O エラー",
+    },
+  },
+  stats: {
+    en: {
+      pageTitle: "HEIC Ready statistics",
+      statsTitle: "Statistics",
+      statsSubtitle: "Days are counted in UTC",
+      refresh: "Refresh",
+      period7: "Last 7 days",
+      period30: "Last 30 days",
+      period90: "Last 90 days",
+      period365: "Last 365 days",
+      converted: "Converted",
+      failed: "Failed",
+      skipped: "Skipped",
+      failureRate: "Failure rate",
+      bytesIn: "Bytes in",
+      bytesOut: "Bytes out",
+      saved: "{percent} smaller",
+      averageTime: "Average conversion time",
+      perDayTitle: "Converted per day",
+      dayTooltip: "{date}: {converted} converted, {failed} failed, {bytesIn} in, {bytesOut} out",
+      failuresTitle: "Failure rate by category",
+      failureLine: "{count} ({rate} of attempts)",
+      noFailures: "No failures in this period.",
+      foldersTitle: "Top folders (all time)",
+      folderLine: "{converted} converted, {failed} failed, {bytesIn} in, {bytesOut} out",
+      noFolders: "Nothing converted yet.",
+      tauriUnavailable: "Tauri API is not available.",
+      loadFailed: "Failed to load statistics: {error}",
+    },
+    ja: {
+      pageTitle: "HEIC Ready 統計",
+      statsTitle: "統計",
+      statsSubtitle: "日付は UTC で集計",
+      refresh: "更新",
+      period7: "直近7日",
+      period30: "直近30日",
+      period90: "直近90日",
+      period365: "直近365日",
+      converted: "変換",
+      failed: "失敗",
+      skipped: "スキップ",
+      failureRate: "失敗率",
+      bytesIn: "入力サイズ",
+      bytesOut: "出力サイズ",
+      saved: "{percent} 削減",
+      averageTime: "平均変換時間",
+      perDayTitle: "日ごとの変換数",
+      dayTooltip: "{date}: 変換 {converted} 件、失敗 {failed} 件、入力 {bytesIn}、出力 {bytesOut}",
+      failuresTitle: "分類ごとの失敗率",
+      failureLine: "{count} 件（試行の {rate}）",
+      noFailures: "この期間に失敗はありません。",
+      foldersTitle: "上位フォルダ（全期間）",
+      folderLine: "変換 {converted} 件、失敗 {failed} 件、入力 {bytesIn}、出力 {bytesOut}",
+      noFolders: "まだ変換していません。",
+      tauriUnavailable: "Tauri API が利用できません。",
+      loadFailed: "統計の読み込みに失敗しました: {error}",
     },
   },
 };
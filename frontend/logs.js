@@ -7,14 +7,65 @@ const ui = {
   logsTitle: document.getElementById("logsTitle"),
   logsSubtitle: document.getElementById("logsSubtitle"),
   recentLogs: document.getElementById("recentLogs"),
-  refreshLogsButton: document.getElementById("refreshLogsButton")
+  refreshLogsButton: document.getElementById("refreshLogsButton"),
+  historyFileName: document.getElementById("historyFileName"),
+  historyResult: document.getElementById("historyResult"),
+  historyCode: document.getElementById("historyCode"),
+  historyFolder: document.getElementById("historyFolder"),
+  historyRange: document.getElementById("historyRange"),
+  historySort: document.getElementById("historySort"),
+  historyPrev: document.getElementById("historyPrev"),
+  historyNext: document.getElementById("historyNext"),
+  historyPageInfo: document.getElementById("historyPageInfo"),
+  exportLabel: document.getElementById("exportLabel"),
+  exportFrom: document.getElementById("exportFrom"),
+  exportTo: document.getElementById("exportTo"),
+  exportFormat: document.getElementById("exportFormat"),
+  exportButton: document.getElementById("exportButton"),
+  exportResult: document.getElementById("exportResult"),
+  undoTitle: document.getElementById("undoTitle"),
+  undoSubtitle: document.getElementById("undoSubtitle"),
+  undoDeleteOutputs: document.getElementById("undoDeleteOutputs"),
+  undoDeleteOutputsLabel: document.getElementById("undoDeleteOutputsLabel"),
+  undoRange: document.getElementById("undoRange"),
+  undoRangeButton: document.getElementById("undoRangeButton"),
+  undoResult: document.getElementById("undoResult"),
+  trashHistory: document.getElementById("trashHistory")
 };
 
 const I18N = window.HEIC_READY_I18N?.logs || {};
 
 const RECENT_LOG_AUTO_REFRESH_MS = 10000;
+const HISTORY_PAGE_SIZE = 50;
+const FILTER_DEBOUNCE_MS = 300;
+const ERROR_CODES = [
+  "stabilize",
+  "decode",
+  "encode",
+  "color",
+  "finalize",
+  "verify",
+  "trash",
+  "archive",
+  "delete",
+  "permission",
+  "disk_full",
+  "unsupported",
+  "corrupt",
+  "io"
+];
 let refreshTimer = null;
+let filterTimer = null;
 let locale = "en";
+let trashJobs = [];
+let watchFolders = [];
+// Records covered by the notification the window was opened for.
+let notifiedRange = window.HEIC_READY_HISTORY_RANGE || null;
+// Cursors of the pages before the current one; the current page starts at `pageCursor`.
+let previousCursors = [];
+let pageCursor = null;
+let nextCursor = null;
+let pageOffset = 0;
 
 function normalizeLocale(value) {
   return value === "ja" ? "ja" : "en";
@@ -24,6 +75,11 @@ function t(key) {
   return I18N[locale]?.[key] ?? I18N.en[key] ?? key;
 }
 
+function codeLabel(code) {
+  const key = `code.${code}`;
+  return I18N[locale]?.[key] ?? I18N.en[key] ?? code;
+}
+
 function tr(template, vars = {}) {
   return String(template).replace(/\{(\w+)\}/g, (_, key) => String(vars[key] ?? ""));
 }
@@ -33,6 +89,25 @@ function applyStaticText() {
   ui.logsTitle.textContent = t("logsTitle");
   ui.logsSubtitle.textContent = t("logsSubtitle");
   ui.refreshLogsButton.textContent = t("refresh");
+  ui.historyFileName.placeholder = t("fileNamePlaceholder");
+  ui.historyPrev.textContent = t("previousPage");
+  ui.historyNext.textContent = t("nextPage");
+  ui.exportLabel.textContent = t("exportLabel");
+  ui.exportButton.textContent = t("exportButton");
+  for (const select of [ui.historyResult, ui.historyRange, ui.historySort]) {
+    for (const option of select.options) {
+      option.textContent = t(option.dataset.label);
+    }
+  }
+  renderFilterChoices();
+  ui.undoTitle.textContent = t("undoTitle");
+  ui.undoSubtitle.textContent = t("undoSubtitle");
+  ui.undoDeleteOutputsLabel.textContent = t("undoDeleteOutputs");
+  ui.undoRangeButton.textContent = t("undoRangeButton");
+  for (const option of ui.undoRange.options) {
+    option.textContent = t(option.dataset.label);
+  }
+  renderTrashHistory(trashJobs);
 }
 
 function formatLogTime(unixMs) {
@@ -43,11 +118,70 @@ function formatLogTime(unixMs) {
   return `${hh}:${mm}:${ss}`;
 }
 
+function formatLogDateTime(unixMs) {
+  const d = new Date(Number(unixMs));
+  const month = String(d.getMonth() + 1).padStart(2, "0");
+  const day = String(d.getDate()).padStart(2, "0");
+  return `${d.getFullYear()}-${month}-${day} ${formatLogTime(unixMs)}`;
+}
+
 function escapeHtml(value) {
   return String(value)
     .replaceAll("&", "&amp;")
     .replaceAll("<", "&lt;")
-    .replaceAll(">", "&gt;");
+    .replaceAll(">", "&gt;")
+    .replaceAll('"', "&quot;")
+    .replaceAll("'", "&#39;");
+}
+
+function fillSelect(select, allLabel, values, labelFor) {
+  const selected = select.value;
+  select.innerHTML = [`<option value="">${escapeHtml(allLabel)}</option>`]
+    .concat(
+      values.map(
+        (value) => `<option value="${escapeHtml(value)}">${escapeHtml(labelFor(value))}</option>`
+      )
+    )
+    .join("");
+  select.value = values.includes(selected) ? selected : "";
+}
+
+function renderFilterChoices() {
+  fillSelect(ui.historyCode, t("filterAllCodes"), ERROR_CODES, codeLabel);
+  fillSelect(ui.historyFolder, t("filterAllFolders"), watchFolders, (folder) => folder);
+}
+
+function readHistoryQuery(cursor) {
+  const now = Date.now();
+  const range = Number(ui.historyRange.value);
+  const notified = ui.historyRange.value === "notified" ? notifiedRange : null;
+  return {
+    result: ui.historyResult.value || null,
+    code: ui.historyCode.value || null,
+    folder: ui.historyFolder.value || null,
+    from_unix_ms: notified ? notified.from_unix_ms : range > 0 ? now - range : null,
+    to_unix_ms: notified ? notified.to_unix_ms : null,
+    file_name: ui.historyFileName.value.trim() || null,
+    sort: ui.historySort.value,
+    cursor,
+    limit: HISTORY_PAGE_SIZE
+  };
+}
+
+function renderHistoryPage(page) {
+  const entries = Array.isArray(page?.entries) ? page.entries : [];
+  nextCursor = page?.next_cursor ?? null;
+  ui.historyPrev.disabled = previousCursors.length === 0;
+  ui.historyNext.disabled = !nextCursor;
+  ui.historyPageInfo.textContent =
+    entries.length === 0
+      ? ""
+      : tr(t("pageInfo"), {
+          start: pageOffset + 1,
+          end: pageOffset + entries.length,
+          total: page.total
+        });
+  renderRecentLogs(entries);
 }
 
 function renderRecentLogs(logs) {
@@ -59,29 +193,193 @@ function renderRecentLogs(logs) {
   ui.recentLogs.innerHTML = logs
     .map((entry) => {
       const result = String(entry.result || "skip");
-      const time = formatLogTime(entry.timestamp_unix_ms);
+      const time = formatLogDateTime(entry.timestamp_unix_ms);
+      const code = entry.code
+        ? `<span class=\"log-code\" data-code=\"${escapeHtml(entry.code)}\">${escapeHtml(
+            codeLabel(entry.code)
+          )}</span> `
+        : "";
       return `<li><span class=\"log-result-${escapeHtml(result)}\">${escapeHtml(
         result.toUpperCase()
-      )}</span> ${escapeHtml(time)} - ${escapeHtml(entry.reason)}<br><span>${escapeHtml(
+      )}</span> ${escapeHtml(time)} - ${code}${escapeHtml(entry.reason)}<br><span>${escapeHtml(
         entry.path
       )}</span></li>`;
     })
     .join("");
 }
 
+function renderTrashHistory(jobs) {
+  if (!Array.isArray(jobs) || jobs.length === 0) {
+    ui.trashHistory.innerHTML = `<li>${escapeHtml(t("noTrashHistory"))}</li>`;
+    ui.undoRangeButton.disabled = true;
+    return;
+  }
+
+  ui.undoRangeButton.disabled = false;
+  ui.trashHistory.innerHTML = jobs
+    .map((job) => {
+      const moves = job.moves
+        .map(
+          (move) =>
+            `${escapeHtml(move.original)}<br><span>${escapeHtml(
+              tr(t("trashedAs"), { path: move.trashed })
+            )}</span>`
+        )
+        .join("<br>");
+      return `<li class=\"undo-job\"><div>#${escapeHtml(job.id)} ${escapeHtml(
+        formatLogTime(job.timestamp_unix_ms)
+      )} - ${moves}</div><button type=\"button\" class=\"subtle\" data-job-id=\"${escapeHtml(
+        job.id
+      )}\">${escapeHtml(t("undoJobButton"))}</button></li>`;
+    })
+    .join("");
+}
+
+async function refreshTrashHistory() {
+  if (!invoke) {
+    return;
+  }
+  try {
+    trashJobs = await invoke("get_trash_history");
+    renderTrashHistory(trashJobs);
+  } catch (error) {
+    ui.trashHistory.innerHTML = `<li>${escapeHtml(tr(t("loadFailed"), { error }))}</li>`;
+  }
+}
+
+function selectedRange() {
+  const value = ui.undoRange.value;
+  if (value === "all") {
+    return { kind: "jobs", ids: trashJobs.map((job) => job.id) };
+  }
+  const now = Date.now();
+  return { kind: "range", from_unix_ms: now - Number(value), to_unix_ms: now };
+}
+
+function countSelected(selection) {
+  if (selection.kind === "jobs") {
+    return selection.ids.length;
+  }
+  return trashJobs.filter(
+    (job) =>
+      Number(job.timestamp_unix_ms) >= selection.from_unix_ms &&
+      Number(job.timestamp_unix_ms) <= selection.to_unix_ms
+  ).length;
+}
+
+async function undoTrashMoves(selection) {
+  if (!invoke) {
+    return;
+  }
+  const count = countSelected(selection);
+  if (count === 0) {
+    ui.undoResult.textContent = t("noTrashHistory");
+    return;
+  }
+  const deleteOutputs = ui.undoDeleteOutputs.checked;
+  const question = deleteOutputs ? t("undoConfirmDelete") : t("undoConfirm");
+  if (!window.confirm(tr(question, { count }))) {
+    return;
+  }
+  try {
+    const report = await invoke("undo_trash_moves", { selection, deleteOutputs });
+    const failures = report.failed.map((failure) => `${failure.path}: ${failure.reason}`);
+    ui.undoResult.textContent = [
+      tr(t("undoResult"), {
+        restored: report.restored.length,
+        deleted: report.deleted_outputs.length,
+        failed: report.failed.length
+      }),
+      ...failures
+    ].join("\n");
+  } catch (error) {
+    ui.undoResult.textContent = tr(t("undoFailed"), { error });
+  }
+  refreshTrashHistory();
+  refreshRecentLogs();
+}
+
 async function refreshRecentLogs() {
   if (!invoke) {
     ui.recentLogs.innerHTML = `<li>${escapeHtml(t("tauriUnavailable"))}</li>`;
     return;
   }
   try {
-    const logs = await invoke("get_recent_logs");
-    renderRecentLogs(logs);
+    const page = await invoke("query_history", { query: readHistoryQuery(pageCursor) });
+    renderHistoryPage(page);
   } catch (error) {
     ui.recentLogs.innerHTML = `<li>${escapeHtml(tr(t("loadFailed"), { error }))}</li>`;
   }
 }
 
+function resetHistoryPaging() {
+  previousCursors = [];
+  pageCursor = null;
+  pageOffset = 0;
+}
+
+function showNextPage() {
+  if (!nextCursor) {
+    return;
+  }
+  previousCursors.push(pageCursor);
+  pageCursor = nextCursor;
+  pageOffset += HISTORY_PAGE_SIZE;
+  refreshRecentLogs();
+}
+
+function showPreviousPage() {
+  if (previousCursors.length === 0) {
+    return;
+  }
+  pageCursor = previousCursors.pop();
+  pageOffset = Math.max(0, pageOffset - HISTORY_PAGE_SIZE);
+  refreshRecentLogs();
+}
+
+function onFilterChanged() {
+  if (filterTimer) {
+    clearTimeout(filterTimer);
+  }
+  filterTimer = setTimeout(() => {
+    resetHistoryPaging();
+    refreshRecentLogs();
+  }, FILTER_DEBOUNCE_MS);
+}
+
+// Date inputs hold local calendar days; the end day is included.
+function localDayStart(value) {
+  return value ? new Date(`${value}T00:00:00`).getTime() : null;
+}
+
+function localDayEnd(value) {
+  return value ? new Date(`${value}T23:59:59.999`).getTime() : null;
+}
+
+async function exportHistory() {
+  if (!invoke) {
+    return;
+  }
+  const format = ui.exportFormat.value;
+  try {
+    const path = await invoke("pick_export_path", { format });
+    if (!path) {
+      return;
+    }
+    const count = await invoke("export_history", {
+      request: {
+        from_unix_ms: localDayStart(ui.exportFrom.value),
+        to_unix_ms: localDayEnd(ui.exportTo.value),
+        format,
+        path
+      }
+    });
+    ui.exportResult.textContent = tr(t("exportResult"), { count, path });
+  } catch (error) {
+    ui.exportResult.textContent = tr(t("exportFailed"), { error });
+  }
+}
+
 function startAutoRefresh() {
   if (refreshTimer) {
     clearInterval(refreshTimer);
@@ -102,29 +400,72 @@ async function loadLocale() {
   try {
     const config = await invoke("get_config");
     locale = normalizeLocale(config?.locale);
+    watchFolders = Array.isArray(config?.watch_folders) ? config.watch_folders : [];
     applyStaticText();
   } catch (_) {
     // ignore and keep English defaults
   }
 }
 
-ui.refreshLogsButton.addEventListener("click", refreshRecentLogs);
+ui.refreshLogsButton.addEventListener("click", () => {
+  refreshRecentLogs();
+  refreshTrashHistory();
+});
+ui.historyFileName.addEventListener("input", onFilterChanged);
+for (const select of [
+  ui.historyResult,
+  ui.historyCode,
+  ui.historyFolder,
+  ui.historyRange,
+  ui.historySort
+]) {
+  select.addEventListener("change", onFilterChanged);
+}
+ui.historyPrev.addEventListener("click", showPreviousPage);
+ui.historyNext.addEventListener("click", showNextPage);
+ui.exportButton.addEventListener("click", exportHistory);
+ui.undoRangeButton.addEventListener("click", () => undoTrashMoves(selectedRange()));
+ui.trashHistory.addEventListener("click", (event) => {
+  const button = event.target.closest("button[data-job-id]");
+  if (button) {
+    undoTrashMoves({ kind: "jobs", ids: [Number(button.dataset.jobId)] });
+  }
+});
 document.addEventListener("visibilitychange", () => {
   if (document.visibilityState === "visible") {
     refreshRecentLogs();
+    refreshTrashHistory();
   }
 });
 
+function showNotifiedRange(range) {
+  notifiedRange = range;
+  const option = ui.historyRange.querySelector('option[value="notified"]');
+  option.hidden = !range;
+  if (range) {
+    ui.historyRange.value = "notified";
+  } else if (ui.historyRange.value === "notified") {
+    ui.historyRange.value = "";
+  }
+}
+
 if (listen) {
   listen("locale-changed", (event) => {
     locale = normalizeLocale(event.payload);
     applyStaticText();
     refreshRecentLogs();
   });
+  listen("history-range", (event) => {
+    showNotifiedRange(event.payload);
+    resetHistoryPaging();
+    refreshRecentLogs();
+  });
 }
 
 applyStaticText();
+showNotifiedRange(notifiedRange);
 loadLocale().finally(() => {
   refreshRecentLogs();
+  refreshTrashHistory();
   startAutoRefresh();
 });
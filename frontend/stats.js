@@ -0,0 +1,213 @@
+const invoke =
+  window.__TAURI__?.core?.invoke ||
+  window.__TAURI_INTERNALS__?.invoke?.bind(window.__TAURI_INTERNALS__);
+const listen = window.__TAURI__?.event?.listen;
+
+const ui = {
+  statsTitle: document.getElementById("statsTitle"),
+  statsSubtitle: document.getElementById("statsSubtitle"),
+  statsPeriod: document.getElementById("statsPeriod"),
+  refreshStatsButton: document.getElementById("refreshStatsButton"),
+  statsTotals: document.getElementById("statsTotals"),
+  statsError: document.getElementById("statsError"),
+  perDayTitle: document.getElementById("perDayTitle"),
+  perDayChart: document.getElementById("perDayChart"),
+  failuresTitle: document.getElementById("failuresTitle"),
+  failuresByCode: document.getElementById("failuresByCode"),
+  foldersTitle: document.getElementById("foldersTitle"),
+  topFolders: document.getElementById("topFolders")
+};
+
+const I18N = window.HEIC_READY_I18N?.stats || {};
+const CODE_LABELS = window.HEIC_READY_I18N?.logs || {};
+
+const STATS_AUTO_REFRESH_MS = 30000;
+let locale = "en";
+let summary = null;
+
+function normalizeLocale(value) {
+  return value === "ja" ? "ja" : "en";
+}
+
+function t(key) {
+  return I18N[locale]?.[key] ?? I18N.en[key] ?? key;
+}
+
+function codeLabel(code) {
+  const key = `code.${code}`;
+  return CODE_LABELS[locale]?.[key] ?? CODE_LABELS.en?.[key] ?? code;
+}
+
+function tr(template, vars = {}) {
+  return String(template).replace(/\{(\w+)\}/g, (_, key) => String(vars[key] ?? ""));
+}
+
+function escapeHtml(value) {
+  return String(value)
+    .replaceAll("&", "&amp;")
+    .replaceAll("<", "&lt;")
+    .replaceAll(">", "&gt;")
+    .replaceAll('"', "&quot;")
+    .replaceAll("'", "&#39;");
+}
+
+function formatBytes(bytes) {
+  const units = ["B", "KB", "MB", "GB", "TB"];
+  let value = Number(bytes) || 0;
+  let unit = 0;
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit += 1;
+  }
+  return `${value.toFixed(unit === 0 ? 0 : 1)} ${units[unit]}`;
+}
+
+function formatPercent(rate) {
+  return `${(Number(rate) * 100).toFixed(1)}%`;
+}
+
+function applyStaticText() {
+  document.title = t("pageTitle");
+  ui.statsTitle.textContent = t("statsTitle");
+  ui.statsSubtitle.textContent = t("statsSubtitle");
+  ui.refreshStatsButton.textContent = t("refresh");
+  ui.perDayTitle.textContent = t("perDayTitle");
+  ui.failuresTitle.textContent = t("failuresTitle");
+  ui.foldersTitle.textContent = t("foldersTitle");
+  for (const option of ui.statsPeriod.options) {
+    option.textContent = t(option.dataset.label);
+  }
+  renderSummary(summary);
+}
+
+function renderTotals(stats) {
+  const saved = stats.bytes_in > 0 ? 1 - stats.bytes_out / stats.bytes_in : 0;
+  const averageMs = stats.average_duration_ms;
+  const rows = [
+    [t("converted"), stats.converted],
+    [t("failed"), stats.failed],
+    [t("skipped"), stats.skipped],
+    [t("failureRate"), formatPercent(stats.failure_rate)],
+    [t("bytesIn"), formatBytes(stats.bytes_in)],
+    [t("bytesOut"), `${formatBytes(stats.bytes_out)} (${tr(t("saved"), { percent: formatPercent(saved) })})`],
+    [t("averageTime"), averageMs == null ? "-" : `${(averageMs / 1000).toFixed(2)} s`]
+  ];
+  ui.statsTotals.innerHTML = rows
+    .map(([label, value]) => `<div><dt>${escapeHtml(label)}</dt><dd>${escapeHtml(value)}</dd></div>`)
+    .join("");
+}
+
+function renderPerDay(days) {
+  const max = Math.max(1, ...days.map((day) => day.converted + day.failed));
+  ui.perDayChart.innerHTML = days
+    .map((day) => {
+      const converted = (day.converted / max) * 100;
+      const failed = (day.failed / max) * 100;
+      const title = tr(t("dayTooltip"), {
+        date: day.date,
+        converted: day.converted,
+        failed: day.failed,
+        bytesIn: formatBytes(day.bytes_in),
+        bytesOut: formatBytes(day.bytes_out)
+      });
+      return `<div class=\"stats-bar\" title=\"${escapeHtml(title)}\"><span class=\"stats-bar-failed\" style=\"height:${failed}%\"></span><span class=\"stats-bar-converted\" style=\"height:${converted}%\"></span></div>`;
+    })
+    .join("");
+}
+
+function renderFailures(failures) {
+  if (failures.length === 0) {
+    ui.failuresByCode.innerHTML = `<li>${escapeHtml(t("noFailures"))}</li>`;
+    return;
+  }
+  ui.failuresByCode.innerHTML = failures
+    .map(
+      (failure) =>
+        `<li><span class=\"log-code\" data-code=\"${escapeHtml(failure.code)}\">${escapeHtml(
+          codeLabel(failure.code)
+        )}</span> ${escapeHtml(
+          tr(t("failureLine"), { count: failure.count, rate: formatPercent(failure.rate) })
+        )}</li>`
+    )
+    .join("");
+}
+
+function renderFolders(folders) {
+  if (folders.length === 0) {
+    ui.topFolders.innerHTML = `<li>${escapeHtml(t("noFolders"))}</li>`;
+    return;
+  }
+  ui.topFolders.innerHTML = folders
+    .map(
+      (folder) =>
+        `<li>${escapeHtml(folder.folder)}<br><span>${escapeHtml(
+          tr(t("folderLine"), {
+            converted: folder.converted,
+            failed: folder.failed,
+            bytesIn: formatBytes(folder.bytes_in),
+            bytesOut: formatBytes(folder.bytes_out)
+          })
+        )}</span></li>`
+    )
+    .join("");
+}
+
+function renderSummary(stats) {
+  if (!stats) {
+    return;
+  }
+  renderTotals(stats);
+  renderPerDay(stats.days);
+  renderFailures(stats.failures_by_code);
+  renderFolders(stats.top_folders);
+}
+
+async function refreshStats() {
+  if (!invoke) {
+    ui.statsError.textContent = t("tauriUnavailable");
+    return;
+  }
+  try {
+    summary = await invoke("get_stats", { days: Number(ui.statsPeriod.value) });
+    ui.statsError.textContent = "";
+    renderSummary(summary);
+  } catch (error) {
+    ui.statsError.textContent = tr(t("loadFailed"), { error });
+  }
+}
+
+async function loadLocale() {
+  if (!invoke) {
+    return;
+  }
+  try {
+    const config = await invoke("get_config");
+    locale = normalizeLocale(config?.locale);
+    applyStaticText();
+  } catch (_) {
+    // ignore and keep English defaults
+  }
+}
+
+ui.refreshStatsButton.addEventListener("click", refreshStats);
+ui.statsPeriod.addEventListener("change", refreshStats);
+document.addEventListener("visibilitychange", () => {
+  if (document.visibilityState === "visible") {
+    refreshStats();
+  }
+});
+setInterval(() => {
+  if (document.visibilityState === "visible") {
+    refreshStats();
+  }
+}, STATS_AUTO_REFRESH_MS);
+
+if (listen) {
+  listen("locale-changed", (event) => {
+    locale = normalizeLocale(event.payload);
+    applyStaticText();
+  });
+}
+
+applyStaticText();
+loadLocale().finally(refreshStats);
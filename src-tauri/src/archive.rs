@@ -0,0 +1,141 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    config::{AppConfig, ArchiveLayout, ArchiveOptions},
+    date,
+    error::ConvertError,
+    trash,
+    watcher::unique_destination_path,
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Moves a converted source into the archive and returns its new path.
+pub(crate) fn move_to_archive(
+    path: &Path,
+    archive: &ArchiveOptions,
+    config: &AppConfig,
+) -> Result<PathBuf, ConvertError> {
+    let dir = archive_dir_for(path, archive, config);
+    fs::create_dir_all(&dir).map_err(|err| {
+        ConvertError::from_io(
+            ConvertError::Archive,
+            format_args!("failed to create archive dir {}", dir.display()),
+            &err,
+        )
+    })?;
+    let destination = unique_destination_path(&dir, path);
+    trash::move_file(path, &destination, ConvertError::Archive)?;
+    Ok(destination)
+}
+
+/// Moves `path` into the archive folder `archived` went to, keeping a Live
+/// Photo video with its still.
+pub(crate) fn move_alongside(path: &Path, archived: &Path) -> Result<PathBuf, ConvertError> {
+    let dir = archived.parent().unwrap_or_else(|| Path::new("."));
+    let destination = unique_destination_path(dir, path);
+    trash::move_file(path, &destination, ConvertError::Archive)?;
+    Ok(destination)
+}
+
+fn archive_dir_for(path: &Path, archive: &ArchiveOptions, config: &AppConfig) -> PathBuf {
+    match archive.layout {
+        ArchiveLayout::Flat => archive.root.clone(),
+        ArchiveLayout::MirrorSubfolders => {
+            let parent = path.parent().unwrap_or_else(|| Path::new("."));
+            archive.root.join(config.path_below_watch_folder(parent))
+        }
+        ArchiveLayout::DateBuckets => {
+            let (year, month) = capture_month(path);
+            archive
+                .root
+                .join(format!("{year:04}"))
+                .join(format!("{month:02}"))
+        }
+    }
+}
+
+/// Year and month the photo was taken, falling back to the (UTC)
+/// modification date for files without `DateTimeOriginal`.
+fn capture_month(path: &Path) -> (i64, u32) {
+    exif_capture_month(path).unwrap_or_else(|| {
+        let days = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_secs() / SECONDS_PER_DAY)
+            .unwrap_or(0);
+        let (year, month, _) = date::civil_from_days(days as i64);
+        (year, month)
+    })
+}
+
+fn exif_capture_month(path: &Path) -> Option<(i64, u32)> {
+    let file = File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+    let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)?;
+    let exif::Value::Ascii(values) = &field.value else {
+        return None;
+    };
+    let datetime = exif::DateTime::from_ascii(values.first()?).ok()?;
+    (1..=12)
+        .contains(&datetime.month)
+        .then_some((i64::from(datetime.year), u32::from(datetime.month)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::unique_temp_dir_path;
+
+    #[test]
+    fn archive_layouts_place_sources_below_root() {
+        let root = unique_temp_dir_path("archive");
+        let watch = root.join("inbox");
+        let source = watch.join("2024/trip/IMG_0001.heic");
+        fs::create_dir_all(source.parent().expect("parent")).expect("create dir");
+        fs::write(&source, b"heic without exif").expect("write source");
+        let config = AppConfig {
+            watch_folders: vec![watch.clone()],
+            ..AppConfig::default()
+        };
+        let archive = |layout| ArchiveOptions {
+            root: root.join("archive"),
+            layout,
+        };
+
+        assert_eq!(
+            archive_dir_for(&source, &archive(ArchiveLayout::Flat), &config),
+            root.join("archive")
+        );
+        assert_eq!(
+            archive_dir_for(&source, &archive(ArchiveLayout::MirrorSubfolders), &config),
+            root.join("archive/2024/trip")
+        );
+        let bucket = archive_dir_for(&source, &archive(ArchiveLayout::DateBuckets), &config);
+        let month = bucket.file_name().expect("month").to_string_lossy();
+        let year = bucket
+            .parent()
+            .and_then(Path::file_name)
+            .expect("year")
+            .to_string_lossy();
+        assert_eq!((year.len(), month.len()), (4, 2));
+
+        let archived = move_to_archive(&source, &archive(ArchiveLayout::MirrorSubfolders), &config)
+            .expect("archive");
+        assert_eq!(archived, root.join("archive/2024/trip/IMG_0001.heic"));
+        assert!(!source.exists());
+        fs::write(&source, b"again").expect("write source again");
+        let second = move_to_archive(&source, &archive(ArchiveLayout::MirrorSubfolders), &config)
+            .expect("archive again");
+        assert_eq!(second, root.join("archive/2024/trip/IMG_0001 (1).heic"));
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,369 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    config::{history_dir_in, trash_journal_path_in},
+    date,
+    export::{self, ExportFormat},
+    undo::{TrashJournal, UndoSelection},
+};
+
+/// Must match `identifier` in `tauri.conf.json`, which names the app config dir.
+const APP_IDENTIFIER: &str = "com.melank.heic-ready";
+
+const USAGE: &str = "usage:
+  heic-ready undo --list
+  heic-ready undo --job <id> [--job <id> ...] [--delete-outputs]
+  heic-ready undo --last <duration> [--delete-outputs]    (e.g. 30m, 2h, 1d)
+  heic-ready export [--from <date>] [--to <date>] [--last <duration>]
+                    [--format csv|json] [--output <file>]  (dates as YYYY-MM-DD, UTC)";
+const MS_PER_DAY: u128 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, PartialEq, Eq)]
+enum UndoCommand {
+    List,
+    Undo {
+        selection: UndoSelection,
+        delete_outputs: bool,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ExportCommand {
+    from_unix_ms: Option<u128>,
+    to_unix_ms: Option<u128>,
+    format: ExportFormat,
+    /// Standard output when unset.
+    output: Option<PathBuf>,
+}
+
+/// Runs a command-line subcommand and returns its exit code, or `None` when
+/// the arguments are not one, so the app starts as usual.
+pub fn run(args: &[String]) -> Option<i32> {
+    let result = match args.first().map(String::as_str) {
+        Some("undo") => parse_undo_args(&args[1..], SystemTime::now()).map(|command| {
+            app_config_dir()
+                .and_then(|dir| run_undo(&TrashJournal::new(&trash_journal_path_in(&dir)), command))
+        }),
+        Some("export") => parse_export_args(&args[1..], SystemTime::now())
+            .map(|command| app_data_dir().and_then(|dir| run_export(&dir, command))),
+        _ => return None,
+    };
+    Some(match result {
+        Ok(Ok(code)) => code,
+        Ok(Err(err)) => {
+            eprintln!("{err}");
+            1
+        }
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            2
+        }
+    })
+}
+
+fn run_undo(journal: &TrashJournal, command: UndoCommand) -> Result<i32, String> {
+    match command {
+        UndoCommand::List => {
+            let now = unix_ms(SystemTime::now());
+            for job in journal.jobs()? {
+                let minutes = now.saturating_sub(job.timestamp_unix_ms) / 60_000;
+                for moved in &job.moves {
+                    println!(
+                        "#{}\t{minutes} min ago\t{}\t(in trash as {})",
+                        job.id,
+                        moved.original.display(),
+                        moved.trashed.display()
+                    );
+                }
+            }
+            Ok(0)
+        }
+        UndoCommand::Undo {
+            selection,
+            delete_outputs,
+        } => {
+            let report = journal.undo(&selection, delete_outputs)?;
+            for path in &report.restored {
+                println!("restored {}", path.display());
+            }
+            for path in &report.deleted_outputs {
+                println!("deleted {}", path.display());
+            }
+            for failure in &report.failed {
+                eprintln!("failed {}: {}", failure.path.display(), failure.reason);
+            }
+            println!("{}", report.summary());
+            Ok(if report.failed.is_empty() { 0 } else { 1 })
+        }
+    }
+}
+
+fn run_export(app_data_dir: &std::path::Path, command: ExportCommand) -> Result<i32, String> {
+    let history_dir = history_dir_in(app_data_dir);
+    match command.output {
+        Some(output) => {
+            let count = export::export_to_file(
+                &history_dir,
+                command.from_unix_ms,
+                command.to_unix_ms,
+                command.format,
+                &output,
+            )?;
+            println!("exported {count} record(s) to {}", output.display());
+        }
+        None => {
+            let records =
+                export::export_records(&history_dir, command.from_unix_ms, command.to_unix_ms);
+            let bytes = export::render(&records, command.format)?;
+            std::io::stdout()
+                .write_all(&bytes)
+                .map_err(|err| format!("failed to write export: {err}"))?;
+        }
+    }
+    Ok(0)
+}
+
+fn parse_undo_args(args: &[String], now: SystemTime) -> Result<UndoCommand, String> {
+    let mut list = false;
+    let mut ids = Vec::new();
+    let mut last = None;
+    let mut delete_outputs = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--list" => list = true,
+            "--delete-outputs" => delete_outputs = true,
+            "--job" => {
+                let value = iter.next().ok_or("--job needs an id")?;
+                ids.push(
+                    value
+                        .trim_start_matches('#')
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid job id: {value}"))?,
+                );
+            }
+            "--last" => {
+                let value = iter.next().ok_or("--last needs a duration")?;
+                last = Some(parse_duration(value)?);
+            }
+            other => return Err(format!("unknown argument: {other}")),
+        }
+    }
+    match (list, ids.is_empty(), last) {
+        (true, true, None) if !delete_outputs => Ok(UndoCommand::List),
+        (false, false, None) => Ok(UndoCommand::Undo {
+            selection: UndoSelection::Jobs(ids),
+            delete_outputs,
+        }),
+        (false, true, Some(duration)) => Ok(UndoCommand::Undo {
+            selection: UndoSelection::Range {
+                from_unix_ms: unix_ms(now).saturating_sub(duration.as_millis()),
+                to_unix_ms: unix_ms(now),
+            },
+            delete_outputs,
+        }),
+        _ => Err("pass exactly one of --list, --job or --last".to_string()),
+    }
+}
+
+fn parse_export_args(args: &[String], now: SystemTime) -> Result<ExportCommand, String> {
+    let mut command = ExportCommand {
+        from_unix_ms: None,
+        to_unix_ms: None,
+        format: ExportFormat::Csv,
+        output: None,
+    };
+    let mut format = None;
+    let mut last = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--from" => command.from_unix_ms = Some(parse_date(value()?)?),
+            // The whole day is included.
+            "--to" => command.to_unix_ms = Some(parse_date(value()?)? + MS_PER_DAY - 1),
+            "--last" => last = Some(parse_duration(value()?)?),
+            "--format" => {
+                let value = value()?;
+                format = Some(
+                    ExportFormat::parse(value).ok_or_else(|| format!("unknown format: {value}"))?,
+                );
+            }
+            "--output" => command.output = Some(PathBuf::from(value()?)),
+            other => return Err(format!("unknown argument: {other}")),
+        }
+    }
+    if let Some(duration) = last {
+        if command.from_unix_ms.is_some() || command.to_unix_ms.is_some() {
+            return Err("--last cannot be combined with --from or --to".to_string());
+        }
+        command.from_unix_ms = Some(unix_ms(now).saturating_sub(duration.as_millis()));
+    }
+    // Without --format, the output file's extension decides.
+    command.format = format
+        .or_else(|| {
+            command
+                .output
+                .as_ref()
+                .and_then(|output| output.extension())
+                .and_then(|extension| ExportFormat::parse(&extension.to_string_lossy()))
+        })
+        .unwrap_or_default();
+    Ok(command)
+}
+
+/// `YYYY-MM-DD` as the start of that day in UTC.
+fn parse_date(value: &str) -> Result<u128, String> {
+    let invalid = || format!("invalid date: {value}");
+    let mut parts = value.splitn(3, '-');
+    let mut next = || parts.next().and_then(|part| part.parse::<u32>().ok());
+    let (year, month, day) = (
+        next().ok_or_else(invalid)?,
+        next().ok_or_else(invalid)?,
+        next().ok_or_else(invalid)?,
+    );
+    if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    let days = date::days_from_civil(i64::from(year), month, day);
+    if date::civil_from_days(days) != (i64::from(year), month, day) {
+        return Err(invalid());
+    }
+    Ok(days as u128 * MS_PER_DAY)
+}
+
+/// `30m`, `2h`, `1d`; a bare number counts minutes.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (digits, unit_secs) = match value.char_indices().last() {
+        Some((index, 'm')) => (&value[..index], 60),
+        Some((index, 'h')) => (&value[..index], 60 * 60),
+        Some((index, 'd')) => (&value[..index], 24 * 60 * 60),
+        _ => (value, 60),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|count| count.checked_mul(unit_secs))
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("invalid duration: {value}"))
+}
+
+fn unix_ms(time: SystemTime) -> u128 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|value| value.as_millis())
+        .unwrap_or(0)
+}
+
+/// The directory Tauri resolves as `app_config_dir`.
+fn app_config_dir() -> Result<PathBuf, String> {
+    app_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// The directory Tauri resolves as `app_data_dir`.
+fn app_data_dir() -> Result<PathBuf, String> {
+    app_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Both resolve to the same directory on macOS and Windows.
+fn app_dir(xdg_var: &str, xdg_default: &str) -> Result<PathBuf, String> {
+    let home = || {
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .ok_or_else(|| "HOME is not set".to_string())
+    };
+    let base = if cfg!(target_os = "macos") {
+        home()?.join("Library/Application Support")
+    } else if cfg!(windows) {
+        std::env::var_os("APPDATA")
+            .map(PathBuf::from)
+            .ok_or_else(|| "APPDATA is not set".to_string())?
+    } else {
+        match std::env::var_os(xdg_var).map(PathBuf::from) {
+            Some(path) if path.is_absolute() => path,
+            _ => home()?.join(xdg_default),
+        }
+    };
+    Ok(base.join(APP_IDENTIFIER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn undo_arguments_select_jobs_or_a_time_range() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(10_000);
+        assert_eq!(
+            parse_undo_args(&args(&["--list"]), now),
+            Ok(UndoCommand::List)
+        );
+        assert_eq!(
+            parse_undo_args(
+                &args(&["--job", "#3", "--job", "7", "--delete-outputs"]),
+                now
+            ),
+            Ok(UndoCommand::Undo {
+                selection: UndoSelection::Jobs(vec![3, 7]),
+                delete_outputs: true,
+            })
+        );
+        assert_eq!(
+            parse_undo_args(&args(&["--last", "2h"]), now),
+            Ok(UndoCommand::Undo {
+                selection: UndoSelection::Range {
+                    from_unix_ms: 2_800_000,
+                    to_unix_ms: 10_000_000,
+                },
+                delete_outputs: false,
+            })
+        );
+        assert!(parse_undo_args(&args(&[]), now).is_err());
+        assert!(parse_undo_args(&args(&["--list", "--job", "1"]), now).is_err());
+        assert!(parse_undo_args(&args(&["--last", "soon"]), now).is_err());
+        assert!(run(&args(&["--minimized"])).is_none());
+    }
+
+    #[test]
+    fn export_arguments_pick_range_and_format() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(10_000);
+        assert_eq!(
+            parse_export_args(
+                &args(&[
+                    "--from",
+                    "2025-10-01",
+                    "--to",
+                    "2025-10-18",
+                    "--output",
+                    "out.JSON"
+                ]),
+                now
+            ),
+            Ok(ExportCommand {
+                from_unix_ms: Some(1_759_276_800_000),
+                to_unix_ms: Some(1_760_831_999_999),
+                format: ExportFormat::Json,
+                output: Some(PathBuf::from("out.JSON")),
+            })
+        );
+        assert_eq!(
+            parse_export_args(&args(&["--last", "1h", "--format", "csv"]), now),
+            Ok(ExportCommand {
+                from_unix_ms: Some(6_400_000),
+                to_unix_ms: None,
+                format: ExportFormat::Csv,
+                output: None,
+            })
+        );
+        assert!(parse_export_args(&args(&["--from", "2025-02-30"]), now).is_err());
+        assert!(parse_export_args(&args(&["--last", "1d", "--to", "2025-10-18"]), now).is_err());
+        assert!(parse_export_args(&args(&["--format", "xml"]), now).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use std::{fs, path::Path};
+
+use image::{DynamicImage, ImageBuffer, Pixel};
+use moxcms::{ColorProfile, Layout, TransformOptions};
+
+use crate::{config::ColorPolicy, error::ConvertError};
+
+/// Converts `image` from its embedded profile (sRGB when untagged) to the
+/// profile the policy asks for, and replaces `icc_profile` with the new one.
+/// Reading a user profile fails as I/O, anything about the profiles or the
+/// transform itself as `Color`.
+pub(crate) fn apply_color_policy(
+    image: &mut DynamicImage,
+    icc_profile: &mut Option<Vec<u8>>,
+    policy: &ColorPolicy,
+) -> Result<(), ConvertError> {
+    // Gray has no gamut to map, and an RGB profile must not tag it.
+    if !image.color().has_color() {
+        return Ok(());
+    }
+    let (target, target_icc) = match policy {
+        ColorPolicy::Preserve => return Ok(()),
+        ColorPolicy::ConvertToSrgb => {
+            // Untagged pixels are already treated as sRGB everywhere.
+            if icc_profile.is_none() {
+                return Ok(());
+            }
+            let srgb = ColorProfile::new_srgb();
+            let encoded = srgb.encode().map_err(|err| {
+                ConvertError::Color(format!("failed to encode srgb profile: {err}"))
+            })?;
+            (srgb, encoded)
+        }
+        ColorPolicy::ConvertToProfile { icc_path } => {
+            let bytes = fs::read(icc_path).map_err(|err| {
+                ConvertError::from_io(
+                    ConvertError::Io,
+                    format!("failed to read icc profile {}", icc_path.display()),
+                    &err,
+                )
+            })?;
+            (
+                parse_profile(&bytes, icc_path).map_err(ConvertError::Color)?,
+                bytes,
+            )
+        }
+    };
+    let source = match icc_profile.as_deref() {
+        Some(bytes) => {
+            parse_profile(bytes, Path::new("embedded profile")).map_err(ConvertError::Color)?
+        }
+        None => ColorProfile::new_srgb(),
+    };
+
+    convert(image, &source, &target).map_err(ConvertError::Color)?;
+    *icc_profile = Some(target_icc);
+    Ok(())
+}
+
+fn parse_profile(bytes: &[u8], origin: &Path) -> Result<ColorProfile, String> {
+    ColorProfile::new_from_slice(bytes)
+        .map_err(|err| format!("invalid icc profile {}: {err}", origin.display()))
+}
+
+fn convert(
+    image: &mut DynamicImage,
+    source: &ColorProfile,
+    target: &ColorProfile,
+) -> Result<(), String> {
+    let options = TransformOptions::default();
+    let transform_err = |err: moxcms::CmsError| format!("color transform failed: {err}");
+    match image {
+        DynamicImage::ImageRgb8(buffer) => {
+            let transform = source
+                .create_transform_8bit(Layout::Rgb, target, Layout::Rgb, options)
+                .map_err(transform_err)?;
+            transform_in_place(buffer, |src, dst| transform.transform(src, dst))
+                .map_err(transform_err)
+        }
+        DynamicImage::ImageRgba8(buffer) => {
+            let transform = source
+                .create_transform_8bit(Layout::Rgba, target, Layout::Rgba, options)
+                .map_err(transform_err)?;
+            transform_in_place(buffer, |src, dst| transform.transform(src, dst))
+                .map_err(transform_err)
+        }
+        DynamicImage::ImageRgb16(buffer) => {
+            let transform = source
+                .create_transform_16bit(Layout::Rgb, target, Layout::Rgb, options)
+                .map_err(transform_err)?;
+            transform_in_place(buffer, |src, dst| transform.transform(src, dst))
+                .map_err(transform_err)
+        }
+        DynamicImage::ImageRgba16(buffer) => {
+            let transform = source
+                .create_transform_16bit(Layout::Rgba, target, Layout::Rgba, options)
+                .map_err(transform_err)?;
+            transform_in_place(buffer, |src, dst| transform.transform(src, dst))
+                .map_err(transform_err)
+        }
+        other => {
+            *other = DynamicImage::ImageRgba16(other.to_rgba16());
+            convert(other, source, target)
+        }
+    }
+}
+
+fn transform_in_place<P, E>(
+    buffer: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+    transform: impl Fn(&[P::Subpixel], &mut [P::Subpixel]) -> Result<(), E>,
+) -> Result<(), E>
+where
+    P: Pixel,
+{
+    let source = buffer.as_raw().clone();
+    transform(&source, buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::read_intermediate;
+    use crate::test_support::unique_temp_file_path;
+    use image::{codecs::png::PngEncoder, ImageEncoder, Rgb, RgbImage};
+    use std::path::PathBuf;
+
+    /// Display P3 encodings of the sRGB primaries and mid gray.
+    const P3_FIXTURE: [([u8; 3], [u8; 3]); 4] = [
+        ([234, 51, 35], [255, 0, 0]),
+        ([117, 251, 76], [0, 255, 0]),
+        ([0, 0, 245], [0, 0, 255]),
+        ([128, 128, 128], [128, 128, 128]),
+    ];
+
+    fn display_p3_icc() -> Vec<u8> {
+        ColorProfile::new_display_p3().encode().expect("encode p3")
+    }
+
+    fn assert_close(actual: &Rgb<u8>, expected: [u8; 3], context: &str) {
+        for (channel, (a, e)) in actual.0.iter().zip(expected).enumerate() {
+            assert!(
+                a.abs_diff(e) <= 3,
+                "{context}: channel {channel} is {a}, expected {e} (pixel {actual:?})"
+            );
+        }
+    }
+
+    /// A tagged P3 PNG, as the sips/ImageIO intermediate would be.
+    fn write_p3_fixture() -> PathBuf {
+        let mut image = RgbImage::new(P3_FIXTURE.len() as u32, 1);
+        for (x, (p3, _)) in P3_FIXTURE.iter().enumerate() {
+            image.put_pixel(x as u32, 0, Rgb(*p3));
+        }
+        let mut bytes = Vec::new();
+        let mut encoder = PngEncoder::new(&mut bytes);
+        encoder.set_icc_profile(display_p3_icc()).expect("icc");
+        encoder
+            .write_image(
+                image.as_raw(),
+                image.width(),
+                image.height(),
+                image::ExtendedColorType::Rgb8,
+            )
+            .expect("encode png");
+        let path = unique_temp_file_path("p3_fixture.png");
+        fs::write(&path, bytes).expect("write fixture");
+        path
+    }
+
+    #[test]
+    fn p3_fixture_converts_to_srgb_pixels_and_profile() {
+        let path = write_p3_fixture();
+        let (mut image, mut icc_profile) = read_intermediate(&path).expect("read fixture");
+        let _ = fs::remove_file(&path);
+        assert!(icc_profile.is_some());
+
+        apply_color_policy(&mut image, &mut icc_profile, &ColorPolicy::ConvertToSrgb)
+            .expect("convert");
+
+        let converted = image.to_rgb8();
+        for (x, (p3, srgb)) in P3_FIXTURE.iter().enumerate() {
+            assert_close(
+                converted.get_pixel(x as u32, 0),
+                *srgb,
+                &format!("p3 {p3:?}"),
+            );
+        }
+        let profile =
+            ColorProfile::new_from_slice(&icc_profile.expect("profile")).expect("parse profile");
+        assert_eq!(profile.red_colorant, ColorProfile::new_srgb().red_colorant);
+    }
+
+    #[test]
+    fn preserve_keeps_pixels_and_profile() {
+        let path = write_p3_fixture();
+        let (mut image, mut icc_profile) = read_intermediate(&path).expect("read fixture");
+        let _ = fs::remove_file(&path);
+        let before = image.to_rgb8();
+
+        apply_color_policy(&mut image, &mut icc_profile, &ColorPolicy::Preserve).expect("preserve");
+
+        assert_eq!(image.to_rgb8(), before);
+        assert_eq!(icc_profile, Some(display_p3_icc()));
+    }
+
+    #[test]
+    fn untagged_source_converts_to_user_profile() {
+        let icc_path = unique_temp_file_path("display_p3.icc");
+        fs::write(&icc_path, display_p3_icc()).expect("write icc");
+        let mut image = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([255, 0, 0, 200]),
+        ));
+        let mut icc_profile = None;
+
+        apply_color_policy(
+            &mut image,
+            &mut icc_profile,
+            &ColorPolicy::ConvertToProfile {
+                icc_path: icc_path.clone(),
+            },
+        )
+        .expect("convert");
+        let _ = fs::remove_file(&icc_path);
+
+        let pixel = image.to_rgba8().get_pixel(0, 0).0;
+        assert_close(
+            &Rgb([pixel[0], pixel[1], pixel[2]]),
+            [234, 51, 35],
+            "srgb red",
+        );
+        assert_eq!(pixel[3], 200);
+        assert_eq!(icc_profile, Some(display_p3_icc()));
+    }
+
+    #[test]
+    fn unreadable_user_profile_fails_conversion() {
+        let mut image = DynamicImage::ImageRgb8(RgbImage::new(1, 1));
+        let mut icc_profile = None;
+        let err = apply_color_policy(
+            &mut image,
+            &mut icc_profile,
+            &ColorPolicy::ConvertToProfile {
+                icc_path: PathBuf::from("/path/does/not/exist.icc"),
+            },
+        )
+        .expect_err("must fail");
+        assert_eq!(err.code(), "io");
+        assert!(err.message().contains("failed to read icc profile"));
+    }
+
+    #[test]
+    fn invalid_user_profile_is_a_color_failure() {
+        let icc_path = unique_temp_file_path("invalid.icc");
+        fs::write(&icc_path, b"not an icc profile").expect("write profile");
+        let mut image = DynamicImage::ImageRgb8(RgbImage::new(1, 1));
+        let mut icc_profile = None;
+
+        let err = apply_color_policy(
+            &mut image,
+            &mut icc_profile,
+            &ColorPolicy::ConvertToProfile {
+                icc_path: icc_path.clone(),
+            },
+        )
+        .expect_err("must fail");
+
+        assert_eq!(err.code(), "color");
+        assert!(err.message().contains("invalid icc profile"));
+        let _ = fs::remove_file(icc_path);
+    }
+}
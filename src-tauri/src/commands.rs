@@ -10,16 +10,114 @@ use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter, Manager, State, WebviewUrl, WebviewWindowBuilder};
 
 use crate::{
-    config::{AppConfig, AppLocale, OutputPolicy},
-    watcher,
-    restart_watch_service, AppState, EVENT_LOCALE_CHANGED, EVENT_PAUSED_CHANGED,
+    config::{
+        default_min_free_space_mb, history_dir_in, AppConfig, AppLocale, ArchiveOptions, ColorPolicy, FolderOptions, HdrPolicy, InputFormat,
+        LivePhotoPolicy, LogFileOptions, MetricsOptions, MultiImagePolicy, NotificationOptions, OutputPolicy, ReplaceMode, ResizeMode, ResizeOptions,
+        TargetSizeOptions, ThumbnailLocation, ThumbnailOptions,
+    },
+    converter::DecodeBackend,
+    export::{self, ExportFormat},
+    history::{self, HistoryQuery, HistorySort},
+    stats::{StatsStore, StatsSummary},
+    trash,
+    undo::{self, TrashJournal, UndoSelection},
+    watcher::{self, BatchProgress, JobResult, JobStage},
+    restart_watch_service, AppState, EVENT_HISTORY_RANGE, EVENT_LOCALE_CHANGED,
+    EVENT_PAUSED_CHANGED,
 };
 
+const MIN_THUMBNAIL_SIZE: u32 = 16;
+const MAX_THUMBNAIL_SIZE: u32 = 4096;
+const MAX_MIN_FREE_SPACE_MB: u64 = 1024 * 1024;
+const MAX_LOG_FILE_MB: u64 = 1024;
+const MAX_LOG_DAYS: u32 = 3650;
+const MAX_LOG_FILES: u32 = 1000;
+/// Ports below this need elevated privileges to bind.
+const MIN_METRICS_PORT: u16 = 1024;
+const MAX_NOTIFICATION_COALESCE_SECS: u64 = 3600;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum OutputPolicyDto {
     Coexist,
     Replace,
+    Archive(ArchiveOptions),
+}
+
+/// Jobs picked in the logs window, or a time range in unix milliseconds.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum UndoSelectionDto {
+    Jobs { ids: Vec<u64> },
+    Range { from_unix_ms: u64, to_unix_ms: u64 },
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistorySortDto {
+    #[default]
+    NewestFirst,
+    OldestFirst,
+}
+
+/// Filters of the logs window; empty strings count as unset.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryQueryDto {
+    pub result: Option<String>,
+    pub code: Option<String>,
+    pub folder: Option<String>,
+    pub from_unix_ms: Option<u64>,
+    pub to_unix_ms: Option<u64>,
+    pub file_name: Option<String>,
+    pub sort: HistorySortDto,
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Export of the logs window; times in unix milliseconds, both inclusive.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExportRequestDto {
+    #[serde(default)]
+    pub from_unix_ms: Option<u64>,
+    #[serde(default)]
+    pub to_unix_ms: Option<u64>,
+    pub format: ExportFormat,
+    pub path: String,
+}
+
+/// Payload of the `job-progress` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobProgressDto {
+    pub stage: JobStage,
+    pub path: String,
+    /// `success` / `failure` / `skip` once finished, as in the logs.
+    pub result: Option<&'static str>,
+    pub code: Option<&'static str>,
+}
+
+impl JobProgressDto {
+    pub(crate) fn new(stage: JobStage, path: &Path, result: Option<JobResult>) -> Self {
+        let (result, code) = match result {
+            Some(JobResult::Converted(_)) => (Some("success"), None),
+            Some(JobResult::Failed(code)) => (Some("failure"), Some(code)),
+            Some(JobResult::Skipped) => (Some("skip"), None),
+            None => (None, None),
+        };
+        Self {
+            stage,
+            path: path.display().to_string(),
+            result,
+            code,
+        }
+    }
+}
+
+/// Records shown in the logs window when it is opened for a notification.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct HistoryRangeDto {
+    pub from_unix_ms: u128,
+    pub to_unix_ms: u128,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -29,15 +127,56 @@ pub enum LocaleDto {
     Ja,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FolderOptionsDto {
+    pub path: String,
+    #[serde(default)]
+    pub input_formats: Option<Vec<InputFormat>>,
+    #[serde(default)]
+    pub resize: Option<ResizeOptions>,
+    #[serde(default)]
+    pub target_size: Option<TargetSizeOptions>,
+    #[serde(default)]
+    pub multi_image: MultiImagePolicy,
+    #[serde(default)]
+    pub hdr: HdrPolicy,
+    #[serde(default)]
+    pub export_depth_map: bool,
+    #[serde(default)]
+    pub color: ColorPolicy,
+    #[serde(default)]
+    pub thumbnails: Option<ThumbnailOptions>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfigDto {
     pub watch_folders: Vec<String>,
     pub recursive_watch: bool,
     pub output_policy: OutputPolicyDto,
+    #[serde(default)]
+    pub replace_mode: ReplaceMode,
+    #[serde(default)]
+    pub confirm_permanent_delete: bool,
     pub jpeg_quality: u8,
+    #[serde(default)]
+    pub normalize_orientation: bool,
+    #[serde(default)]
+    pub verify_outputs: bool,
     pub rescan_interval_secs: u64,
+    #[serde(default = "default_min_free_space_mb")]
+    pub min_free_space_mb: u64,
     pub paused: bool,
     pub locale: LocaleDto,
+    #[serde(default)]
+    pub folder_options: Vec<FolderOptionsDto>,
+    #[serde(default)]
+    pub live_photo_policy: LivePhotoPolicy,
+    #[serde(default)]
+    pub log_file: LogFileOptions,
+    #[serde(default)]
+    pub metrics: MetricsOptions,
+    #[serde(default)]
+    pub notifications: NotificationOptions,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +190,7 @@ impl From<OutputPolicy> for OutputPolicyDto {
         match value {
             OutputPolicy::Coexist => Self::Coexist,
             OutputPolicy::Replace => Self::Replace,
+            OutputPolicy::Archive(options) => Self::Archive(options),
         }
     }
 }
@@ -60,6 +200,7 @@ impl From<OutputPolicyDto> for OutputPolicy {
         match value {
             OutputPolicyDto::Coexist => Self::Coexist,
             OutputPolicyDto::Replace => Self::Replace,
+            OutputPolicyDto::Archive(options) => Self::Archive(options),
         }
     }
 }
@@ -82,6 +223,95 @@ impl From<LocaleDto> for AppLocale {
     }
 }
 
+impl From<HistoryQueryDto> for HistoryQuery {
+    fn from(value: HistoryQueryDto) -> Self {
+        let non_empty = |value: Option<String>| {
+            value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Self {
+            result: non_empty(value.result),
+            code: non_empty(value.code),
+            folder: non_empty(value.folder).map(PathBuf::from),
+            from_unix_ms: value.from_unix_ms.map(u128::from),
+            to_unix_ms: value.to_unix_ms.map(u128::from),
+            file_name: non_empty(value.file_name),
+            sort: match value.sort {
+                HistorySortDto::NewestFirst => HistorySort::NewestFirst,
+                HistorySortDto::OldestFirst => HistorySort::OldestFirst,
+            },
+            cursor: non_empty(value.cursor),
+            limit: value.limit,
+        }
+    }
+}
+
+impl From<UndoSelectionDto> for UndoSelection {
+    fn from(value: UndoSelectionDto) -> Self {
+        match value {
+            UndoSelectionDto::Jobs { ids } => Self::Jobs(ids),
+            UndoSelectionDto::Range {
+                from_unix_ms,
+                to_unix_ms,
+            } => Self::Range {
+                from_unix_ms: u128::from(from_unix_ms),
+                to_unix_ms: u128::from(to_unix_ms),
+            },
+        }
+    }
+}
+
+impl From<FolderOptions> for FolderOptionsDto {
+    fn from(value: FolderOptions) -> Self {
+        Self {
+            path: value.path.to_string_lossy().into_owned(),
+            input_formats: value.input_formats,
+            resize: value.resize,
+            target_size: value.target_size,
+            multi_image: value.multi_image,
+            hdr: value.hdr,
+            export_depth_map: value.export_depth_map,
+            color: value.color,
+            thumbnails: value.thumbnails,
+        }
+    }
+}
+
+impl TryFrom<FolderOptionsDto> for FolderOptions {
+    type Error = String;
+
+    fn try_from(value: FolderOptionsDto) -> Result<Self, Self::Error> {
+        let Some(path) = normalize_watch_folder_path(&value.path)? else {
+            return Err("folder_options path must not be empty".to_string());
+        };
+        if let Some(resize) = value.resize.as_ref() {
+            validate_resize_options(resize)?;
+        }
+        if let Some(target_size) = value.target_size.as_ref() {
+            validate_target_size_options(target_size)?;
+        }
+        if let Some(formats) = value.input_formats.as_deref() {
+            validate_input_formats(formats)?;
+        }
+        validate_color_policy(&value.color)?;
+        if let Some(thumbnails) = value.thumbnails.as_ref() {
+            validate_thumbnail_options(thumbnails)?;
+        }
+        Ok(Self {
+            path,
+            input_formats: value.input_formats,
+            resize: value.resize,
+            target_size: value.target_size,
+            multi_image: value.multi_image,
+            hdr: value.hdr,
+            export_depth_map: value.export_depth_map,
+            color: value.color,
+            thumbnails: value.thumbnails,
+        })
+    }
+}
+
 impl From<AppConfig> for AppConfigDto {
     fn from(value: AppConfig) -> Self {
         Self {
@@ -92,10 +322,24 @@ impl From<AppConfig> for AppConfigDto {
                 .collect(),
             recursive_watch: value.recursive_watch,
             output_policy: value.output_policy.into(),
+            replace_mode: value.replace_mode,
+            confirm_permanent_delete: value.confirm_permanent_delete,
             jpeg_quality: value.jpeg_quality,
+            normalize_orientation: value.normalize_orientation,
+            verify_outputs: value.verify_outputs,
             rescan_interval_secs: value.rescan_interval_secs,
+            min_free_space_mb: value.min_free_space_mb,
             paused: value.paused,
             locale: value.locale.into(),
+            folder_options: value
+                .folder_options
+                .into_iter()
+                .map(FolderOptionsDto::from)
+                .collect(),
+            live_photo_policy: value.live_photo_policy,
+            log_file: value.log_file,
+            metrics: value.metrics,
+            notifications: value.notifications,
         }
     }
 }
@@ -110,6 +354,11 @@ impl TryFrom<AppConfigDto> for AppConfig {
         if value.rescan_interval_secs < 15 || value.rescan_interval_secs > 3600 {
             return Err("rescan_interval_secs must be in range 15..=3600".to_string());
         }
+        if value.min_free_space_mb > MAX_MIN_FREE_SPACE_MB {
+            return Err(format!(
+                "min_free_space_mb must be in range 0..={MAX_MIN_FREE_SPACE_MB}"
+            ));
+        }
 
         let mut watch_folders = Vec::new();
         let mut seen = HashSet::new();
@@ -122,18 +371,188 @@ impl TryFrom<AppConfigDto> for AppConfig {
             }
         }
 
+        validate_log_file_options(&value.log_file)?;
+        if value.metrics.port < MIN_METRICS_PORT {
+            return Err(format!(
+                "metrics.port must be in range {MIN_METRICS_PORT}..=65535"
+            ));
+        }
+        if !(1..=MAX_NOTIFICATION_COALESCE_SECS).contains(&value.notifications.coalesce_secs) {
+            return Err(format!(
+                "notifications.coalesce_secs must be in range 1..={MAX_NOTIFICATION_COALESCE_SECS}"
+            ));
+        }
+
+        let output_policy = OutputPolicy::from(value.output_policy);
+        if let OutputPolicy::Archive(archive) = &output_policy {
+            validate_archive_root(&archive.root, &watch_folders)?;
+        }
+        if matches!(output_policy, OutputPolicy::Replace)
+            && value.replace_mode.is_permanent()
+            && !value.confirm_permanent_delete
+        {
+            return Err(
+                "confirm_permanent_delete must be set to delete sources permanently".to_string(),
+            );
+        }
+
+        let folder_options = value
+            .folder_options
+            .into_iter()
+            .map(FolderOptions::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+
         Ok(Self {
             watch_folders,
             recursive_watch: value.recursive_watch,
-            output_policy: value.output_policy.into(),
+            output_policy,
+            replace_mode: value.replace_mode,
+            confirm_permanent_delete: value.confirm_permanent_delete,
             jpeg_quality: value.jpeg_quality,
+            normalize_orientation: value.normalize_orientation,
+            verify_outputs: value.verify_outputs,
             rescan_interval_secs: value.rescan_interval_secs,
+            min_free_space_mb: value.min_free_space_mb,
             paused: value.paused,
             locale: value.locale.into(),
+            folder_options,
+            live_photo_policy: value.live_photo_policy,
+            log_file: value.log_file,
+            metrics: value.metrics,
+            notifications: value.notifications,
         })
     }
 }
 
+/// Archived sources inside a watch folder would be picked up and converted again.
+fn validate_archive_root(root: &Path, watch_folders: &[PathBuf]) -> Result<(), String> {
+    if !root.is_absolute() {
+        return Err(format!("archive root must be absolute: {}", root.display()));
+    }
+    if let Some(folder) = watch_folders.iter().find(|folder| root.starts_with(folder)) {
+        return Err(format!(
+            "archive root {} must not be inside watch folder {}",
+            root.display(),
+            folder.display()
+        ));
+    }
+    Ok(())
+}
+
+fn validate_log_file_options(options: &LogFileOptions) -> Result<(), String> {
+    if !(1..=MAX_LOG_FILE_MB).contains(&options.max_file_mb) {
+        return Err(format!(
+            "log_file.max_file_mb must be in range 1..={MAX_LOG_FILE_MB}"
+        ));
+    }
+    if !(1..=MAX_LOG_DAYS).contains(&options.rotate_after_days)
+        || !(1..=MAX_LOG_DAYS).contains(&options.retention_days)
+    {
+        return Err(format!(
+            "log_file.rotate_after_days and retention_days must be in range 1..={MAX_LOG_DAYS}"
+        ));
+    }
+    if !(1..=MAX_LOG_FILES).contains(&options.max_files) {
+        return Err(format!(
+            "log_file.max_files must be in range 1..={MAX_LOG_FILES}"
+        ));
+    }
+    Ok(())
+}
+
+fn validate_resize_options(resize: &ResizeOptions) -> Result<(), String> {
+    match resize.mode {
+        ResizeMode::MaxLongEdge { pixels: 0 } => {
+            Err("resize max_long_edge must be greater than 0".to_string())
+        }
+        ResizeMode::MaxDimensions {
+            max_width,
+            max_height,
+        } => {
+            if max_width.is_none() && max_height.is_none() {
+                return Err("resize max_dimensions needs max_width or max_height".to_string());
+            }
+            if max_width == Some(0) || max_height == Some(0) {
+                return Err("resize max_dimensions must be greater than 0".to_string());
+            }
+            Ok(())
+        }
+        ResizeMode::Percentage { percent } if !(1..=400).contains(&percent) => {
+            Err("resize percentage must be in range 1..=400".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn validate_target_size_options(target_size: &TargetSizeOptions) -> Result<(), String> {
+    if target_size.max_bytes == 0 {
+        return Err("target_size max_bytes must be greater than 0".to_string());
+    }
+    if target_size.min_quality == 0
+        || target_size.max_quality > 100
+        || target_size.min_quality > target_size.max_quality
+    {
+        return Err("target_size qualities must satisfy 1 <= min_quality <= max_quality <= 100".to_string());
+    }
+    Ok(())
+}
+
+fn validate_input_formats(formats: &[InputFormat]) -> Result<(), String> {
+    if formats.is_empty() {
+        return Err("input_formats must not be empty".to_string());
+    }
+    if let Some(format) = formats
+        .iter()
+        .find(|format| DecodeBackend::for_format(**format).is_none())
+    {
+        return Err(format!(
+            "no decoder for {} input on this platform",
+            format.label()
+        ));
+    }
+    Ok(())
+}
+
+fn validate_color_policy(color: &ColorPolicy) -> Result<(), String> {
+    let ColorPolicy::ConvertToProfile { icc_path } = color else {
+        return Ok(());
+    };
+    if !icc_path.is_absolute() {
+        return Err(format!(
+            "color icc_path must be absolute: {}",
+            icc_path.display()
+        ));
+    }
+    if !icc_path.is_file() {
+        return Err(format!("color icc_path not found: {}", icc_path.display()));
+    }
+    Ok(())
+}
+
+fn validate_thumbnail_options(thumbnails: &ThumbnailOptions) -> Result<(), String> {
+    if thumbnails.sizes.is_empty() {
+        return Err("thumbnails sizes must not be empty".to_string());
+    }
+    if let Some(size) = thumbnails
+        .sizes
+        .iter()
+        .find(|size| !(MIN_THUMBNAIL_SIZE..=MAX_THUMBNAIL_SIZE).contains(*size))
+    {
+        return Err(format!(
+            "thumbnail size {size} must be in range {MIN_THUMBNAIL_SIZE}..={MAX_THUMBNAIL_SIZE}"
+        ));
+    }
+    if let ThumbnailLocation::SeparateRoot { root } = &thumbnails.location {
+        if !root.is_absolute() {
+            return Err(format!(
+                "thumbnails root must be absolute: {}",
+                root.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn normalize_watch_folder_path(raw: &str) -> Result<Option<PathBuf>, String> {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
@@ -148,8 +567,72 @@ fn normalize_watch_folder_path(raw: &str) -> Result<Option<PathBuf>, String> {
 }
 
 #[tauri::command]
-pub fn get_recent_logs() -> Vec<watcher::RecentLog> {
-    watcher::get_recent_logs()
+pub fn query_history(query: HistoryQueryDto) -> Result<history::HistoryPage, String> {
+    history::query(&query.into())
+}
+
+#[tauri::command]
+pub fn get_trash_history(state: State<'_, AppState>) -> Result<Vec<undo::TrashJob>, String> {
+    trash_journal(&state)?.jobs()
+}
+
+/// The watcher is stopped while originals come back: it would see them as
+/// new files before the journal remembers them as restored, and convert and
+/// trash them again in `replace` mode.
+#[tauri::command]
+pub fn undo_trash_moves(
+    selection: UndoSelectionDto,
+    delete_outputs: bool,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<undo::UndoReport, String> {
+    let journal = trash_journal(&state)?;
+    let running = state
+        .watch_service
+        .lock()
+        .map_err(|err| format!("failed to lock watch service: {err}"))?
+        .take();
+    let was_running = running.is_some();
+    if let Some(service) = running {
+        service.stop();
+    }
+    let report = journal.undo(&selection.into(), delete_outputs);
+    if was_running {
+        restart_watch_service(&app)?;
+    }
+    let report = report?;
+    log::info!("{}", report.summary());
+    watcher::push_recent_info(&report.summary());
+    Ok(report)
+}
+
+/// Totals of the last `days` days, with the all-time top folders.
+#[tauri::command]
+pub fn get_stats(days: u32, state: State<'_, AppState>) -> Result<StatsSummary, String> {
+    let path = state
+        .config_store
+        .lock()
+        .map_err(|err| format!("failed to lock config store: {err}"))?
+        .stats_path();
+    StatsStore::new(&path).summary(days)
+}
+
+/// The batch in progress, for windows opened while it runs.
+#[tauri::command]
+pub fn get_batch_progress(state: State<'_, AppState>) -> Result<BatchProgress, String> {
+    state
+        .batch_progress
+        .lock()
+        .map(|progress| *progress)
+        .map_err(|err| format!("failed to lock batch progress: {err}"))
+}
+
+fn trash_journal(state: &State<'_, AppState>) -> Result<TrashJournal, String> {
+    let store = state
+        .config_store
+        .lock()
+        .map_err(|err| format!("failed to lock config store: {err}"))?;
+    Ok(TrashJournal::new(&store.trash_journal_path()))
 }
 
 #[tauri::command]
@@ -189,8 +672,10 @@ pub fn update_config(
     app.emit(EVENT_LOCALE_CHANGED, LocaleDto::from(locale))
         .map_err(|err| format!("failed to emit locale event: {err}"))?;
 
-    if warning.is_some() {
-        watcher::push_recent_info("replace unavailable; fallback to coexist");
+    if let Some(warning) = &warning {
+        // The first line names the policy, e.g. "Replace unavailable".
+        let summary = warning.lines().next().unwrap_or_default().to_lowercase();
+        watcher::push_recent_info(&format!("{summary}; fallback to coexist"));
     }
 
     let result_config = store_config_to_dto(state)?;
@@ -249,12 +734,70 @@ end try"#;
     Ok(Some(normalized.to_string_lossy().into_owned()))
 }
 
+#[tauri::command]
+pub fn pick_export_path(format: ExportFormat) -> Result<Option<String>, String> {
+    let script = format!(
+        r#"try
+POSIX path of (choose file name with prompt "Export HEIC Ready history" default name "heic-ready-history.{}")
+on error number -128
+return ""
+end try"#,
+        format.extension()
+    );
+
+    let output = Command::new("osascript")
+        .args(["-e", &script])
+        .output()
+        .map_err(|err| format!("failed to open save dialog: {err}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(format!("save dialog failed: {stderr}"));
+    }
+
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((!path.is_empty()).then_some(path))
+}
+
+#[tauri::command]
+pub fn export_history(app: AppHandle, request: ExportRequestDto) -> Result<usize, String> {
+    let path = PathBuf::from(request.path.trim());
+    if !path.is_absolute() {
+        return Err(format!("export path must be absolute: {}", path.display()));
+    }
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|err| format!("failed to resolve app data dir: {err}"))?;
+    let count = export::export_to_file(
+        &history_dir_in(&app_data_dir),
+        request.from_unix_ms.map(u128::from),
+        request.to_unix_ms.map(u128::from),
+        request.format,
+        &path,
+    )?;
+    log::info!("exported {count} history record(s) to {}", path.display());
+    Ok(count)
+}
+
 #[tauri::command]
 pub fn open_recent_logs_window(app: AppHandle) -> Result<(), String> {
-    let locale = read_locale(&app)?;
+    open_logs_window(&app, None)
+}
+
+/// Opens the logs window, filtered to `range` when given.
+pub(crate) fn open_logs_window(
+    app: &AppHandle,
+    range: Option<HistoryRangeDto>,
+) -> Result<(), String> {
+    let locale = read_locale(app)?;
     let title = recent_logs_window_title(locale);
 
     if let Some(window) = app.get_webview_window("recent-logs") {
+        if let Some(range) = range {
+            app.emit_to("recent-logs", EVENT_HISTORY_RANGE, range)
+                .map_err(|err| format!("failed to filter recent logs window: {err}"))?;
+        }
         window
             .show()
             .map_err(|err| format!("failed to show recent logs window: {err}"))?;
@@ -267,17 +810,21 @@ pub fn open_recent_logs_window(app: AppHandle) -> Result<(), String> {
         return Ok(());
     }
 
-    let window = WebviewWindowBuilder::new(
-        &app,
-        "recent-logs",
-        WebviewUrl::App("logs.html".into()),
-    )
-    .title(title)
-    .inner_size(620.0, 420.0)
-    .min_inner_size(520.0, 320.0)
-    .resizable(true)
-    .build()
-    .map_err(|err| format!("failed to create recent logs window: {err}"))?;
+    let mut builder =
+        WebviewWindowBuilder::new(app, "recent-logs", WebviewUrl::App("logs.html".into()))
+            .title(title)
+            .inner_size(620.0, 640.0)
+            .min_inner_size(520.0, 320.0)
+            .resizable(true);
+    if let Some(range) = range {
+        let range = serde_json::to_string(&range)
+            .map_err(|err| format!("failed to serialize history range: {err}"))?;
+        builder =
+            builder.initialization_script(format!("window.HEIC_READY_HISTORY_RANGE = {range};"));
+    }
+    let window = builder
+        .build()
+        .map_err(|err| format!("failed to create recent logs window: {err}"))?;
 
     let logs_window = window.clone();
     window.on_window_event(move |event| {
@@ -292,6 +839,45 @@ pub fn open_recent_logs_window(app: AppHandle) -> Result<(), String> {
     Ok(())
 }
 
+#[tauri::command]
+pub fn open_stats_window(app: AppHandle) -> Result<(), String> {
+    let locale = read_locale(&app)?;
+    let title = stats_window_title(locale);
+
+    if let Some(window) = app.get_webview_window("stats") {
+        window
+            .show()
+            .map_err(|err| format!("failed to show stats window: {err}"))?;
+        window
+            .set_title(title)
+            .map_err(|err| format!("failed to set stats title: {err}"))?;
+        window
+            .set_focus()
+            .map_err(|err| format!("failed to focus stats window: {err}"))?;
+        return Ok(());
+    }
+
+    let window = WebviewWindowBuilder::new(&app, "stats", WebviewUrl::App("stats.html".into()))
+        .title(title)
+        .inner_size(620.0, 640.0)
+        .min_inner_size(520.0, 320.0)
+        .resizable(true)
+        .build()
+        .map_err(|err| format!("failed to create stats window: {err}"))?;
+
+    let stats_window = window.clone();
+    window.on_window_event(move |event| {
+        if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+            api.prevent_close();
+            if let Err(err) = stats_window.hide() {
+                log::error!("failed to hide stats window: {err}");
+            }
+        }
+    });
+
+    Ok(())
+}
+
 fn read_locale(app: &AppHandle) -> Result<AppLocale, String> {
     let state: State<'_, AppState> = app.state();
     let store = state
@@ -308,6 +894,13 @@ fn recent_logs_window_title(locale: AppLocale) -> &'static str {
     }
 }
 
+fn stats_window_title(locale: AppLocale) -> &'static str {
+    match locale {
+        AppLocale::En => "HEIC Ready statistics",
+        AppLocale::Ja => "HEIC Ready 統計",
+    }
+}
+
 fn store_config_to_dto(state: State<'_, AppState>) -> Result<AppConfigDto, String> {
     let store = state
         .config_store
@@ -316,12 +909,48 @@ fn store_config_to_dto(state: State<'_, AppState>) -> Result<AppConfigDto, Strin
     Ok(store.config().clone().into())
 }
 
-fn verify_replace_permissions(watch_folders: &[PathBuf]) -> Result<(), String> {
+/// Deleting needs the watch folders only; the Trash is probed for `trash` mode.
+fn verify_replace_permissions(watch_folders: &[PathBuf], mode: ReplaceMode) -> Result<(), String> {
     let mut issues = Vec::new();
 
-    let trash = user_trash_dir()?;
-    if let Err(err) = verify_writable_dir(&trash, "trash") {
-        issues.push(err);
+    // Folders on other volumes may trash into that volume's own trash.
+    let mut checked_trash = HashSet::new();
+    for folder in watch_folders {
+        if let Err(err) = verify_writable_dir(folder, "watch folder") {
+            issues.push(err);
+        }
+        if mode.is_permanent() {
+            continue;
+        }
+        match trash::trash_dir_for(folder) {
+            Ok(trash) if checked_trash.insert(trash.clone()) => {
+                if let Err(err) = verify_writable_dir(&trash, "trash") {
+                    issues.push(err);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => issues.push(format!("trash: {err}")),
+        }
+    }
+
+    if !issues.is_empty() {
+        return Err(issues.join("\n"));
+    }
+    Ok(())
+}
+
+/// A missing archive root is created with the first archived file, so only
+/// its nearest existing ancestor has to be writable.
+fn verify_archive_permissions(watch_folders: &[PathBuf], root: &Path) -> Result<(), String> {
+    let mut issues = Vec::new();
+
+    match root.ancestors().find(|dir| dir.exists()) {
+        Some(existing) => {
+            if let Err(err) = verify_writable_dir(existing, "archive") {
+                issues.push(err);
+            }
+        }
+        None => issues.push(format!("archive: missing ({})", root.display())),
     }
     for folder in watch_folders {
         if let Err(err) = verify_writable_dir(folder, "watch folder") {
@@ -335,21 +964,26 @@ fn verify_replace_permissions(watch_folders: &[PathBuf]) -> Result<(), String> {
     Ok(())
 }
 
+/// Falls back to `coexist` when the source could not be moved away later.
 fn apply_replace_permission_policy(mut config: AppConfig) -> (AppConfig, Option<String>) {
-    if matches!(config.output_policy, OutputPolicy::Replace) {
-        if let Err(err) = verify_replace_permissions(&config.watch_folders) {
-            config.output_policy = OutputPolicy::Coexist;
-            return (config, Some(format!("Replace unavailable\n{err}\nFallback: coexist")));
+    let checked = match &config.output_policy {
+        OutputPolicy::Coexist => return (config, None),
+        OutputPolicy::Replace => {
+            verify_replace_permissions(&config.watch_folders, config.replace_mode)
+                .map_err(|err| ("Replace", err))
         }
+        OutputPolicy::Archive(archive) => {
+            verify_archive_permissions(&config.watch_folders, &archive.root)
+                .map_err(|err| ("Archive", err))
+        }
+    };
+    if let Err((mode, err)) = checked {
+        config.output_policy = OutputPolicy::Coexist;
+        return (config, Some(format!("{mode} unavailable\n{err}\nFallback: coexist")));
     }
     (config, None)
 }
 
-fn user_trash_dir() -> Result<PathBuf, String> {
-    let home = std::env::var_os("HOME").ok_or_else(|| "HOME is not set".to_string())?;
-    Ok(PathBuf::from(home).join(".Trash"))
-}
-
 fn verify_writable_dir(dir: &Path, label: &str) -> Result<(), String> {
     if !dir.exists() {
         return Err(format!("{label}: missing ({})", dir.display()));
@@ -377,6 +1011,7 @@ fn verify_writable_dir(dir: &Path, label: &str) -> Result<(), String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::ArchiveLayout;
 
     #[test]
     fn replace_policy_falls_back_to_coexist_on_permission_probe_failure() {
@@ -384,10 +1019,7 @@ mod tests {
             watch_folders: vec![PathBuf::from("/path/does/not/exist")],
             recursive_watch: false,
             output_policy: OutputPolicy::Replace,
-            jpeg_quality: 92,
-            rescan_interval_secs: 60,
-            paused: false,
-            locale: AppLocale::En,
+            ..AppConfig::default()
         };
 
         let (updated, warning) = apply_replace_permission_policy(config);
@@ -395,16 +1027,63 @@ mod tests {
         assert!(warning.is_some());
     }
 
+    #[test]
+    fn archive_root_must_be_outside_watch_folders_and_writable() {
+        let watch_folders = vec![PathBuf::from("/tmp/inbox")];
+        let err = validate_archive_root(Path::new("/tmp/inbox/archive"), &watch_folders)
+            .expect_err("inside watch folder");
+        assert!(err.contains("must not be inside watch folder"));
+        assert!(validate_archive_root(Path::new("archive"), &watch_folders).is_err());
+        assert!(validate_archive_root(Path::new("/tmp/archive"), &watch_folders).is_ok());
+
+        let config = AppConfig {
+            watch_folders: vec![PathBuf::from("/path/does/not/exist")],
+            output_policy: OutputPolicy::Archive(ArchiveOptions {
+                root: PathBuf::from("/dev/null/archive"),
+                layout: ArchiveLayout::DateBuckets,
+            }),
+            ..AppConfig::default()
+        };
+        let (updated, warning) = apply_replace_permission_policy(config);
+        assert!(matches!(updated.output_policy, OutputPolicy::Coexist));
+        assert!(warning.is_some_and(|warning| warning.starts_with("Archive unavailable")));
+
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let missing = std::env::temp_dir().join(format!("heic-ready_{stamp}_archive"));
+        assert!(verify_archive_permissions(&[], &missing.join("2025")).is_ok());
+        assert!(!missing.exists(), "checking must not create the archive root");
+    }
+
+    #[test]
+    fn permanent_delete_needs_confirmation_and_skips_trash_probe() {
+        let mut dto = AppConfigDto::from(AppConfig {
+            output_policy: OutputPolicy::Replace,
+            replace_mode: ReplaceMode::Delete,
+            ..AppConfig::default()
+        });
+        let err = AppConfig::try_from(dto.clone()).expect_err("unconfirmed");
+        assert!(err.contains("confirm_permanent_delete"));
+        dto.confirm_permanent_delete = true;
+        assert!(AppConfig::try_from(dto).is_ok());
+
+        let missing = [PathBuf::from("/path/does/not/exist")];
+        let err = verify_replace_permissions(&missing, ReplaceMode::SecureDelete)
+            .expect_err("missing folder");
+        assert!(err.contains("watch folder") && !err.contains("trash"));
+        let writable = [std::env::temp_dir()];
+        assert!(verify_replace_permissions(&writable, ReplaceMode::Delete).is_ok());
+    }
+
     #[test]
     fn coexist_policy_is_unchanged() {
         let config = AppConfig {
             watch_folders: vec![PathBuf::from("/path/does/not/exist")],
             recursive_watch: false,
             output_policy: OutputPolicy::Coexist,
-            jpeg_quality: 92,
-            rescan_interval_secs: 60,
-            paused: false,
-            locale: AppLocale::En,
+            ..AppConfig::default()
         };
 
         let (updated, warning) = apply_replace_permission_policy(config.clone());
@@ -420,6 +1099,101 @@ mod tests {
         assert_eq!(path, PathBuf::from("/tmp/heic-ready_perm_test"));
     }
 
+    #[test]
+    fn folder_options_dto_rejects_empty_resize_limits() {
+        let dto = FolderOptionsDto {
+            path: "/tmp/cms/".to_string(),
+            resize: Some(ResizeOptions {
+                mode: ResizeMode::MaxDimensions {
+                    max_width: None,
+                    max_height: None,
+                },
+                only_shrink: true,
+                filter: Default::default(),
+            }),
+            ..FolderOptionsDto::default()
+        };
+        let err = FolderOptions::try_from(dto).expect_err("must fail");
+        assert!(err.contains("max_width or max_height"));
+    }
+
+    #[test]
+    fn folder_options_dto_rejects_inverted_target_quality_bounds() {
+        let dto = FolderOptionsDto {
+            path: "/tmp/mail".to_string(),
+            resize: None,
+            target_size: Some(TargetSizeOptions {
+                max_bytes: 1_500_000,
+                min_quality: 90,
+                max_quality: 60,
+            }),
+            ..FolderOptionsDto::default()
+        };
+        let err = FolderOptions::try_from(dto).expect_err("must fail");
+        assert!(err.contains("min_quality <= max_quality"));
+    }
+
+    #[test]
+    fn folder_options_dto_rejects_missing_icc_profile() {
+        let dto = FolderOptionsDto {
+            path: "/tmp/web".to_string(),
+            color: ColorPolicy::ConvertToProfile {
+                icc_path: PathBuf::from("/path/does/not/exist.icc"),
+            },
+            ..FolderOptionsDto::default()
+        };
+        let err = FolderOptions::try_from(dto).expect_err("must fail");
+        assert!(err.contains("icc_path not found"));
+    }
+
+    #[test]
+    fn folder_options_dto_rejects_empty_input_formats() {
+        let dto = FolderOptionsDto {
+            path: "/tmp/inbox".to_string(),
+            input_formats: Some(Vec::new()),
+            ..FolderOptionsDto::default()
+        };
+        let err = FolderOptions::try_from(dto).expect_err("must fail");
+        assert!(err.contains("input_formats must not be empty"));
+    }
+
+    #[test]
+    fn folder_options_dto_rejects_bad_thumbnail_options() {
+        let with_thumbnails = |sizes: Vec<u32>, location: ThumbnailLocation| FolderOptionsDto {
+            path: "/tmp/web".to_string(),
+            thumbnails: Some(ThumbnailOptions { sizes, location }),
+            ..FolderOptionsDto::default()
+        };
+
+        let err = FolderOptions::try_from(with_thumbnails(Vec::new(), ThumbnailLocation::Subfolder))
+            .expect_err("must fail");
+        assert!(err.contains("sizes must not be empty"));
+        let err = FolderOptions::try_from(with_thumbnails(
+            vec![256, 8192],
+            ThumbnailLocation::Subfolder,
+        ))
+        .expect_err("must fail");
+        assert!(err.contains("thumbnail size 8192"));
+        let err = FolderOptions::try_from(with_thumbnails(
+            vec![256],
+            ThumbnailLocation::SeparateRoot {
+                root: PathBuf::from("previews"),
+            },
+        ))
+        .expect_err("must fail");
+        assert!(err.contains("root must be absolute"));
+
+        let options = FolderOptions::try_from(with_thumbnails(
+            vec![256, 1024],
+            ThumbnailLocation::Subfolder,
+        ))
+        .expect("valid thumbnails");
+        assert_eq!(
+            options.thumbnails.map(|thumbnails| thumbnails.sizes),
+            Some(vec![256, 1024])
+        );
+    }
+
     #[test]
     fn normalize_watch_folder_path_rejects_relative_path() {
         let err = normalize_watch_folder_path("tmp/heic-ready").expect_err("must fail");
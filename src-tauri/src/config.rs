@@ -7,12 +7,37 @@ use serde::{Deserialize, Serialize};
 
 const APP_CONFIG_SUBDIR: &str = "heic-ready";
 const CONFIG_FILE_NAME: &str = "config.json";
+const LEDGER_FILE_NAME: &str = "ledger.json";
+const TRASH_JOURNAL_FILE_NAME: &str = "trash_journal.json";
+const STATS_FILE_NAME: &str = "stats.json";
+const HISTORY_SUBDIR: &str = "logs";
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum OutputPolicy {
     Coexist,
     Replace,
+    /// Move the source into an archive folder once its JPEG is verified.
+    Archive(ArchiveOptions),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ArchiveOptions {
+    pub root: PathBuf,
+    #[serde(default)]
+    pub layout: ArchiveLayout,
+}
+
+/// How archived sources are arranged below the archive root.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveLayout {
+    #[default]
+    Flat,
+    /// The source's folder below its watch folder.
+    MirrorSubfolders,
+    /// `YYYY/MM` of the capture date, or of the modification date without EXIF.
+    DateBuckets,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -22,17 +47,331 @@ pub enum AppLocale {
     Ja,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ResizeMode {
+    MaxLongEdge {
+        pixels: u32,
+    },
+    MaxDimensions {
+        #[serde(default)]
+        max_width: Option<u32>,
+        #[serde(default)]
+        max_height: Option<u32>,
+    },
+    Percentage {
+        percent: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResampleFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos3,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ResizeOptions {
+    pub mode: ResizeMode,
+    #[serde(default = "default_only_shrink")]
+    pub only_shrink: bool,
+    #[serde(default)]
+    pub filter: ResampleFilter,
+}
+
+/// Source formats a folder picks up. The extension only preselects candidate
+/// files; the format is decided by the file's content.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum InputFormat {
+    Heic,
+    Avif,
+    Dng,
+    Webp,
+    Tiff,
+}
+
+impl InputFormat {
+    /// What a folder without `input_formats` accepts.
+    pub const DEFAULT: &'static [InputFormat] = &[InputFormat::Heic];
+
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Heic => &["heic", "heif", "heics", "avcs"],
+            Self::Avif => &["avif"],
+            Self::Dng => &["dng"],
+            Self::Webp => &["webp"],
+            Self::Tiff => &["tif", "tiff"],
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Heic => "HEIC",
+            Self::Avif => "AVIF",
+            Self::Dng => "DNG",
+            Self::Webp => "WebP",
+            Self::Tiff => "TIFF",
+        }
+    }
+}
+
+/// Which images of a multi-image HEIF container (bursts, `.heics` sequences,
+/// edited + original) are exported.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MultiImagePolicy {
+    #[default]
+    Primary,
+    All,
+}
+
+/// How HDR content (gain maps, HDR transfer curves) is carried into the JPEG.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HdrPolicy {
+    /// Keep the SDR base image as stored and drop any gain map.
+    #[default]
+    BaseImage,
+    /// Let the decoder tone-map HDR content down to SDR.
+    ToneMapSdr,
+    /// Keep the SDR base image and embed the gain map as Ultra HDR.
+    UltraHdr,
+}
+
+/// Color space of the JPEG relative to the decoded source.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ColorPolicy {
+    /// Keep the source pixels and embed the source profile.
+    #[default]
+    Preserve,
+    ConvertToSrgb,
+    ConvertToProfile { icc_path: PathBuf },
+}
+
+/// How `replace` gets rid of the source once its JPEG is written.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaceMode {
+    /// Move it to the Trash, where it can be restored.
+    #[default]
+    Trash,
+    /// Unlink it right away.
+    Delete,
+    /// Overwrite its contents with zeros before unlinking it. Best effort:
+    /// SSDs and copy-on-write file systems may keep the old blocks.
+    SecureDelete,
+}
+
+impl ReplaceMode {
+    /// The source cannot be recovered from the Trash afterwards.
+    pub fn is_permanent(self) -> bool {
+        !matches!(self, Self::Trash)
+    }
+}
+
+/// What happens to the `.mov` half of a Live Photo once its HEIC is converted.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LivePhotoPolicy {
+    #[default]
+    Keep,
+    /// Move the video wherever the HEIC goes: the Trash in `replace` mode,
+    /// its archive folder in `archive` mode.
+    TrashAlongside,
+    /// Rename the video to the JPEG's stem, e.g. `IMG_0001 (1).MOV`.
+    RenameToOutput,
+    /// Move the video into a `Live Photos` folder next to the source.
+    MoveToSidecarFolder,
+}
+
+/// Replaces the fixed `jpeg_quality` with the highest quality in
+/// `min_quality..=max_quality` whose output fits in `max_bytes`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TargetSizeOptions {
+    pub max_bytes: u64,
+    #[serde(default = "default_target_min_quality")]
+    pub min_quality: u8,
+    #[serde(default = "default_target_max_quality")]
+    pub max_quality: u8,
+}
+
+/// Where thumbnails are written.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ThumbnailLocation {
+    /// A `.thumbs` folder next to the JPEG.
+    #[default]
+    Subfolder,
+    /// `root`, mirroring the output's folder below its watch folder.
+    SeparateRoot { root: PathBuf },
+}
+
+/// Small JPEG previews rendered from the same decode as the main output.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ThumbnailOptions {
+    /// Long edge of each thumbnail in pixels, e.g. `[256, 1024]`.
+    pub sizes: Vec<u32>,
+    #[serde(default)]
+    pub location: ThumbnailLocation,
+}
+
+/// Conversion settings that apply to files below `path` only.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FolderOptions {
+    pub path: PathBuf,
+    /// `None` accepts `InputFormat::DEFAULT`.
+    #[serde(default)]
+    pub input_formats: Option<Vec<InputFormat>>,
+    #[serde(default)]
+    pub resize: Option<ResizeOptions>,
+    #[serde(default)]
+    pub target_size: Option<TargetSizeOptions>,
+    #[serde(default)]
+    pub multi_image: MultiImagePolicy,
+    #[serde(default)]
+    pub hdr: HdrPolicy,
+    /// Also write the depth (or disparity) map as `<output>_depth.png`.
+    #[serde(default)]
+    pub export_depth_map: bool,
+    #[serde(default)]
+    pub color: ColorPolicy,
+    #[serde(default)]
+    pub thumbnails: Option<ThumbnailOptions>,
+}
+
+/// Rotation and retention of the JSON-lines history in the app data dir.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogFileOptions {
+    /// The active file is rotated once it reaches this size.
+    #[serde(default = "default_log_max_file_mb")]
+    pub max_file_mb: u64,
+    /// ... or once its first record is this old.
+    #[serde(default = "default_log_rotate_after_days")]
+    pub rotate_after_days: u32,
+    /// Rotated files last written longer ago than this are deleted.
+    #[serde(default = "default_log_retention_days")]
+    pub retention_days: u32,
+    /// At most this many rotated files are kept, the oldest go first.
+    #[serde(default = "default_log_max_files")]
+    pub max_files: u32,
+}
+
+impl Default for LogFileOptions {
+    fn default() -> Self {
+        Self {
+            max_file_mb: default_log_max_file_mb(),
+            rotate_after_days: default_log_rotate_after_days(),
+            retention_days: default_log_retention_days(),
+            max_files: default_log_max_files(),
+        }
+    }
+}
+
+/// Optional Prometheus endpoint, served on 127.0.0.1 only.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MetricsOptions {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_metrics_port")]
+    pub port: u16,
+}
+
+impl Default for MetricsOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_metrics_port(),
+        }
+    }
+}
+
+/// Desktop notifications for finished batches and failures.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NotificationOptions {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Failures after a shown one, and finished batches, are combined over
+    /// this many seconds.
+    #[serde(default = "default_notification_coalesce_secs")]
+    pub coalesce_secs: u64,
+}
+
+impl Default for NotificationOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            coalesce_secs: default_notification_coalesce_secs(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct AppConfig {
     pub watch_folders: Vec<PathBuf>,
     pub recursive_watch: bool,
     pub output_policy: OutputPolicy,
+    #[serde(default)]
+    pub replace_mode: ReplaceMode,
+    /// Must be set for a permanent `replace_mode`; without it sources are kept.
+    #[serde(default)]
+    pub confirm_permanent_delete: bool,
     pub jpeg_quality: u8,
+    #[serde(default)]
+    pub normalize_orientation: bool,
+    #[serde(default)]
+    pub verify_outputs: bool,
     #[serde(default = "default_rescan_interval_secs")]
     pub rescan_interval_secs: u64,
+    /// Free space kept on the output volume on top of each job's estimate;
+    /// conversions wait while it is not available.
+    #[serde(default = "default_min_free_space_mb")]
+    pub min_free_space_mb: u64,
     pub paused: bool,
     #[serde(default = "default_locale")]
     pub locale: AppLocale,
+    #[serde(default)]
+    pub folder_options: Vec<FolderOptions>,
+    #[serde(default)]
+    pub live_photo_policy: LivePhotoPolicy,
+    #[serde(default)]
+    pub log_file: LogFileOptions,
+    #[serde(default)]
+    pub metrics: MetricsOptions,
+    #[serde(default)]
+    pub notifications: NotificationOptions,
+}
+
+impl AppConfig {
+    /// Returns the options of the most specific folder containing `path`.
+    pub fn folder_options_for(&self, path: &Path) -> Option<&FolderOptions> {
+        self.folder_options
+            .iter()
+            .filter(|options| path.starts_with(&options.path))
+            .max_by_key(|options| options.path.components().count())
+    }
+
+    /// `dir` relative to the watch folder containing it; empty outside them.
+    pub fn path_below_watch_folder<'a>(&self, dir: &'a Path) -> &'a Path {
+        self.watch_folders
+            .iter()
+            .filter_map(|folder| dir.strip_prefix(folder).ok())
+            .min_by_key(|relative| relative.components().count())
+            .unwrap_or_else(|| Path::new(""))
+    }
+
+    /// Input formats accepted for files at `path`.
+    pub fn input_formats_for(&self, path: &Path) -> &[InputFormat] {
+        self.folder_options_for(path)
+            .and_then(|options| options.input_formats.as_deref())
+            .unwrap_or(InputFormat::DEFAULT)
+    }
 }
 
 impl Default for AppConfig {
@@ -41,10 +380,20 @@ impl Default for AppConfig {
             watch_folders: Vec::new(),
             recursive_watch: false,
             output_policy: OutputPolicy::Coexist,
+            replace_mode: ReplaceMode::Trash,
+            confirm_permanent_delete: false,
             jpeg_quality: 92,
+            normalize_orientation: false,
+            verify_outputs: false,
             rescan_interval_secs: default_rescan_interval_secs(),
+            min_free_space_mb: default_min_free_space_mb(),
             paused: false,
             locale: default_locale(),
+            folder_options: Vec::new(),
+            live_photo_policy: LivePhotoPolicy::Keep,
+            log_file: LogFileOptions::default(),
+            metrics: MetricsOptions::default(),
+            notifications: NotificationOptions::default(),
         }
     }
 }
@@ -53,10 +402,50 @@ const fn default_rescan_interval_secs() -> u64 {
     60
 }
 
+pub(crate) const fn default_min_free_space_mb() -> u64 {
+    1024
+}
+
+const fn default_log_max_file_mb() -> u64 {
+    10
+}
+
+const fn default_log_rotate_after_days() -> u32 {
+    7
+}
+
+const fn default_log_retention_days() -> u32 {
+    90
+}
+
+const fn default_log_max_files() -> u32 {
+    20
+}
+
+pub(crate) const fn default_metrics_port() -> u16 {
+    9464
+}
+
+const fn default_notification_coalesce_secs() -> u64 {
+    10
+}
+
 const fn default_locale() -> AppLocale {
     AppLocale::En
 }
 
+const fn default_only_shrink() -> bool {
+    true
+}
+
+const fn default_target_min_quality() -> u8 {
+    40
+}
+
+const fn default_target_max_quality() -> u8 {
+    95
+}
+
 pub struct ConfigStore {
     path: PathBuf,
     config: AppConfig,
@@ -103,6 +492,21 @@ impl ConfigStore {
         &self.path
     }
 
+    /// The thumbnail ledger lives next to the config file.
+    pub fn ledger_path(&self) -> PathBuf {
+        self.path.with_file_name(LEDGER_FILE_NAME)
+    }
+
+    /// So is the record of sources moved to the trash, used to undo them.
+    pub fn trash_journal_path(&self) -> PathBuf {
+        self.path.with_file_name(TRASH_JOURNAL_FILE_NAME)
+    }
+
+    /// And the conversion statistics.
+    pub fn stats_path(&self) -> PathBuf {
+        self.path.with_file_name(STATS_FILE_NAME)
+    }
+
     pub fn set_paused(&mut self, paused: bool) {
         self.config.paused = paused;
     }
@@ -128,7 +532,18 @@ fn config_file_path(app_config_dir: &Path) -> PathBuf {
         .join(CONFIG_FILE_NAME)
 }
 
-fn atomic_write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+/// The trash journal of the config in `app_config_dir`, for callers that
+/// do not load the config itself.
+pub(crate) fn trash_journal_path_in(app_config_dir: &Path) -> PathBuf {
+    config_file_path(app_config_dir).with_file_name(TRASH_JOURNAL_FILE_NAME)
+}
+
+/// Where the history log lives inside the app data dir.
+pub(crate) fn history_dir_in(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(APP_CONFIG_SUBDIR).join(HISTORY_SUBDIR)
+}
+
+pub(crate) fn atomic_write(path: &Path, bytes: &[u8]) -> io::Result<()> {
     let tmp_path = tmp_path_for(path);
     fs::write(&tmp_path, bytes)?;
     fs::rename(&tmp_path, path)?;
@@ -187,10 +602,54 @@ mod tests {
             watch_folders: vec![PathBuf::from("/tmp/drop")],
             recursive_watch: true,
             output_policy: OutputPolicy::Replace,
+            replace_mode: ReplaceMode::SecureDelete,
+            confirm_permanent_delete: true,
             jpeg_quality: 88,
+            normalize_orientation: true,
+            verify_outputs: true,
             rescan_interval_secs: 120,
+            min_free_space_mb: 256,
             paused: true,
             locale: AppLocale::Ja,
+            folder_options: vec![FolderOptions {
+                path: PathBuf::from("/tmp/drop/cms"),
+                input_formats: Some(vec![InputFormat::Heic, InputFormat::Dng]),
+                resize: Some(ResizeOptions {
+                    mode: ResizeMode::MaxLongEdge { pixels: 4096 },
+                    only_shrink: true,
+                    filter: ResampleFilter::CatmullRom,
+                }),
+                target_size: Some(TargetSizeOptions {
+                    max_bytes: 1_500_000,
+                    min_quality: 50,
+                    max_quality: 90,
+                }),
+                multi_image: MultiImagePolicy::All,
+                hdr: HdrPolicy::UltraHdr,
+                export_depth_map: true,
+                color: ColorPolicy::ConvertToSrgb,
+                thumbnails: Some(ThumbnailOptions {
+                    sizes: vec![256, 1024],
+                    location: ThumbnailLocation::SeparateRoot {
+                        root: PathBuf::from("/tmp/previews"),
+                    },
+                }),
+            }],
+            live_photo_policy: LivePhotoPolicy::MoveToSidecarFolder,
+            log_file: LogFileOptions {
+                max_file_mb: 5,
+                rotate_after_days: 1,
+                retention_days: 30,
+                max_files: 4,
+            },
+            metrics: MetricsOptions {
+                enabled: true,
+                port: 9100,
+            },
+            notifications: NotificationOptions {
+                enabled: true,
+                coalesce_secs: 30,
+            },
         };
         fs::write(
             &path,
@@ -236,4 +695,92 @@ mod tests {
         let _ = fs::remove_dir_all(root);
     }
 
+    #[test]
+    fn folder_options_pick_most_specific_folder() {
+        let resize = |pixels| {
+            Some(ResizeOptions {
+                mode: ResizeMode::MaxLongEdge { pixels },
+                only_shrink: true,
+                filter: ResampleFilter::default(),
+            })
+        };
+        let config = AppConfig {
+            folder_options: vec![
+                FolderOptions {
+                    path: PathBuf::from("/drop"),
+                    resize: resize(4096),
+                    ..FolderOptions::default()
+                },
+                FolderOptions {
+                    path: PathBuf::from("/drop/thumbs"),
+                    resize: resize(512),
+                    ..FolderOptions::default()
+                },
+            ],
+            ..AppConfig::default()
+        };
+
+        let nested = config
+            .folder_options_for(Path::new("/drop/thumbs/IMG_0001.heic"))
+            .expect("nested options");
+        assert_eq!(nested.path, PathBuf::from("/drop/thumbs"));
+        let top = config
+            .folder_options_for(Path::new("/drop/IMG_0001.heic"))
+            .expect("top options");
+        assert_eq!(top.path, PathBuf::from("/drop"));
+        assert!(config
+            .folder_options_for(Path::new("/dropbox/IMG_0001.heic"))
+            .is_none());
+    }
+
+    #[test]
+    fn output_policy_accepts_plain_names_and_archive_options() {
+        let parse = |json: &str| serde_json::from_str::<OutputPolicy>(json).expect("parse");
+        assert_eq!(parse("\"replace\""), OutputPolicy::Replace);
+        assert_eq!(
+            parse(r#"{ "archive": { "root": "/Volumes/Originals" } }"#),
+            OutputPolicy::Archive(ArchiveOptions {
+                root: PathBuf::from("/Volumes/Originals"),
+                layout: ArchiveLayout::Flat,
+            })
+        );
+        assert_eq!(
+            parse(r#"{ "archive": { "root": "/a", "layout": "date_buckets" } }"#),
+            OutputPolicy::Archive(ArchiveOptions {
+                root: PathBuf::from("/a"),
+                layout: ArchiveLayout::DateBuckets,
+            })
+        );
+    }
+
+    #[test]
+    fn input_formats_default_to_heic_outside_configured_folders() {
+        let config = AppConfig {
+            folder_options: vec![FolderOptions {
+                path: PathBuf::from("/inbox"),
+                input_formats: Some(vec![InputFormat::Heic, InputFormat::Avif]),
+                ..FolderOptions::default()
+            }],
+            ..AppConfig::default()
+        };
+
+        assert_eq!(
+            config.input_formats_for(Path::new("/inbox/render.avif")),
+            &[InputFormat::Heic, InputFormat::Avif]
+        );
+        assert_eq!(
+            config.input_formats_for(Path::new("/drop/IMG_0001.heic")),
+            InputFormat::DEFAULT
+        );
+    }
+
+    #[test]
+    fn resize_options_default_to_shrink_only_lanczos() {
+        let options: ResizeOptions =
+            serde_json::from_str(r#"{ "mode": { "kind": "max_long_edge", "pixels": 4096 } }"#)
+                .expect("parse resize options");
+
+        assert!(options.only_shrink);
+        assert_eq!(options.filter, ResampleFilter::Lanczos3);
+    }
 }
@@ -0,0 +1,1648 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Cursor, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, metadata::Orientation, DynamicImage,
+    GenericImageView, GrayImage, ImageDecoder, ImageEncoder, ImageFormat, ImageReader,
+};
+use serde::Deserialize;
+
+use crate::{
+    color,
+    config::{
+        AppConfig, ColorPolicy, HdrPolicy, InputFormat, MultiImagePolicy, ResampleFilter,
+        ResizeMode, ResizeOptions, TargetSizeOptions,
+    },
+    error::ConvertError,
+    ultra_hdr,
+};
+
+const EXIF_ORIENTATION_TAG: u16 = 0x0112;
+const EXIF_TYPE_SHORT: u16 = 3;
+const THUMBNAIL_QUALITY: u8 = 85;
+
+// CoreVideo pixel formats ImageIO reports for auxiliary images.
+const PIXEL_FORMAT_ONE_COMPONENT_8: u32 = u32::from_be_bytes(*b"L008");
+const PIXEL_FORMAT_ONE_COMPONENT_16: u32 = u32::from_be_bytes(*b"L016");
+const PIXEL_FORMAT_DISPARITY_FLOAT16: u32 = u32::from_be_bytes(*b"hdis");
+const PIXEL_FORMAT_DISPARITY_FLOAT32: u32 = u32::from_be_bytes(*b"fdis");
+const PIXEL_FORMAT_DEPTH_FLOAT16: u32 = u32::from_be_bytes(*b"hdep");
+const PIXEL_FORMAT_DEPTH_FLOAT32: u32 = u32::from_be_bytes(*b"fdep");
+
+static DECODE_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Exports the primary (`argv[2] == "primary"`) or every top-level image of
+/// `argv[0]` as `argv[1]-<index>.png` with its orientation applied, tone-mapped
+/// to SDR when `argv[3] == "sdr"`, and returns the image count.
+const IMAGE_IO_EXPORT_SCRIPT: &str = r#"ObjC.import("Foundation");
+ObjC.import("ImageIO");
+function run(argv) {
+  const source = $.CGImageSourceCreateWithURL($.NSURL.fileURLWithPath(argv[0]), $());
+  if (!source || source.isNil()) {
+    throw new Error("failed to open image source");
+  }
+  const settings = {
+    kCGImageSourceCreateThumbnailFromImageAlways: true,
+    kCGImageSourceCreateThumbnailWithTransform: true
+  };
+  if (argv[3] === "sdr") {
+    settings.kCGImageSourceDecodeRequest = "kCGImageSourceDecodeToSDR";
+  }
+  const options = $(settings);
+  const indices = argv[2] === "primary"
+    ? [$.CGImageSourceGetPrimaryImageIndex(source)]
+    : [...Array($.CGImageSourceGetCount(source)).keys()];
+  for (let index = 0; index < indices.length; index++) {
+    const image = $.CGImageSourceCreateThumbnailAtIndex(source, indices[index], options);
+    if (!image || image.isNil()) {
+      throw new Error("failed to decode image " + indices[index]);
+    }
+    const url = $.NSURL.fileURLWithPath(argv[1] + "-" + index + ".png");
+    const destination = $.CGImageDestinationCreateWithURL(url, "public.png", 1, $());
+    $.CGImageDestinationAddImage(destination, image, $());
+    if (!$.CGImageDestinationFinalize(destination)) {
+      throw new Error("failed to write image " + index);
+    }
+  }
+  return indices.length;
+}"#;
+
+/// Writes the first auxiliary image of the primary image of `argv[0]` whose
+/// type is listed in `argv[2..]` to `argv[1]` as raw pixels and prints its
+/// description as JSON, or prints nothing when there is none.
+const IMAGE_IO_AUXILIARY_SCRIPT: &str = r#"ObjC.import("Foundation");
+ObjC.import("ImageIO");
+function run(argv) {
+  const source = $.CGImageSourceCreateWithURL($.NSURL.fileURLWithPath(argv[0]), $());
+  if (!source || source.isNil()) {
+    throw new Error("failed to open image source");
+  }
+  const index = $.CGImageSourceGetPrimaryImageIndex(source);
+  for (const type of argv.slice(2)) {
+    const ref = $.CGImageSourceCopyAuxiliaryDataInfoAtIndex(source, index, type);
+    if (!ref || ref.isNil()) {
+      continue;
+    }
+    const info = ObjC.castRefToObject(ref);
+    const description = info.objectForKey("kCGImageAuxiliaryDataInfoDataDescription");
+    if (!info.objectForKey("kCGImageAuxiliaryDataInfoData").writeToFileAtomically(argv[1], true)) {
+      throw new Error("failed to write auxiliary data");
+    }
+    let headroom = null;
+    const metadata = info.objectForKey("kCGImageAuxiliaryDataInfoMetadata");
+    if (metadata && !metadata.isNil()) {
+      const tag = $.CGImageMetadataCopyTagWithPath(metadata, $(), "HDRGainMap:HDRGainMapHeadroom");
+      if (tag && !tag.isNil()) {
+        headroom = Number(ObjC.unwrap(ObjC.castRefToObject($.CGImageMetadataTagCopyValue(tag))));
+      }
+    }
+    return JSON.stringify({
+      width: Number(ObjC.unwrap(description.objectForKey("Width"))),
+      height: Number(ObjC.unwrap(description.objectForKey("Height"))),
+      bytes_per_row: Number(ObjC.unwrap(description.objectForKey("BytesPerRow"))),
+      pixel_format: Number(ObjC.unwrap(description.objectForKey("PixelFormat"))),
+      headroom: headroom
+    });
+  }
+  return "";
+}"#;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ConvertOptions {
+    pub(crate) jpeg_quality: u8,
+    pub(crate) normalize_orientation: bool,
+    pub(crate) resize: Option<ResizeOptions>,
+    pub(crate) target_size: Option<TargetSizeOptions>,
+    pub(crate) multi_image: MultiImagePolicy,
+    pub(crate) hdr: HdrPolicy,
+    pub(crate) export_depth_map: bool,
+    pub(crate) color: ColorPolicy,
+    /// Long edges of the thumbnails rendered alongside the JPEG.
+    pub(crate) thumbnail_sizes: Vec<u32>,
+}
+
+impl ConvertOptions {
+    pub(crate) fn for_source(config: &AppConfig, input_path: &Path) -> Self {
+        let folder = config.folder_options_for(input_path);
+        Self {
+            jpeg_quality: config.jpeg_quality,
+            normalize_orientation: config.normalize_orientation,
+            resize: folder.and_then(|options| options.resize),
+            target_size: folder.and_then(|options| options.target_size),
+            multi_image: folder
+                .map(|options| options.multi_image)
+                .unwrap_or_default(),
+            hdr: folder.map(|options| options.hdr).unwrap_or_default(),
+            export_depth_map: folder.is_some_and(|options| options.export_depth_map),
+            color: folder
+                .map(|options| options.color.clone())
+                .unwrap_or_default(),
+            thumbnail_sizes: folder
+                .and_then(|options| options.thumbnails.as_ref())
+                .map(|thumbnails| thumbnails.sizes.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ConvertReport {
+    /// As displayed, i.e. with the EXIF orientation applied, so a rotated
+    /// photo reports the same size whether or not it was normalized.
+    pub(crate) source_dimensions: (u32, u32),
+    pub(crate) output_dimensions: (u32, u32),
+    pub(crate) jpeg_quality: u8,
+    /// Set when even `min_quality` could not bring the output under the target.
+    pub(crate) missed_target_bytes: Option<u64>,
+    /// Whether the output carries an Ultra HDR gain map.
+    pub(crate) ultra_hdr: bool,
+}
+
+/// One encoded JPEG, not yet written to disk.
+pub(crate) struct ConvertedImage {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) sidecars: Vec<Sidecar>,
+    pub(crate) report: ConvertReport,
+}
+
+/// Extra files derived from the same decode, written next to the JPEG.
+pub(crate) struct Sidecar {
+    pub(crate) kind: SidecarKind,
+    pub(crate) bytes: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SidecarKind {
+    DepthMap,
+    Thumbnail { size: u32 },
+}
+
+/// Decoders that turn a source file into pixels for the shared pipeline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DecodeBackend {
+    Sips,
+}
+
+impl DecodeBackend {
+    const ALL: [Self; 1] = [Self::Sips];
+
+    /// The first backend that can decode `format`.
+    pub(crate) fn for_format(format: InputFormat) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|backend| backend.decodes().contains(&format))
+    }
+
+    /// Formats this backend can turn into pixels.
+    pub(crate) fn decodes(self) -> &'static [InputFormat] {
+        match self {
+            // sips and ImageIO read ProRAW DNG and AVIF as well (AVIF from
+            // macOS 13).
+            Self::Sips => &[
+                InputFormat::Heic,
+                InputFormat::Avif,
+                InputFormat::Dng,
+                InputFormat::Webp,
+                InputFormat::Tiff,
+            ],
+        }
+    }
+
+    /// Decodes the primary image or every top-level image in container order.
+    fn decode(
+        self,
+        input_path: &Path,
+        selection: MultiImagePolicy,
+        tone_map_sdr: bool,
+    ) -> Result<Vec<DecodedImage>, String> {
+        match self {
+            // sips cannot tone-map, so HDR requests go through ImageIO.
+            Self::Sips if selection == MultiImagePolicy::Primary && !tone_map_sdr => {
+                Ok(vec![decode_with_sips(input_path)?])
+            }
+            Self::Sips => decode_with_image_io(input_path, selection, tone_map_sdr),
+        }
+    }
+
+    /// Reads an auxiliary image of the primary image, in stored orientation.
+    fn decode_auxiliary(
+        self,
+        input_path: &Path,
+        kind: AuxiliaryKind,
+    ) -> Result<Option<AuxiliaryImage>, String> {
+        match self {
+            Self::Sips => read_auxiliary_with_image_io(input_path, kind),
+        }
+    }
+
+    fn source_orientation(self, input_path: &Path) -> Orientation {
+        match self {
+            Self::Sips => read_sips_orientation(input_path),
+        }
+    }
+}
+
+/// Pixels as handed over by a backend, plus the orientation that still has to
+/// be applied for them to display upright.
+pub(crate) struct DecodedImage {
+    pub(crate) image: DynamicImage,
+    pub(crate) orientation: Orientation,
+    pub(crate) icc_profile: Option<Vec<u8>>,
+    pub(crate) exif: Option<Vec<u8>>,
+    /// Auxiliary images share the pixel frame of `image`.
+    pub(crate) gain_map: Option<GainMap>,
+    pub(crate) depth_map: Option<DynamicImage>,
+}
+
+pub(crate) struct GainMap {
+    pub(crate) image: DynamicImage,
+    pub(crate) headroom: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AuxiliaryKind {
+    GainMap,
+    DepthMap,
+}
+
+impl AuxiliaryKind {
+    fn image_io_types(self) -> &'static [&'static str] {
+        match self {
+            Self::GainMap => &["kCGImageAuxiliaryDataTypeHDRGainMap"],
+            Self::DepthMap => &[
+                "kCGImageAuxiliaryDataTypeDisparity",
+                "kCGImageAuxiliaryDataTypeDepth",
+            ],
+        }
+    }
+}
+
+/// Raw auxiliary pixels as described by the backend.
+#[derive(Debug, Deserialize)]
+struct AuxiliaryImage {
+    width: u32,
+    height: u32,
+    bytes_per_row: usize,
+    pixel_format: u32,
+    headroom: Option<f32>,
+    #[serde(skip)]
+    data: Vec<u8>,
+}
+
+/// Decodes `input_path` and encodes one JPEG per exported image; a single
+/// image unless `multi_image` asks for the whole container.
+pub(crate) fn convert_source(
+    input_path: &Path,
+    format: InputFormat,
+    options: &ConvertOptions,
+) -> Result<Vec<ConvertedImage>, ConvertError> {
+    let backend = DecodeBackend::for_format(format).ok_or_else(|| {
+        ConvertError::Unsupported(format!("no decoder for {} input", format.label()))
+    })?;
+    let mut decoded = backend
+        .decode(
+            input_path,
+            options.multi_image,
+            options.hdr == HdrPolicy::ToneMapSdr,
+        )
+        .map_err(ConvertError::Decode)?;
+    // Gain and depth maps belong to the primary image only.
+    if options.multi_image == MultiImagePolicy::Primary {
+        if let Some(primary) = decoded.first_mut() {
+            attach_auxiliary_images(backend, input_path, primary, options);
+        }
+    }
+    decoded
+        .into_iter()
+        .map(|image| convert_decoded(input_path, image, options))
+        .collect()
+}
+
+/// Missing or unreadable auxiliary images never fail the conversion; the JPEG
+/// is written without them.
+fn attach_auxiliary_images(
+    backend: DecodeBackend,
+    input_path: &Path,
+    decoded: &mut DecodedImage,
+    options: &ConvertOptions,
+) {
+    let mut wanted = Vec::new();
+    if options.hdr == HdrPolicy::UltraHdr {
+        wanted.push(AuxiliaryKind::GainMap);
+    }
+    if options.export_depth_map {
+        wanted.push(AuxiliaryKind::DepthMap);
+    }
+    // Auxiliary images are stored unrotated; when the backend already turned
+    // the main image upright they need the same transform.
+    let mut upright = None;
+    for kind in wanted {
+        let aux = match backend.decode_auxiliary(input_path, kind) {
+            Ok(Some(aux)) => aux,
+            Ok(None) => {
+                log::debug!("no {kind:?} in {}", input_path.display());
+                continue;
+            }
+            Err(err) => {
+                log::warn!("failed to read {kind:?} of {}: {err}", input_path.display());
+                continue;
+            }
+        };
+        let mut image = match auxiliary_to_gray(&aux) {
+            Ok(image) => DynamicImage::ImageLuma8(image),
+            Err(err) => {
+                log::warn!("skipping {kind:?} of {}: {err}", input_path.display());
+                continue;
+            }
+        };
+        if decoded.orientation == Orientation::NoTransforms {
+            image.apply_orientation(
+                *upright.get_or_insert_with(|| backend.source_orientation(input_path)),
+            );
+        }
+        match kind {
+            AuxiliaryKind::GainMap => match aux.headroom {
+                Some(headroom) if headroom > 1.0 => {
+                    decoded.gain_map = Some(GainMap { image, headroom });
+                }
+                _ => log::info!(
+                    "gain map of {} has no headroom metadata; writing the sdr base image only",
+                    input_path.display()
+                ),
+            },
+            AuxiliaryKind::DepthMap => decoded.depth_map = Some(image),
+        }
+    }
+}
+
+fn convert_decoded(
+    input_path: &Path,
+    decoded: DecodedImage,
+    options: &ConvertOptions,
+) -> Result<ConvertedImage, ConvertError> {
+    let source_dimensions = displayed_dimensions(&decoded);
+    let prepared = apply_pipeline(decoded, options)?;
+    let encoded = match options.target_size.as_ref() {
+        Some(target) => {
+            search_quality_for_target(target, |quality| encode_output(&prepared, quality))
+                .map_err(ConvertError::Encode)?
+        }
+        None => EncodedJpeg {
+            quality: options.jpeg_quality,
+            bytes: encode_output(&prepared, options.jpeg_quality).map_err(ConvertError::Encode)?,
+            missed_target_bytes: None,
+        },
+    };
+    let mut sidecars = Vec::new();
+    if let Some(depth_map) = prepared.depth_map.as_ref() {
+        sidecars.push(Sidecar {
+            kind: SidecarKind::DepthMap,
+            bytes: encode_depth_map(depth_map, prepared.orientation)
+                .map_err(ConvertError::Encode)?,
+        });
+    }
+    sidecars.extend(
+        encode_thumbnails(&prepared, &options.thumbnail_sizes).map_err(ConvertError::Encode)?,
+    );
+    if let Some(target) = options.target_size.as_ref() {
+        log::info!(
+            "target size {} bytes for {}: chose quality {} ({} bytes)",
+            target.max_bytes,
+            input_path.display(),
+            encoded.quality,
+            encoded.bytes.len()
+        );
+    }
+    Ok(ConvertedImage {
+        report: ConvertReport {
+            source_dimensions,
+            output_dimensions: displayed_dimensions(&prepared),
+            jpeg_quality: encoded.quality,
+            missed_target_bytes: encoded.missed_target_bytes,
+            ultra_hdr: prepared.gain_map.is_some(),
+        },
+        bytes: encoded.bytes,
+        sidecars,
+    })
+}
+
+struct EncodedJpeg {
+    quality: u8,
+    bytes: Vec<u8>,
+    missed_target_bytes: Option<u64>,
+}
+
+/// Binary-searches the highest quality whose output fits the target size,
+/// falling back to `min_quality` when nothing fits.
+fn search_quality_for_target(
+    target: &TargetSizeOptions,
+    mut encode: impl FnMut(u8) -> Result<Vec<u8>, String>,
+) -> Result<EncodedJpeg, String> {
+    let min_quality = target.min_quality.clamp(1, 100);
+    let mut low = min_quality;
+    let mut high = target.max_quality.clamp(min_quality, 100);
+    let mut best: Option<(u8, Vec<u8>)> = None;
+
+    while low <= high {
+        let quality = low + (high - low) / 2;
+        let bytes = encode(quality)?;
+        if bytes.len() as u64 <= target.max_bytes {
+            best = Some((quality, bytes));
+            low = quality + 1;
+        } else if quality == min_quality {
+            return Ok(EncodedJpeg {
+                quality,
+                bytes,
+                missed_target_bytes: Some(target.max_bytes),
+            });
+        } else {
+            high = quality - 1;
+        }
+    }
+
+    let (quality, bytes) = best.ok_or_else(|| "target size search did not encode".to_string())?;
+    Ok(EncodedJpeg {
+        quality,
+        bytes,
+        missed_target_bytes: None,
+    })
+}
+
+fn apply_pipeline(
+    mut decoded: DecodedImage,
+    options: &ConvertOptions,
+) -> Result<DecodedImage, ConvertError> {
+    if options.normalize_orientation {
+        normalize_orientation(&mut decoded);
+    }
+    if let Some(resize) = options.resize.as_ref() {
+        resize_image(&mut decoded, resize);
+    }
+    // Color conversion runs last so it touches as few pixels as possible.
+    color::apply_color_policy(&mut decoded.image, &mut decoded.icc_profile, &options.color)?;
+    Ok(decoded)
+}
+
+fn resize_image(decoded: &mut DecodedImage, options: &ResizeOptions) {
+    // Width/height limits refer to the image as displayed, so evaluate them
+    // on swapped axes while a 90/270 degree orientation is still pending.
+    let swapped = swaps_axes(decoded.orientation);
+    let (width, height) = decoded.image.dimensions();
+    let displayed = if swapped {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let Some((target_width, target_height)) = target_dimensions(displayed.0, displayed.1, options)
+    else {
+        return;
+    };
+    let (target_width, target_height) = if swapped {
+        (target_height, target_width)
+    } else {
+        (target_width, target_height)
+    };
+    decoded.image =
+        decoded
+            .image
+            .resize_exact(target_width, target_height, filter_type(options.filter));
+
+    // Keep the gain map's resolution ratio to the main image.
+    if let Some(gain_map) = decoded.gain_map.as_mut() {
+        let scaled = |value: u32, target: u32, source: u32| {
+            ((u64::from(value) * u64::from(target) + u64::from(source) / 2) / u64::from(source))
+                .max(1) as u32
+        };
+        let (map_width, map_height) = gain_map.image.dimensions();
+        gain_map.image = gain_map.image.resize_exact(
+            scaled(map_width, target_width, width),
+            scaled(map_height, target_height, height),
+            filter_type(options.filter),
+        );
+    }
+}
+
+fn target_dimensions(width: u32, height: u32, options: &ResizeOptions) -> Option<(u32, u32)> {
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let scale = match options.mode {
+        ResizeMode::MaxLongEdge { pixels } => f64::from(pixels) / f64::from(width.max(height)),
+        ResizeMode::MaxDimensions {
+            max_width,
+            max_height,
+        } => {
+            let by_width = max_width.map(|value| f64::from(value) / f64::from(width));
+            let by_height = max_height.map(|value| f64::from(value) / f64::from(height));
+            match (by_width, by_height) {
+                (Some(w), Some(h)) => w.min(h),
+                (Some(scale), None) | (None, Some(scale)) => scale,
+                (None, None) => return None,
+            }
+        }
+        ResizeMode::Percentage { percent } => f64::from(percent) / 100.0,
+    };
+    if scale == 1.0 || (options.only_shrink && scale > 1.0) {
+        return None;
+    }
+
+    let scaled = |value: u32| ((f64::from(value) * scale).round() as u32).max(1);
+    let target = (scaled(width), scaled(height));
+    (target != (width, height)).then_some(target)
+}
+
+/// Width and height once the pending orientation is applied.
+fn displayed_dimensions(decoded: &DecodedImage) -> (u32, u32) {
+    let (width, height) = decoded.image.dimensions();
+    if swaps_axes(decoded.orientation) {
+        (height, width)
+    } else {
+        (width, height)
+    }
+}
+
+fn swaps_axes(orientation: Orientation) -> bool {
+    matches!(
+        orientation,
+        Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    )
+}
+
+fn filter_type(filter: ResampleFilter) -> FilterType {
+    match filter {
+        ResampleFilter::Nearest => FilterType::Nearest,
+        ResampleFilter::Triangle => FilterType::Triangle,
+        ResampleFilter::CatmullRom => FilterType::CatmullRom,
+        ResampleFilter::Gaussian => FilterType::Gaussian,
+        ResampleFilter::Lanczos3 => FilterType::Lanczos3,
+    }
+}
+
+fn normalize_orientation(decoded: &mut DecodedImage) {
+    if decoded.orientation != Orientation::NoTransforms {
+        decoded.image.apply_orientation(decoded.orientation);
+        if let Some(gain_map) = decoded.gain_map.as_mut() {
+            gain_map.image.apply_orientation(decoded.orientation);
+        }
+        if let Some(depth_map) = decoded.depth_map.as_mut() {
+            depth_map.apply_orientation(decoded.orientation);
+        }
+        decoded.orientation = Orientation::NoTransforms;
+    }
+    if let Some(exif) = decoded.exif.as_mut() {
+        set_exif_orientation(exif, 1);
+    }
+}
+
+/// The JPEG for `decoded`, as Ultra HDR when a gain map came along.
+fn encode_output(decoded: &DecodedImage, quality: u8) -> Result<Vec<u8>, String> {
+    let primary = encode_jpeg(decoded, quality)?;
+    let Some(gain_map) = decoded.gain_map.as_ref() else {
+        return Ok(primary);
+    };
+    let encoded_map = DynamicImage::ImageLuma8(ultra_hdr::apple_gain_map_to_ultra_hdr(
+        &gain_map.image.to_luma8(),
+        gain_map.headroom,
+    ));
+    let mut map_bytes = Vec::new();
+    encoded_map
+        .write_with_encoder(JpegEncoder::new_with_quality(
+            &mut map_bytes,
+            quality.clamp(1, 100),
+        ))
+        .map_err(|err| format!("failed to encode gain map at quality {quality}: {err}"))?;
+    ultra_hdr::assemble(&primary, &map_bytes, gain_map.headroom)
+}
+
+/// Depth maps carry no EXIF, so any pending orientation is always baked in.
+fn encode_depth_map(depth_map: &DynamicImage, orientation: Orientation) -> Result<Vec<u8>, String> {
+    let mut upright = depth_map.clone();
+    upright.apply_orientation(orientation);
+    let mut bytes = Vec::new();
+    upright
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .map_err(|err| format!("failed to encode depth map: {err}"))?;
+    Ok(bytes)
+}
+
+/// Renders every thumbnail from the prepared image, largest first so each
+/// smaller size is scaled down from the previous one instead of the full image.
+fn encode_thumbnails(decoded: &DecodedImage, sizes: &[u32]) -> Result<Vec<Sidecar>, String> {
+    if sizes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut sorted = sizes.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    sorted.dedup();
+
+    // Thumbnails carry no EXIF, so the orientation is baked in.
+    let mut current = decoded.image.clone();
+    current.apply_orientation(decoded.orientation);
+    let mut thumbnails = Vec::with_capacity(sorted.len());
+    for size in sorted {
+        let (width, height) = current.dimensions();
+        if width.max(height) > size {
+            current = current.resize(size, size, FilterType::Triangle);
+        }
+        let mut bytes = Vec::new();
+        let mut encoder = JpegEncoder::new_with_quality(&mut bytes, THUMBNAIL_QUALITY);
+        if let Some(icc_profile) = decoded.icc_profile.clone() {
+            if let Err(err) = encoder.set_icc_profile(icc_profile) {
+                log::warn!("jpeg encoder dropped thumbnail icc profile: {err}");
+            }
+        }
+        current
+            .write_with_encoder(encoder)
+            .map_err(|err| format!("failed to encode {size}px thumbnail: {err}"))?;
+        thumbnails.push(Sidecar {
+            kind: SidecarKind::Thumbnail { size },
+            bytes,
+        });
+    }
+    Ok(thumbnails)
+}
+
+fn encode_jpeg(decoded: &DecodedImage, quality: u8) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut encoder = JpegEncoder::new_with_quality(&mut bytes, quality.clamp(1, 100));
+    if let Some(icc_profile) = decoded.icc_profile.clone() {
+        if let Err(err) = encoder.set_icc_profile(icc_profile) {
+            log::warn!("jpeg encoder dropped icc profile: {err}");
+        }
+    }
+    if let Some(exif) = decoded.exif.clone() {
+        if let Err(err) = encoder.set_exif_metadata(exif) {
+            log::warn!("jpeg encoder dropped exif metadata: {err}");
+        }
+    }
+    decoded
+        .image
+        .write_with_encoder(encoder)
+        .map_err(|err| format!("failed to encode jpeg at quality {quality}: {err}"))?;
+    Ok(bytes)
+}
+
+pub(crate) fn write_output(output_path: &Path, bytes: &[u8]) -> Result<(), ConvertError> {
+    let file = File::create(output_path).map_err(|err| {
+        ConvertError::from_io(
+            ConvertError::Finalize,
+            format_args!("failed to create output {}", output_path.display()),
+            &err,
+        )
+    })?;
+    let mut writer = BufWriter::new(file);
+    writer
+        .write_all(bytes)
+        .and_then(|()| writer.flush())
+        .map_err(|err| {
+            ConvertError::from_io(
+                ConvertError::Finalize,
+                format_args!("failed to write output {}", output_path.display()),
+                &err,
+            )
+        })
+}
+
+/// Reads back a written JPEG and checks it is complete and decodes to
+/// `expected_dimensions`, before the source is allowed to go anywhere.
+pub(crate) fn verify_output(path: &Path, expected_dimensions: (u32, u32)) -> Result<(), String> {
+    let bytes =
+        fs::read(path).map_err(|err| format!("failed to read back {}: {err}", path.display()))?;
+    if !bytes.ends_with(&[0xFF, 0xD9]) {
+        return Err(format!(
+            "{} is truncated: missing end-of-image marker",
+            path.display()
+        ));
+    }
+    let image = image::load_from_memory_with_format(&bytes, ImageFormat::Jpeg)
+        .map_err(|err| format!("failed to decode {}: {err}", path.display()))?;
+    if image.dimensions() != expected_dimensions {
+        let (width, height) = image.dimensions();
+        let (expected_width, expected_height) = expected_dimensions;
+        return Err(format!(
+            "{} decodes to {width}x{height}, expected {expected_width}x{expected_height}",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+fn decode_with_sips(input_path: &Path) -> Result<DecodedImage, String> {
+    let intermediate_path = intermediate_path_for(input_path);
+    let result = run_sips_decode(input_path, &intermediate_path)
+        .and_then(|()| read_intermediate(&intermediate_path));
+    if intermediate_path.exists() {
+        let _ = fs::remove_file(&intermediate_path);
+    }
+    let (image, icc_profile) = result?;
+
+    Ok(DecodedImage {
+        image,
+        orientation: read_sips_orientation(input_path),
+        icc_profile,
+        exif: read_source_exif(input_path),
+        gain_map: None,
+        depth_map: None,
+    })
+}
+
+fn decode_with_image_io(
+    input_path: &Path,
+    selection: MultiImagePolicy,
+    tone_map_sdr: bool,
+) -> Result<Vec<DecodedImage>, String> {
+    let prefix = intermediate_path_for(input_path).with_extension("");
+    let result =
+        run_image_io_export(input_path, &prefix, selection, tone_map_sdr).and_then(|count| {
+            // ImageIO already applied each image's transform, so the primary
+            // image's EXIF travels along with its orientation reset.
+            let exif = read_source_exif(input_path).map(|mut exif| {
+                set_exif_orientation(&mut exif, 1);
+                exif
+            });
+            (0..count)
+                .map(|index| {
+                    let (image, icc_profile) =
+                        read_intermediate(&exported_image_path(&prefix, index))?;
+                    Ok(DecodedImage {
+                        image,
+                        orientation: Orientation::NoTransforms,
+                        icc_profile,
+                        exif: exif.clone(),
+                        gain_map: None,
+                        depth_map: None,
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()
+        });
+
+    let mut index = 0;
+    while exported_image_path(&prefix, index).exists() {
+        let _ = fs::remove_file(exported_image_path(&prefix, index));
+        index += 1;
+    }
+    let decoded = result?;
+    if decoded.is_empty() {
+        return Err(format!("no images found in {}", input_path.display()));
+    }
+    Ok(decoded)
+}
+
+fn run_image_io_export(
+    input_path: &Path,
+    prefix: &Path,
+    selection: MultiImagePolicy,
+    tone_map_sdr: bool,
+) -> Result<usize, String> {
+    let stdout = run_image_io_script(
+        "image io export",
+        IMAGE_IO_EXPORT_SCRIPT,
+        &[
+            input_path.as_os_str(),
+            prefix.as_os_str(),
+            match selection {
+                MultiImagePolicy::Primary => OsStr::new("primary"),
+                MultiImagePolicy::All => OsStr::new("all"),
+            },
+            OsStr::new(if tone_map_sdr { "sdr" } else { "default" }),
+        ],
+    )?;
+    stdout
+        .trim()
+        .parse()
+        .map_err(|err| format!("unexpected image io export output {stdout:?}: {err}"))
+}
+
+fn read_auxiliary_with_image_io(
+    input_path: &Path,
+    kind: AuxiliaryKind,
+) -> Result<Option<AuxiliaryImage>, String> {
+    let data_path = intermediate_path_for(input_path).with_extension("aux");
+    let mut args = vec![input_path.as_os_str(), data_path.as_os_str()];
+    args.extend(kind.image_io_types().iter().map(OsStr::new));
+    let result = run_image_io_script(
+        "image io auxiliary export",
+        IMAGE_IO_AUXILIARY_SCRIPT,
+        &args,
+    )
+    .and_then(|stdout| {
+        if stdout.trim().is_empty() {
+            return Ok(None);
+        }
+        let mut aux: AuxiliaryImage = serde_json::from_str(stdout.trim())
+            .map_err(|err| format!("unexpected image io auxiliary output {stdout:?}: {err}"))?;
+        aux.data = fs::read(&data_path).map_err(|err| {
+            format!(
+                "failed to read auxiliary data {}: {err}",
+                data_path.display()
+            )
+        })?;
+        Ok(Some(aux))
+    });
+    if data_path.exists() {
+        let _ = fs::remove_file(&data_path);
+    }
+    result
+}
+
+fn run_image_io_script(label: &str, script: &str, args: &[&OsStr]) -> Result<String, String> {
+    let output = Command::new("osascript")
+        .arg("-l")
+        .arg("JavaScript")
+        .arg("-e")
+        .arg(script)
+        .args(args)
+        .output()
+        .map_err(|err| format!("failed to run osascript: {err}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(format!(
+            "{label} exited with status {}: {}",
+            output.status,
+            if stderr.is_empty() {
+                "no stderr output"
+            } else {
+                &stderr
+            }
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Converts auxiliary pixels to 8-bit grayscale. Gain maps keep their stored
+/// values; depth and disparity are stretched to the full range with near
+/// surfaces bright, as Photos renders them.
+fn auxiliary_to_gray(aux: &AuxiliaryImage) -> Result<GrayImage, String> {
+    let (bytes_per_sample, invert) = match aux.pixel_format {
+        PIXEL_FORMAT_ONE_COMPONENT_8 => (1, false),
+        PIXEL_FORMAT_ONE_COMPONENT_16 | PIXEL_FORMAT_DISPARITY_FLOAT16 => (2, false),
+        PIXEL_FORMAT_DEPTH_FLOAT16 => (2, true),
+        PIXEL_FORMAT_DISPARITY_FLOAT32 => (4, false),
+        PIXEL_FORMAT_DEPTH_FLOAT32 => (4, true),
+        other => {
+            return Err(format!(
+                "unsupported auxiliary pixel format {:?}",
+                String::from_utf8_lossy(&other.to_be_bytes())
+            ))
+        }
+    };
+    let (width, height) = (aux.width as usize, aux.height as usize);
+    if width == 0
+        || height == 0
+        || aux.bytes_per_row < width * bytes_per_sample
+        || aux.data.len() < aux.bytes_per_row * (height - 1) + width * bytes_per_sample
+    {
+        return Err(format!(
+            "auxiliary data of {} bytes does not hold {width}x{height} pixels",
+            aux.data.len()
+        ));
+    }
+
+    let sample = |x: usize, y: usize| -> f32 {
+        let at = y * aux.bytes_per_row + x * bytes_per_sample;
+        let raw = &aux.data[at..at + bytes_per_sample];
+        match aux.pixel_format {
+            PIXEL_FORMAT_ONE_COMPONENT_8 => f32::from(raw[0]),
+            PIXEL_FORMAT_ONE_COMPONENT_16 => f32::from(u16::from_le_bytes([raw[0], raw[1]]) >> 8),
+            PIXEL_FORMAT_DISPARITY_FLOAT16 | PIXEL_FORMAT_DEPTH_FLOAT16 => {
+                f16_to_f32(u16::from_le_bytes([raw[0], raw[1]]))
+            }
+            _ => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]),
+        }
+    };
+    let samples: Vec<f32> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| sample(x, y))
+        .collect();
+
+    let pixels: Vec<u8> =
+        if bytes_per_sample == 1 || aux.pixel_format == PIXEL_FORMAT_ONE_COMPONENT_16 {
+            samples.iter().map(|value| *value as u8).collect()
+        } else {
+            let finite = samples.iter().copied().filter(|value| value.is_finite());
+            let min = finite.clone().fold(f32::INFINITY, f32::min);
+            let max = finite.fold(f32::NEG_INFINITY, f32::max);
+            let range = if max > min { max - min } else { 1.0 };
+            samples
+                .iter()
+                .map(|value| {
+                    if !value.is_finite() {
+                        return 0;
+                    }
+                    let level = (value - min) / range;
+                    let level = if invert { 1.0 - level } else { level };
+                    (level * 255.0).round() as u8
+                })
+                .collect()
+        };
+    GrayImage::from_raw(aux.width, aux.height, pixels)
+        .ok_or_else(|| "auxiliary pixel buffer size mismatch".to_string())
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from((bits >> 10) & 0x1F);
+    let mantissa = f32::from(bits & 0x3FF);
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1F if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn exported_image_path(prefix: &Path, index: usize) -> PathBuf {
+    let mut name = prefix.as_os_str().to_os_string();
+    name.push(format!("-{index}.png"));
+    PathBuf::from(name)
+}
+
+fn run_sips_decode(input_path: &Path, output_path: &Path) -> Result<(), String> {
+    let output = Command::new("sips")
+        .arg("-s")
+        .arg("format")
+        .arg("png")
+        .arg(input_path.as_os_str())
+        .arg("--out")
+        .arg(output_path.as_os_str())
+        .output()
+        .map_err(|err| format!("failed to run sips: {err}"))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(format!(
+        "sips exited with status {}: {}",
+        output.status,
+        if stderr.is_empty() {
+            "no stderr output"
+        } else {
+            &stderr
+        }
+    ))
+}
+
+fn read_sips_orientation(input_path: &Path) -> Orientation {
+    let output = match Command::new("sips")
+        .arg("-g")
+        .arg("orientation")
+        .arg(input_path.as_os_str())
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        Ok(_) | Err(_) => return Orientation::NoTransforms,
+    };
+    parse_sips_orientation(&String::from_utf8_lossy(&output.stdout))
+        .unwrap_or(Orientation::NoTransforms)
+}
+
+fn parse_sips_orientation(stdout: &str) -> Option<Orientation> {
+    stdout.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("orientation:")?;
+        Orientation::from_exif(value.trim().parse().ok()?)
+    })
+}
+
+pub(crate) fn read_intermediate(path: &Path) -> Result<(DynamicImage, Option<Vec<u8>>), String> {
+    let mut decoder = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|err| format!("failed to open decoded image {}: {err}", path.display()))?
+        .into_decoder()
+        .map_err(|err| format!("failed to read decoded image {}: {err}", path.display()))?;
+    let icc_profile = decoder.icc_profile().ok().flatten();
+    let image = DynamicImage::from_decoder(decoder)
+        .map_err(|err| format!("failed to read decoded image {}: {err}", path.display()))?;
+    Ok((image, icc_profile))
+}
+
+fn read_source_exif(input_path: &Path) -> Option<Vec<u8>> {
+    let file = File::open(input_path).ok()?;
+    match exif::Reader::new().read_from_container(&mut BufReader::new(file)) {
+        Ok(exif) => Some(exif.buf().to_vec()),
+        Err(err) => {
+            log::debug!("no exif metadata in {}: {err}", input_path.display());
+            None
+        }
+    }
+}
+
+fn intermediate_path_for(input_path: &Path) -> PathBuf {
+    let stem = input_path
+        .file_stem()
+        .map(|value| value.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "input".to_string());
+    let stamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|value| value.as_nanos())
+        .unwrap_or(0);
+    let seq = DECODE_SEQ.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!(
+        "heic-ready_{}_{stamp}_{seq}_{stem}.png",
+        std::process::id()
+    ))
+}
+
+/// Rewrites the orientation entry of IFD0 in a raw TIFF/EXIF block in place.
+/// Returns `false` when the block is malformed or carries no orientation.
+fn set_exif_orientation(tiff: &mut [u8], value: u16) -> bool {
+    let little_endian = match tiff.get(0..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return false,
+    };
+    let read_u16 = |bytes: &[u8], at: usize| -> Option<u16> {
+        let raw: [u8; 2] = bytes.get(at..at + 2)?.try_into().ok()?;
+        Some(if little_endian {
+            u16::from_le_bytes(raw)
+        } else {
+            u16::from_be_bytes(raw)
+        })
+    };
+    let read_u32 = |bytes: &[u8], at: usize| -> Option<u32> {
+        let raw: [u8; 4] = bytes.get(at..at + 4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(raw)
+        } else {
+            u32::from_be_bytes(raw)
+        })
+    };
+
+    let Some(ifd0) = read_u32(tiff, 4).map(|offset| offset as usize) else {
+        return false;
+    };
+    let Some(count) = read_u16(tiff, ifd0) else {
+        return false;
+    };
+    for index in 0..usize::from(count) {
+        let entry = ifd0 + 2 + index * 12;
+        if read_u16(tiff, entry) != Some(EXIF_ORIENTATION_TAG) {
+            continue;
+        }
+        if read_u16(tiff, entry + 2) != Some(EXIF_TYPE_SHORT) {
+            return false;
+        }
+        let Some(slot) = tiff.get_mut(entry + 8..entry + 10) else {
+            return false;
+        };
+        slot.copy_from_slice(&if little_endian {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        });
+        return true;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    const SOURCE_WIDTH: u32 = 3;
+    const SOURCE_HEIGHT: u32 = 2;
+
+    fn labelled_image() -> DynamicImage {
+        let mut image = RgbImage::new(SOURCE_WIDTH, SOURCE_HEIGHT);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            *pixel = Rgb([x as u8, y as u8, 0]);
+        }
+        DynamicImage::ImageRgb8(image)
+    }
+
+    /// Where the stored pixel (x, y) must end up once orientation `value` is
+    /// applied, following the EXIF 2.3 definition of each tag value.
+    fn expected_position(value: u8, x: u32, y: u32) -> (u32, u32) {
+        let (w, h) = (SOURCE_WIDTH, SOURCE_HEIGHT);
+        match value {
+            1 => (x, y),
+            2 => (w - 1 - x, y),
+            3 => (w - 1 - x, h - 1 - y),
+            4 => (x, h - 1 - y),
+            5 => (y, x),
+            6 => (h - 1 - y, x),
+            7 => (h - 1 - y, w - 1 - x),
+            8 => (y, w - 1 - x),
+            _ => unreachable!(),
+        }
+    }
+
+    fn tiff_with_orientation(little_endian: bool, value: u16) -> Vec<u8> {
+        let u16_bytes = |v: u16| {
+            if little_endian {
+                v.to_le_bytes()
+            } else {
+                v.to_be_bytes()
+            }
+        };
+        let u32_bytes = |v: u32| {
+            if little_endian {
+                v.to_le_bytes()
+            } else {
+                v.to_be_bytes()
+            }
+        };
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(if little_endian { b"II" } else { b"MM" });
+        tiff.extend_from_slice(&u16_bytes(42));
+        tiff.extend_from_slice(&u32_bytes(8));
+        tiff.extend_from_slice(&u16_bytes(2));
+        // ImageWidth (LONG) precedes the orientation entry.
+        tiff.extend_from_slice(&u16_bytes(0x0100));
+        tiff.extend_from_slice(&u16_bytes(4));
+        tiff.extend_from_slice(&u32_bytes(1));
+        tiff.extend_from_slice(&u32_bytes(4032));
+        tiff.extend_from_slice(&u16_bytes(EXIF_ORIENTATION_TAG));
+        tiff.extend_from_slice(&u16_bytes(EXIF_TYPE_SHORT));
+        tiff.extend_from_slice(&u32_bytes(1));
+        tiff.extend_from_slice(&u16_bytes(value));
+        tiff.extend_from_slice(&[0, 0]);
+        tiff.extend_from_slice(&u32_bytes(0));
+        tiff
+    }
+
+    fn exif_orientation_of(tiff: &[u8]) -> u32 {
+        let exif = exif::Reader::new()
+            .read_raw(tiff.to_vec())
+            .expect("parse exif");
+        exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+            .and_then(|field| field.value.get_uint(0))
+            .expect("orientation field")
+    }
+
+    #[test]
+    fn normalize_orientation_bakes_all_eight_orientations_into_pixels() {
+        for value in 1..=8u8 {
+            let source = labelled_image();
+            let mut decoded = DecodedImage {
+                image: source.clone(),
+                orientation: Orientation::from_exif(value).expect("orientation"),
+                icc_profile: None,
+                exif: Some(tiff_with_orientation(true, u16::from(value))),
+                gain_map: None,
+                depth_map: None,
+            };
+
+            normalize_orientation(&mut decoded);
+
+            let rotated = value >= 5;
+            let (expected_w, expected_h) = if rotated {
+                (SOURCE_HEIGHT, SOURCE_WIDTH)
+            } else {
+                (SOURCE_WIDTH, SOURCE_HEIGHT)
+            };
+            assert_eq!(
+                decoded.image.dimensions(),
+                (expected_w, expected_h),
+                "orientation {value}"
+            );
+            for (x, y, pixel) in source.to_rgb8().enumerate_pixels() {
+                let (dx, dy) = expected_position(value, x, y);
+                assert_eq!(
+                    decoded.image.to_rgb8().get_pixel(dx, dy),
+                    pixel,
+                    "orientation {value}: source ({x}, {y})"
+                );
+            }
+            assert_eq!(decoded.orientation, Orientation::NoTransforms);
+            assert_eq!(
+                exif_orientation_of(decoded.exif.as_deref().expect("exif")),
+                1,
+                "orientation {value}"
+            );
+        }
+    }
+
+    #[test]
+    fn pipeline_keeps_orientation_when_normalization_is_disabled() {
+        let decoded = DecodedImage {
+            image: labelled_image(),
+            orientation: Orientation::Rotate90,
+            icc_profile: None,
+            exif: Some(tiff_with_orientation(false, 6)),
+            gain_map: None,
+            depth_map: None,
+        };
+        let options = ConvertOptions {
+            jpeg_quality: 92,
+            normalize_orientation: false,
+            resize: None,
+            target_size: None,
+            multi_image: MultiImagePolicy::Primary,
+            hdr: HdrPolicy::BaseImage,
+            export_depth_map: false,
+            color: ColorPolicy::Preserve,
+            thumbnail_sizes: Vec::new(),
+        };
+
+        let prepared = apply_pipeline(decoded, &options).expect("pipeline");
+
+        assert_eq!(prepared.image.dimensions(), (SOURCE_WIDTH, SOURCE_HEIGHT));
+        assert_eq!(
+            exif_orientation_of(prepared.exif.as_deref().expect("exif")),
+            6
+        );
+    }
+
+    fn shrink(mode: ResizeMode) -> ResizeOptions {
+        ResizeOptions {
+            mode,
+            only_shrink: true,
+            filter: ResampleFilter::Lanczos3,
+        }
+    }
+
+    #[test]
+    fn target_dimensions_cover_each_resize_mode() {
+        let long_edge = shrink(ResizeMode::MaxLongEdge { pixels: 4096 });
+        assert_eq!(
+            target_dimensions(8064, 6048, &long_edge),
+            Some((4096, 3072))
+        );
+        assert_eq!(target_dimensions(3024, 4032, &long_edge), None);
+
+        let box_limit = shrink(ResizeMode::MaxDimensions {
+            max_width: Some(1920),
+            max_height: Some(1080),
+        });
+        assert_eq!(
+            target_dimensions(4032, 3024, &box_limit),
+            Some((1440, 1080))
+        );
+
+        let width_only = shrink(ResizeMode::MaxDimensions {
+            max_width: Some(1000),
+            max_height: None,
+        });
+        assert_eq!(
+            target_dimensions(4000, 3000, &width_only),
+            Some((1000, 750))
+        );
+
+        let half = shrink(ResizeMode::Percentage { percent: 50 });
+        assert_eq!(target_dimensions(4031, 3023, &half), Some((2016, 1512)));
+    }
+
+    #[test]
+    fn only_shrink_skips_upscaling() {
+        let mut options = shrink(ResizeMode::MaxLongEdge { pixels: 4096 });
+        assert_eq!(target_dimensions(1024, 768, &options), None);
+
+        options.only_shrink = false;
+        assert_eq!(target_dimensions(1024, 768, &options), Some((4096, 3072)));
+    }
+
+    #[test]
+    fn resize_limits_apply_to_displayed_orientation() {
+        let mut decoded = DecodedImage {
+            image: DynamicImage::ImageRgb8(RgbImage::new(400, 300)),
+            orientation: Orientation::Rotate90,
+            icc_profile: None,
+            exif: None,
+            gain_map: None,
+            depth_map: None,
+        };
+        let options = shrink(ResizeMode::MaxDimensions {
+            max_width: Some(150),
+            max_height: None,
+        });
+
+        resize_image(&mut decoded, &options);
+
+        // Displayed as 300x400, so the 150px width limit halves both axes.
+        assert_eq!(decoded.image.dimensions(), (200, 150));
+    }
+
+    fn target(max_bytes: u64) -> TargetSizeOptions {
+        TargetSizeOptions {
+            max_bytes,
+            min_quality: 40,
+            max_quality: 95,
+        }
+    }
+
+    #[test]
+    fn target_size_search_picks_highest_quality_that_fits() {
+        let mut tried = Vec::new();
+        let encoded = search_quality_for_target(&target(7_000), |quality| {
+            tried.push(quality);
+            Ok(vec![0; usize::from(quality) * 100])
+        })
+        .expect("search");
+
+        assert_eq!(encoded.quality, 70);
+        assert_eq!(encoded.bytes.len(), 7_000);
+        assert_eq!(encoded.missed_target_bytes, None);
+        assert!(tried.len() <= 7, "binary search tried {tried:?}");
+    }
+
+    #[test]
+    fn target_size_search_falls_back_to_min_quality_when_nothing_fits() {
+        let encoded = search_quality_for_target(&target(100), |quality| {
+            Ok(vec![0; usize::from(quality) * 100])
+        })
+        .expect("search");
+
+        assert_eq!(encoded.quality, 40);
+        assert_eq!(encoded.missed_target_bytes, Some(100));
+    }
+
+    #[test]
+    fn target_size_search_shrinks_real_jpeg_output() {
+        let mut noise = RgbImage::new(128, 128);
+        for (x, y, pixel) in noise.enumerate_pixels_mut() {
+            let v = (x.wrapping_mul(73) ^ y.wrapping_mul(151)) as u8;
+            *pixel = Rgb([v, v.wrapping_mul(3), v.wrapping_add(x as u8)]);
+        }
+        let decoded = DecodedImage {
+            image: DynamicImage::ImageRgb8(noise),
+            orientation: Orientation::NoTransforms,
+            icc_profile: None,
+            exif: None,
+            gain_map: None,
+            depth_map: None,
+        };
+        let full = encode_jpeg(&decoded, 95).expect("encode");
+        let limit = (full.len() / 2) as u64;
+
+        let encoded =
+            search_quality_for_target(&target(limit), |quality| encode_jpeg(&decoded, quality))
+                .expect("search");
+
+        assert!(encoded.bytes.len() as u64 <= limit);
+        assert!(encoded.quality < 95);
+    }
+
+    #[test]
+    fn verify_output_rejects_truncated_or_mismatched_jpeg() {
+        let path = std::env::temp_dir().join(format!(
+            "heic-ready_{}_verify.jpg.tmp",
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .expect("system time")
+                .as_nanos()
+        ));
+        let decoded = DecodedImage {
+            image: labelled_image(),
+            orientation: Orientation::NoTransforms,
+            icc_profile: None,
+            exif: None,
+            gain_map: None,
+            depth_map: None,
+        };
+        let bytes = encode_jpeg(&decoded, 90).expect("encode");
+
+        fs::write(&path, &bytes).expect("write jpeg");
+        assert_eq!(verify_output(&path, (SOURCE_WIDTH, SOURCE_HEIGHT)), Ok(()));
+        let mismatch = verify_output(&path, (SOURCE_HEIGHT, SOURCE_WIDTH)).expect_err("swapped");
+        assert!(mismatch.contains("decodes to 3x2, expected 2x3"));
+
+        fs::write(&path, &bytes[..bytes.len() / 2]).expect("write truncated");
+        let truncated = verify_output(&path, (SOURCE_WIDTH, SOURCE_HEIGHT)).expect_err("truncated");
+        assert!(truncated.contains("missing end-of-image marker"));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn set_exif_orientation_handles_both_byte_orders() {
+        for little_endian in [true, false] {
+            let mut tiff = tiff_with_orientation(little_endian, 8);
+            assert!(set_exif_orientation(&mut tiff, 1));
+            assert_eq!(exif_orientation_of(&tiff), 1);
+        }
+    }
+
+    #[test]
+    fn set_exif_orientation_rejects_malformed_block() {
+        let mut truncated = tiff_with_orientation(true, 6);
+        truncated.truncate(12);
+        assert!(!set_exif_orientation(&mut truncated, 1));
+        assert!(!set_exif_orientation(&mut [0u8; 4], 1));
+    }
+
+    #[test]
+    fn sips_orientation_output_is_parsed() {
+        let stdout = "/tmp/IMG_0001.HEIC\n  orientation: 6\n";
+        assert_eq!(parse_sips_orientation(stdout), Some(Orientation::Rotate90));
+        assert_eq!(
+            parse_sips_orientation("/tmp/a.heic\n  orientation: <nil>\n"),
+            None
+        );
+    }
+
+    fn with_auxiliary_images(width: u32, height: u32, orientation: Orientation) -> DecodedImage {
+        DecodedImage {
+            image: DynamicImage::ImageRgb8(RgbImage::new(width, height)),
+            orientation,
+            icc_profile: None,
+            exif: None,
+            gain_map: Some(GainMap {
+                image: DynamicImage::ImageLuma8(GrayImage::new(width / 2, height / 2)),
+                headroom: 4.0,
+            }),
+            depth_map: Some(DynamicImage::ImageLuma8(GrayImage::new(
+                width / 4,
+                height / 4,
+            ))),
+        }
+    }
+
+    #[test]
+    fn gain_map_follows_orientation_and_resize() {
+        let decoded = with_auxiliary_images(400, 300, Orientation::Rotate90);
+        let options = ConvertOptions {
+            jpeg_quality: 90,
+            normalize_orientation: true,
+            resize: Some(shrink(ResizeMode::MaxLongEdge { pixels: 200 })),
+            target_size: None,
+            multi_image: MultiImagePolicy::Primary,
+            hdr: HdrPolicy::UltraHdr,
+            export_depth_map: true,
+            color: ColorPolicy::Preserve,
+            thumbnail_sizes: Vec::new(),
+        };
+
+        let prepared = apply_pipeline(decoded, &options).expect("pipeline");
+
+        assert_eq!(prepared.image.dimensions(), (150, 200));
+        let gain_map = prepared.gain_map.as_ref().expect("gain map");
+        assert_eq!(gain_map.image.dimensions(), (75, 100));
+        // The depth map keeps its resolution but turns upright as well.
+        assert_eq!(
+            prepared.depth_map.as_ref().expect("depth").dimensions(),
+            (75, 100)
+        );
+    }
+
+    #[test]
+    fn auxiliary_images_become_ultra_hdr_and_upright_depth_sidecar() {
+        let decoded = with_auxiliary_images(64, 48, Orientation::Rotate90);
+        let options = ConvertOptions {
+            jpeg_quality: 90,
+            normalize_orientation: false,
+            resize: None,
+            target_size: None,
+            multi_image: MultiImagePolicy::Primary,
+            hdr: HdrPolicy::UltraHdr,
+            export_depth_map: true,
+            color: ColorPolicy::Preserve,
+            thumbnail_sizes: Vec::new(),
+        };
+
+        let converted =
+            convert_decoded(Path::new("/tmp/IMG_0001.heic"), decoded, &options).expect("convert");
+
+        assert!(converted.report.ultra_hdr);
+        assert_eq!(converted.report.source_dimensions, (48, 64));
+        assert_eq!(converted.report.output_dimensions, (48, 64));
+        assert!(converted.bytes.windows(4).any(|window| window == b"MPF\0"));
+        let [depth] = converted.sidecars.as_slice() else {
+            panic!("expected one sidecar");
+        };
+        assert_eq!(depth.kind, SidecarKind::DepthMap);
+        let png =
+            image::load_from_memory_with_format(&depth.bytes, ImageFormat::Png).expect("depth png");
+        assert_eq!(png.dimensions(), (12, 16));
+    }
+
+    #[test]
+    fn report_dimensions_do_not_change_when_orientation_is_normalized() {
+        let decoded = DecodedImage {
+            image: DynamicImage::ImageRgb8(RgbImage::new(400, 300)),
+            orientation: Orientation::Rotate90,
+            icc_profile: None,
+            exif: None,
+            gain_map: None,
+            depth_map: None,
+        };
+        let options = ConvertOptions {
+            jpeg_quality: 90,
+            normalize_orientation: true,
+            resize: None,
+            target_size: None,
+            multi_image: MultiImagePolicy::Primary,
+            hdr: HdrPolicy::BaseImage,
+            export_depth_map: false,
+            color: ColorPolicy::Preserve,
+            thumbnail_sizes: Vec::new(),
+        };
+
+        let converted =
+            convert_decoded(Path::new("/tmp/IMG_0001.heic"), decoded, &options).expect("convert");
+
+        assert_eq!(converted.report.source_dimensions, (300, 400));
+        assert_eq!(converted.report.output_dimensions, (300, 400));
+    }
+
+    #[test]
+    fn thumbnails_are_upright_and_never_upscaled() {
+        let decoded = DecodedImage {
+            image: DynamicImage::ImageRgb8(RgbImage::new(400, 300)),
+            orientation: Orientation::Rotate90,
+            icc_profile: None,
+            exif: None,
+            gain_map: None,
+            depth_map: None,
+        };
+        let options = ConvertOptions {
+            jpeg_quality: 90,
+            normalize_orientation: false,
+            resize: None,
+            target_size: None,
+            multi_image: MultiImagePolicy::Primary,
+            hdr: HdrPolicy::BaseImage,
+            export_depth_map: false,
+            color: ColorPolicy::Preserve,
+            thumbnail_sizes: vec![64, 1024, 256, 64],
+        };
+
+        let converted =
+            convert_decoded(Path::new("/tmp/IMG_0001.heic"), decoded, &options).expect("convert");
+
+        let thumbnails: Vec<(SidecarKind, (u32, u32))> = converted
+            .sidecars
+            .iter()
+            .map(|sidecar| {
+                let jpeg = image::load_from_memory_with_format(&sidecar.bytes, ImageFormat::Jpeg)
+                    .expect("thumbnail jpeg");
+                (sidecar.kind, jpeg.dimensions())
+            })
+            .collect();
+        assert_eq!(
+            thumbnails,
+            vec![
+                (SidecarKind::Thumbnail { size: 1024 }, (300, 400)),
+                (SidecarKind::Thumbnail { size: 256 }, (192, 256)),
+                (SidecarKind::Thumbnail { size: 64 }, (48, 64)),
+            ]
+        );
+    }
+
+    fn aux(
+        pixel_format: &[u8; 4],
+        width: u32,
+        bytes_per_row: usize,
+        data: Vec<u8>,
+    ) -> AuxiliaryImage {
+        AuxiliaryImage {
+            width,
+            height: (data.len() / bytes_per_row) as u32,
+            bytes_per_row,
+            pixel_format: u32::from_be_bytes(*pixel_format),
+            headroom: None,
+            data,
+        }
+    }
+
+    #[test]
+    fn auxiliary_gain_map_keeps_values_and_skips_row_padding() {
+        let gray = auxiliary_to_gray(&aux(
+            b"L008",
+            2,
+            4,
+            vec![10, 20, 0xEE, 0xEE, 30, 40, 0xEE, 0xEE],
+        ))
+        .expect("gray");
+        assert_eq!(gray.into_raw(), vec![10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn auxiliary_depth_is_normalized_with_near_surfaces_bright() {
+        // Disparity grows towards the camera, depth shrinks.
+        let disparity = [0.5f32, 1.0, 2.0]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let gray = auxiliary_to_gray(&aux(b"fdis", 3, 12, disparity)).expect("gray");
+        assert_eq!(gray.into_raw(), vec![0, 85, 255]);
+
+        // 1.0, 2.0 and 3.0 as float16.
+        let depth = [0x3C00u16, 0x4000, 0x4200]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let gray = auxiliary_to_gray(&aux(b"hdep", 3, 6, depth)).expect("gray");
+        assert_eq!(gray.into_raw(), vec![255, 128, 0]);
+    }
+
+    #[test]
+    fn auxiliary_rejects_unknown_format_and_short_data() {
+        assert!(auxiliary_to_gray(&aux(b"BGRA", 1, 4, vec![0; 4])).is_err());
+        let mut short = aux(b"L008", 4, 4, vec![0; 8]);
+        short.data.truncate(6);
+        assert!(auxiliary_to_gray(&short).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use std::{fmt, io};
+
+/// Why a file was not (fully) converted. `code()` is what the frontend sees,
+/// so existing codes must never change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ConvertError {
+    /// The file kept changing or vanished while waiting for it to settle.
+    Stabilize(String),
+    Decode(String),
+    Encode(String),
+    /// Parsing an ICC profile or converting between profiles.
+    Color(String),
+    /// Writing or renaming the output files.
+    Finalize(String),
+    /// The written output did not read back intact.
+    Verify(String),
+    Trash(String),
+    /// Moving the source into the archive folder.
+    Archive(String),
+    /// Deleting the source permanently in `replace` mode.
+    Delete(String),
+    Permission(String),
+    DiskFull(String),
+    /// Content this folder or platform cannot convert.
+    Unsupported(String),
+    /// A truncated or malformed file.
+    Corrupt(String),
+    Io(String),
+}
+
+impl ConvertError {
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            Self::Stabilize(_) => "stabilize",
+            Self::Decode(_) => "decode",
+            Self::Encode(_) => "encode",
+            Self::Color(_) => "color",
+            Self::Finalize(_) => "finalize",
+            Self::Verify(_) => "verify",
+            Self::Trash(_) => "trash",
+            Self::Archive(_) => "archive",
+            Self::Delete(_) => "delete",
+            Self::Permission(_) => "permission",
+            Self::DiskFull(_) => "disk_full",
+            Self::Unsupported(_) => "unsupported",
+            Self::Corrupt(_) => "corrupt",
+            Self::Io(_) => "io",
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        match self {
+            Self::Stabilize(message)
+            | Self::Decode(message)
+            | Self::Encode(message)
+            | Self::Color(message)
+            | Self::Finalize(message)
+            | Self::Verify(message)
+            | Self::Trash(message)
+            | Self::Archive(message)
+            | Self::Delete(message)
+            | Self::Permission(message)
+            | Self::DiskFull(message)
+            | Self::Unsupported(message)
+            | Self::Corrupt(message)
+            | Self::Io(message) => message,
+        }
+    }
+
+    /// Classifies `err` by its kind or OS error code: permission and full-disk
+    /// errors get their own code whatever the stage, anything else becomes
+    /// `stage`.
+    pub(crate) fn from_io(
+        stage: fn(String) -> Self,
+        context: impl fmt::Display,
+        err: &io::Error,
+    ) -> Self {
+        let message = format!("{context}: {err}");
+        match (err.kind(), err.raw_os_error()) {
+            (io::ErrorKind::PermissionDenied, _) | (_, Some(libc::EROFS)) => {
+                Self::Permission(message)
+            }
+            (_, Some(libc::ENOSPC | libc::EDQUOT)) => Self::DiskFull(message),
+            _ => stage(message),
+        }
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_are_classified_by_kind_not_text() {
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        let full = io::Error::from_raw_os_error(libc::ENOSPC);
+        let read_only = io::Error::from_raw_os_error(libc::EROFS);
+        let other = io::Error::other("permission denied, but only in the text");
+
+        let err = ConvertError::from_io(ConvertError::Finalize, "failed to write out.jpg", &denied);
+        assert_eq!(err.code(), "permission");
+        assert!(err.message().starts_with("failed to write out.jpg: "));
+        assert_eq!(
+            ConvertError::from_io(ConvertError::Finalize, "write", &full).code(),
+            "disk_full"
+        );
+        assert_eq!(
+            ConvertError::from_io(ConvertError::Archive, "move", &read_only).code(),
+            "permission"
+        );
+        assert_eq!(
+            ConvertError::from_io(ConvertError::Trash, "trash", &other),
+            ConvertError::Trash("trash: permission denied, but only in the text".to_string())
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::atomic_write,
+    date::civil_from_days,
+    history::{self, HistoryQuery},
+    watcher::RecentLog,
+};
+
+const CSV_HEADER: [&str; 13] = [
+    "time_utc",
+    "timestamp_unix_ms",
+    "result",
+    "source_path",
+    "output_paths",
+    "policy",
+    "trashed_as",
+    "archived_to",
+    "source_bytes",
+    "output_bytes",
+    "duration_ms",
+    "code",
+    "reason",
+];
+/// Joins several outputs in one CSV cell.
+const CSV_LIST_SEPARATOR: &str = ";";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        [Self::Csv, Self::Json]
+            .into_iter()
+            .find(|format| value.eq_ignore_ascii_case(format.extension()))
+    }
+}
+
+/// One exported record, flattened so CSV and JSON share the columns.
+#[derive(Debug, Serialize)]
+struct ExportRow<'a> {
+    time_utc: String,
+    timestamp_unix_ms: u128,
+    result: &'a str,
+    source_path: &'a str,
+    output_paths: Vec<&'a str>,
+    policy: Option<&'a str>,
+    trashed_as: Option<&'a str>,
+    archived_to: Option<&'a str>,
+    source_bytes: Option<u64>,
+    output_bytes: Option<u64>,
+    duration_ms: Option<u64>,
+    code: Option<&'a str>,
+    reason: &'a str,
+}
+
+impl<'a> ExportRow<'a> {
+    fn new(record: &'a RecentLog) -> Self {
+        let details = record.details.as_ref();
+        Self {
+            time_utc: format_utc(record.timestamp_unix_ms),
+            timestamp_unix_ms: record.timestamp_unix_ms,
+            result: &record.result,
+            source_path: &record.path,
+            output_paths: details
+                .map(|details| details.outputs.iter().map(String::as_str).collect())
+                .unwrap_or_default(),
+            policy: details.map(|details| details.policy.as_str()),
+            trashed_as: details.and_then(|details| details.trashed_as.as_deref()),
+            archived_to: details.and_then(|details| details.archived_to.as_deref()),
+            source_bytes: details.and_then(|details| details.source_bytes),
+            output_bytes: details.map(|details| details.output_bytes),
+            duration_ms: details.map(|details| details.duration_ms),
+            code: record.code.as_deref(),
+            reason: &record.reason,
+        }
+    }
+
+    fn csv_fields(&self) -> [String; 13] {
+        let number = |value: Option<u64>| value.map(|value| value.to_string()).unwrap_or_default();
+        let text = |value: Option<&str>| value.unwrap_or_default().to_string();
+        [
+            self.time_utc.clone(),
+            self.timestamp_unix_ms.to_string(),
+            self.result.to_string(),
+            self.source_path.to_string(),
+            self.output_paths.join(CSV_LIST_SEPARATOR),
+            text(self.policy),
+            text(self.trashed_as),
+            text(self.archived_to),
+            number(self.source_bytes),
+            number(self.output_bytes),
+            number(self.duration_ms),
+            text(self.code),
+            self.reason.to_string(),
+        ]
+    }
+}
+
+/// Records about files between both times (unix milliseconds, inclusive),
+/// oldest first. `info` records such as "watch started" are left out.
+pub(crate) fn export_records(
+    history_dir: &Path,
+    from_unix_ms: Option<u128>,
+    to_unix_ms: Option<u128>,
+) -> Vec<RecentLog> {
+    let query = HistoryQuery {
+        from_unix_ms,
+        to_unix_ms,
+        ..HistoryQuery::default()
+    };
+    let mut records = history::read_matching(history_dir, &query);
+    records.retain(|record| record.result != "info");
+    records.sort_by_key(|record| record.timestamp_unix_ms);
+    records
+}
+
+pub(crate) fn render(records: &[RecentLog], format: ExportFormat) -> Result<Vec<u8>, String> {
+    let rows: Vec<ExportRow> = records.iter().map(ExportRow::new).collect();
+    match format {
+        ExportFormat::Json => serde_json::to_vec_pretty(&rows)
+            .map_err(|err| format!("failed to serialize history export: {err}")),
+        ExportFormat::Csv => {
+            let mut csv = csv_line(CSV_HEADER.iter().map(|name| name.to_string()));
+            for row in &rows {
+                csv.push_str(&csv_line(row.csv_fields()));
+            }
+            Ok(csv.into_bytes())
+        }
+    }
+}
+
+/// Writes the export to `output` and returns how many records it holds.
+pub(crate) fn export_to_file(
+    history_dir: &Path,
+    from_unix_ms: Option<u128>,
+    to_unix_ms: Option<u128>,
+    format: ExportFormat,
+    output: &Path,
+) -> Result<usize, String> {
+    let records = export_records(history_dir, from_unix_ms, to_unix_ms);
+    let bytes = render(&records, format)?;
+    atomic_write(output, &bytes)
+        .map_err(|err| format!("failed to write {}: {err}", output.display()))?;
+    Ok(records.len())
+}
+
+fn csv_line(fields: impl IntoIterator<Item = String>) -> String {
+    let mut line = fields
+        .into_iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+    line
+}
+
+/// `2026-10-18T09:30:00.000Z`
+fn format_utc(unix_ms: u128) -> String {
+    let secs = (unix_ms / 1000) as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let time = secs.rem_euclid(86_400);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        unix_ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::ConversionDetails;
+
+    #[test]
+    fn rows_flatten_details_for_csv_and_json() {
+        let records = vec![
+            RecentLog {
+                timestamp_unix_ms: 1_760_779_800_123,
+                path: "/inbox/IMG_0001.heic".to_string(),
+                result: "success".to_string(),
+                code: Some("trash".to_string()),
+                reason: "converted with warning, \"kept\"".to_string(),
+                details: Some(ConversionDetails {
+                    policy: "replace".to_string(),
+                    outputs: vec![
+                        "/inbox/IMG_0001.jpg".to_string(),
+                        "/inbox/IMG_0001_1.jpg".to_string(),
+                    ],
+                    trashed_as: Some("/trash/IMG_0001.heic".to_string()),
+                    archived_to: None,
+                    source_bytes: Some(2_048),
+                    output_bytes: 4_096,
+                    duration_ms: 350,
+                }),
+            },
+            RecentLog {
+                timestamp_unix_ms: 1_760_779_801_000,
+                path: "/inbox/broken.heic".to_string(),
+                result: "failure".to_string(),
+                code: Some("decode".to_string()),
+                reason: "bad data".to_string(),
+                details: None,
+            },
+        ];
+
+        let csv =
+            String::from_utf8(render(&records, ExportFormat::Csv).expect("csv")).expect("utf8");
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines[0], CSV_HEADER.join(","));
+        assert_eq!(
+            lines[1],
+            "2025-10-18T09:30:00.123Z,1760779800123,success,/inbox/IMG_0001.heic,\
+             /inbox/IMG_0001.jpg;/inbox/IMG_0001_1.jpg,replace,/trash/IMG_0001.heic,,2048,4096,350,\
+             trash,\"converted with warning, \"\"kept\"\"\""
+        );
+        assert_eq!(
+            lines[2],
+            "2025-10-18T09:30:01.000Z,1760779801000,failure,/inbox/broken.heic,,,,,,,,decode,bad data"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&render(&records, ExportFormat::Json).expect("json"))
+                .expect("parse");
+        assert_eq!(json[0]["output_paths"][1], "/inbox/IMG_0001_1.jpg");
+        assert_eq!(json[0]["trashed_as"], "/trash/IMG_0001.heic");
+        assert_eq!(json[1]["policy"], serde_json::Value::Null);
+        assert_eq!(ExportFormat::parse("JSON"), Some(ExportFormat::Json));
+    }
+}
@@ -0,0 +1,532 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::LogFileOptions,
+    watcher::{self, RecentLog},
+};
+
+const ACTIVE_FILE_NAME: &str = "history.jsonl";
+const ROTATED_PREFIX: &str = "history.";
+const ROTATED_SUFFIX: &str = ".jsonl";
+const BYTES_PER_MB: u64 = 1024 * 1024;
+const MS_PER_DAY: u128 = 24 * 60 * 60 * 1000;
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// Set up once the app data dir is known; records pushed before that only
+/// reach the recent log buffer.
+static HISTORY: Mutex<Option<HistoryLog>> = Mutex::new(None);
+
+/// Every recent log record, appended as one JSON object per line so what
+/// happened to a file can be looked up long after the buffer dropped it.
+///
+/// `history.jsonl` is renamed to `history.<first record ms>.jsonl` once it is
+/// too big or too old; rotated files past the retention limits are deleted.
+struct HistoryLog {
+    dir: PathBuf,
+    options: LogFileOptions,
+    file: Option<File>,
+    len: u64,
+    /// Time of the first record in the active file.
+    started_unix_ms: Option<u128>,
+}
+
+/// Starts writing the history below `dir`.
+pub(crate) fn init(dir: &Path, options: LogFileOptions) {
+    match HISTORY.lock() {
+        Ok(mut history) => *history = Some(HistoryLog::new(dir, options)),
+        Err(err) => log::error!("failed to lock history log: {err}"),
+    }
+}
+
+/// Applies changed rotation settings; the next record rotates if needed.
+pub(crate) fn set_options(options: LogFileOptions) {
+    match HISTORY.lock() {
+        Ok(mut history) => {
+            if let Some(history) = history.as_mut() {
+                history.options = options;
+            }
+        }
+        Err(err) => log::error!("failed to lock history log: {err}"),
+    }
+}
+
+/// Appends `record`; failures are logged and never reach the conversion.
+pub(crate) fn append(record: &RecentLog) {
+    let mut history = match HISTORY.lock() {
+        Ok(history) => history,
+        Err(err) => {
+            log::error!("failed to lock history log: {err}");
+            return;
+        }
+    };
+    if let Some(history) = history.as_mut() {
+        if let Err(err) = history.append(record) {
+            log::warn!(
+                "failed to write history log in {}: {err}",
+                history.dir.display()
+            );
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum HistorySort {
+    #[default]
+    NewestFirst,
+    OldestFirst,
+}
+
+/// Filters for the logs window; unset fields match everything.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HistoryQuery {
+    pub(crate) result: Option<String>,
+    /// `ConvertError` code, i.e. the category of the failure or skip.
+    pub(crate) code: Option<String>,
+    /// Only records for paths below this folder.
+    pub(crate) folder: Option<PathBuf>,
+    pub(crate) from_unix_ms: Option<u128>,
+    pub(crate) to_unix_ms: Option<u128>,
+    /// Case-insensitive substring of the file name.
+    pub(crate) file_name: Option<String>,
+    pub(crate) sort: HistorySort,
+    /// `next_cursor` of the previous page.
+    pub(crate) cursor: Option<String>,
+    pub(crate) limit: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct HistoryPage {
+    pub entries: Vec<RecentLog>,
+    /// Records matching the filters across all pages.
+    pub total: usize,
+    /// Pass back as `cursor` for the next page; `None` on the last one.
+    pub next_cursor: Option<String>,
+}
+
+/// Searches the history files, or only the recent log buffer when the history
+/// could not be set up.
+pub(crate) fn query(query: &HistoryQuery) -> Result<HistoryPage, String> {
+    // Not held while reading: pushing a record locks the buffer, then this.
+    let dir = HISTORY
+        .lock()
+        .map_err(|err| format!("failed to lock history log: {err}"))?
+        .as_ref()
+        .map(|history| history.dir.clone());
+    let records = match dir {
+        Some(dir) => read_matching(&dir, query),
+        None => watcher::get_recent_logs()
+            .into_iter()
+            .filter(|record| query.matches(record))
+            .collect(),
+    };
+    page(records, query)
+}
+
+impl HistoryLog {
+    fn new(dir: &Path, options: LogFileOptions) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            options,
+            file: None,
+            len: 0,
+            started_unix_ms: None,
+        }
+    }
+
+    fn active_path(&self) -> PathBuf {
+        self.dir.join(ACTIVE_FILE_NAME)
+    }
+
+    fn append(&mut self, record: &RecentLog) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        line.push(b'\n');
+        if self.file.is_none() {
+            self.open()?;
+        }
+        if self.must_rotate(line.len() as u64, record.timestamp_unix_ms) {
+            self.rotate(record.timestamp_unix_ms)?;
+            self.open()?;
+        }
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        file.write_all(&line)?;
+        self.len += line.len() as u64;
+        self.started_unix_ms.get_or_insert(record.timestamp_unix_ms);
+        Ok(())
+    }
+
+    /// Opens (or creates) the active file and picks up its size and age.
+    fn open(&mut self) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.active_path();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        self.len = file.metadata()?.len();
+        self.started_unix_ms = first_timestamp(&path);
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn must_rotate(&self, additional: u64, now_unix_ms: u128) -> bool {
+        if self.len == 0 {
+            return false;
+        }
+        let max_len = self.options.max_file_mb.saturating_mul(BYTES_PER_MB);
+        let max_age = u128::from(self.options.rotate_after_days) * MS_PER_DAY;
+        self.len + additional > max_len
+            || self
+                .started_unix_ms
+                .is_some_and(|started| now_unix_ms.saturating_sub(started) >= max_age)
+    }
+
+    fn rotate(&mut self, now_unix_ms: u128) -> io::Result<()> {
+        self.file = None;
+        let started = self.started_unix_ms.unwrap_or(now_unix_ms);
+        let mut rotated = self.dir.join(rotated_file_name(started, 0));
+        let mut index = 1;
+        while rotated.exists() {
+            rotated = self.dir.join(rotated_file_name(started, index));
+            index += 1;
+        }
+        fs::rename(self.active_path(), &rotated)?;
+        self.prune(SystemTime::now());
+        Ok(())
+    }
+
+    /// Deletes rotated files that are past `retention_days` or beyond `max_files`.
+    fn prune(&self, now: SystemTime) {
+        let rotated = rotated_files(&self.dir);
+        let retention = Duration::from_secs(u64::from(self.options.retention_days) * 24 * 60 * 60);
+        let keep = self.options.max_files as usize;
+        let excess = rotated.len().saturating_sub(keep);
+        for (index, path) in rotated.into_iter().enumerate() {
+            let expired = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age > retention);
+            if index < excess || expired {
+                if let Err(err) = fs::remove_file(&path) {
+                    log::warn!("failed to remove old history {}: {err}", path.display());
+                }
+            }
+        }
+    }
+}
+
+/// Rotated files, oldest first.
+pub(crate) fn rotated_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut rotated: Vec<(u128, usize, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let (started, index) = parse_rotated_file_name(&name)?;
+            Some((started, index, entry.path()))
+        })
+        .collect();
+    rotated.sort();
+    rotated.into_iter().map(|(_, _, path)| path).collect()
+}
+
+/// Matching records of every history file, oldest file first. Lines that do
+/// not parse, such as one cut off by a crash, are skipped.
+pub(crate) fn read_matching(dir: &Path, query: &HistoryQuery) -> Vec<RecentLog> {
+    let mut files: Vec<(Option<u128>, PathBuf)> = rotated_files(dir)
+        .into_iter()
+        .map(|path| {
+            let started = path
+                .file_name()
+                .and_then(|name| parse_rotated_file_name(&name.to_string_lossy()))
+                .map(|(started, _)| started);
+            (started, path)
+        })
+        .collect();
+    files.push((None, dir.join(ACTIVE_FILE_NAME)));
+
+    let mut records = Vec::new();
+    for (index, (started, path)) in files.iter().enumerate() {
+        // A rotated file ends where the next one starts.
+        let ended = files.get(index + 1).and_then(|(next, _)| *next);
+        let too_old = query
+            .from_unix_ms
+            .zip(ended)
+            .is_some_and(|(from, ended)| ended < from);
+        let too_new = query
+            .to_unix_ms
+            .zip(*started)
+            .is_some_and(|(to, started)| started > to);
+        if too_old || too_new {
+            continue;
+        }
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                log::warn!("failed to read history {}: {err}", path.display());
+                continue;
+            }
+        };
+        records.extend(
+            BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .filter_map(|line| serde_json::from_str::<RecentLog>(&line).ok())
+                .filter(|record| query.matches(record)),
+        );
+    }
+    records
+}
+
+/// Sorts `records` and cuts out the page after `query.cursor`.
+///
+/// The cursor is `<timestamp>:<n>`: the page continues after the first `n`
+/// records at that timestamp, so records appended meanwhile do not shift it.
+fn page(mut records: Vec<RecentLog>, query: &HistoryQuery) -> Result<HistoryPage, String> {
+    records.sort_by_key(|record| record.timestamp_unix_ms);
+    if query.sort == HistorySort::NewestFirst {
+        records.reverse();
+    }
+    let comes_before = |timestamp: u128, other: u128| match query.sort {
+        HistorySort::NewestFirst => timestamp > other,
+        HistorySort::OldestFirst => timestamp < other,
+    };
+
+    let mut start = 0;
+    if let Some(cursor) = query.cursor.as_deref() {
+        let (timestamp, seen) = parse_cursor(cursor)?;
+        start = records.partition_point(|record| comes_before(record.timestamp_unix_ms, timestamp));
+        let same = records[start..]
+            .iter()
+            .take_while(|record| record.timestamp_unix_ms == timestamp)
+            .count();
+        start += seen.min(same);
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let end = start.saturating_add(limit).min(records.len());
+    let next_cursor = match records[start..end].last() {
+        Some(last) if end < records.len() => {
+            let timestamp = last.timestamp_unix_ms;
+            let first_same =
+                records.partition_point(|record| comes_before(record.timestamp_unix_ms, timestamp));
+            Some(format!("{timestamp}:{}", end - first_same))
+        }
+        _ => None,
+    };
+    Ok(HistoryPage {
+        total: records.len(),
+        entries: records.drain(start..end).collect(),
+        next_cursor,
+    })
+}
+
+fn parse_cursor(cursor: &str) -> Result<(u128, usize), String> {
+    cursor
+        .split_once(':')
+        .and_then(|(timestamp, seen)| Some((timestamp.parse().ok()?, seen.parse().ok()?)))
+        .ok_or_else(|| format!("invalid history cursor: {cursor}"))
+}
+
+impl HistoryQuery {
+    fn matches(&self, record: &RecentLog) -> bool {
+        let path = Path::new(&record.path);
+        let name_matches = |needle: &String| {
+            path.file_name().is_some_and(|name| {
+                name.to_string_lossy()
+                    .to_lowercase()
+                    .contains(&needle.to_lowercase())
+            })
+        };
+        self.result
+            .as_ref()
+            .is_none_or(|result| *result == record.result)
+            && self
+                .code
+                .as_ref()
+                .is_none_or(|code| record.code.as_ref() == Some(code))
+            && self
+                .folder
+                .as_ref()
+                .is_none_or(|folder| path.starts_with(folder))
+            && self
+                .from_unix_ms
+                .is_none_or(|from| record.timestamp_unix_ms >= from)
+            && self
+                .to_unix_ms
+                .is_none_or(|to| record.timestamp_unix_ms <= to)
+            && self.file_name.as_ref().is_none_or(name_matches)
+    }
+}
+
+fn rotated_file_name(started_unix_ms: u128, index: usize) -> String {
+    if index == 0 {
+        format!("{ROTATED_PREFIX}{started_unix_ms}{ROTATED_SUFFIX}")
+    } else {
+        format!("{ROTATED_PREFIX}{started_unix_ms}-{index}{ROTATED_SUFFIX}")
+    }
+}
+
+fn parse_rotated_file_name(name: &str) -> Option<(u128, usize)> {
+    let stamp = name
+        .strip_prefix(ROTATED_PREFIX)?
+        .strip_suffix(ROTATED_SUFFIX)?;
+    match stamp.split_once('-') {
+        Some((started, index)) => Some((started.parse().ok()?, index.parse().ok()?)),
+        None => Some((stamp.parse().ok()?, 0)),
+    }
+}
+
+fn first_timestamp(path: &Path) -> Option<u128> {
+    let mut line = String::new();
+    BufReader::new(File::open(path).ok()?)
+        .read_line(&mut line)
+        .ok()?;
+    let value: serde_json::Value = serde_json::from_str(&line).ok()?;
+    value.get("timestamp_unix_ms")?.as_u64().map(u128::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::unique_temp_dir_path;
+
+    fn record(timestamp_unix_ms: u128, reason: &str) -> RecentLog {
+        RecentLog {
+            timestamp_unix_ms,
+            path: "/inbox/IMG_0001.heic".to_string(),
+            result: "success".to_string(),
+            code: None,
+            reason: reason.to_string(),
+            details: None,
+        }
+    }
+
+    fn options(max_files: u32) -> LogFileOptions {
+        LogFileOptions {
+            max_file_mb: 1,
+            rotate_after_days: 1,
+            retention_days: 30,
+            max_files,
+        }
+    }
+
+    #[test]
+    fn records_are_appended_as_json_lines_and_rotate_by_age() {
+        let dir = unique_temp_dir_path("history_age");
+        let mut history = HistoryLog::new(&dir, options(5));
+        history.append(&record(1_000, "first")).expect("append");
+        history.append(&record(2_000, "second")).expect("append");
+
+        let contents = fs::read_to_string(dir.join(ACTIVE_FILE_NAME)).expect("read");
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("json line"))
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["reason"], "second");
+        assert_eq!(lines[0]["code"], serde_json::Value::Null);
+
+        // A new instance picks up the age of the existing file.
+        let mut reopened = HistoryLog::new(&dir, options(5));
+        reopened
+            .append(&record(1_000 + MS_PER_DAY, "next day"))
+            .expect("append");
+        assert_eq!(rotated_files(&dir), vec![dir.join("history.1000.jsonl")]);
+        let active = fs::read_to_string(dir.join(ACTIVE_FILE_NAME)).expect("read active");
+        assert_eq!(active.lines().count(), 1);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn size_rotation_keeps_at_most_max_files() {
+        let dir = unique_temp_dir_path("history_size");
+        let mut history = HistoryLog::new(&dir, options(2));
+        let reason = "x".repeat(400 * 1024);
+        for index in 0..7 {
+            history
+                .append(&record(10_000 + index, &reason))
+                .expect("append");
+        }
+
+        let rotated = rotated_files(&dir);
+        assert_eq!(
+            rotated,
+            vec![
+                dir.join("history.10002.jsonl"),
+                dir.join("history.10004.jsonl")
+            ]
+        );
+        assert!(dir.join(ACTIVE_FILE_NAME).exists());
+        assert_eq!(parse_rotated_file_name("history.7-2.jsonl"), Some((7, 2)));
+        assert_eq!(parse_rotated_file_name("history.jsonl"), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn query_filters_across_files_and_pages_with_a_cursor() {
+        let dir = unique_temp_dir_path("history_query");
+        let mut history = HistoryLog::new(&dir, options(5));
+        let mut push = |timestamp_unix_ms: u128, path: &str, result: &str| {
+            let mut entry = record(timestamp_unix_ms, result);
+            entry.path = path.to_string();
+            entry.result = result.to_string();
+            history.append(&entry).expect("append");
+        };
+        push(1_000, "/inbox/IMG_0001.heic", "success");
+        push(2_000, "/inbox/IMG_0002.heic", "failure");
+        // Rotates by age, so the rest lands in a new active file.
+        push(1_000 + MS_PER_DAY, "/inbox/IMG_0003.heic", "success");
+        push(1_000 + MS_PER_DAY, "/inbox/IMG_0004.heic", "success");
+        push(1_000 + MS_PER_DAY, "/other/IMG_0005.heic", "success");
+        assert_eq!(rotated_files(&dir).len(), 1);
+
+        let mut query = HistoryQuery {
+            result: Some("success".to_string()),
+            folder: Some(PathBuf::from("/inbox")),
+            limit: Some(2),
+            ..HistoryQuery::default()
+        };
+        let paths = |page: &HistoryPage| -> Vec<String> {
+            page.entries
+                .iter()
+                .map(|entry| entry.path.clone())
+                .collect()
+        };
+        let first = page(read_matching(&dir, &query), &query).expect("first page");
+        assert_eq!(first.total, 3);
+        assert_eq!(
+            paths(&first),
+            vec!["/inbox/IMG_0004.heic", "/inbox/IMG_0003.heic"]
+        );
+        query.cursor = first.next_cursor.clone();
+        let second = page(read_matching(&dir, &query), &query).expect("second page");
+        assert_eq!(paths(&second), vec!["/inbox/IMG_0001.heic"]);
+        assert_eq!(second.next_cursor, None);
+
+        let query = HistoryQuery {
+            file_name: Some("img_0002".to_string()),
+            to_unix_ms: Some(5_000),
+            sort: HistorySort::OldestFirst,
+            ..HistoryQuery::default()
+        };
+        let found = page(read_matching(&dir, &query), &query).expect("search");
+        assert_eq!(paths(&found), vec!["/inbox/IMG_0002.heic"]);
+        assert!(parse_cursor("soon").is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::config::atomic_write;
+
+/// One JSON file next to the config, read and rewritten whole under a
+/// process-wide lock; the stats and the trash journal are kept this way.
+#[derive(Clone, Debug)]
+pub(crate) struct JsonStore {
+    path: PathBuf,
+    /// Used in log and error messages, e.g. `trash journal`.
+    name: &'static str,
+    lock: &'static Mutex<()>,
+}
+
+impl JsonStore {
+    pub(crate) fn new(path: &Path, name: &'static str, lock: &'static Mutex<()>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            name,
+            lock,
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Held for one read-modify-write cycle.
+    pub(crate) fn lock(&self) -> Result<MutexGuard<'static, ()>, String> {
+        self.lock
+            .lock()
+            .map_err(|err| format!("failed to lock {}: {err}", self.name))
+    }
+
+    /// Starts empty when the file is missing or unreadable, like the ledger.
+    pub(crate) fn load<T: DeserializeOwned + Default>(&self) -> T {
+        match fs::read(&self.path) {
+            Ok(contents) => serde_json::from_slice(&contents).unwrap_or_else(|err| {
+                log::warn!(
+                    "failed to parse {} at {}: {err}",
+                    self.name,
+                    self.path.display()
+                );
+                T::default()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => T::default(),
+            Err(err) => {
+                log::warn!(
+                    "failed to read {} at {}: {err}",
+                    self.name,
+                    self.path.display()
+                );
+                T::default()
+            }
+        }
+    }
+
+    pub(crate) fn save<T: Serialize>(&self, value: &T) -> Result<(), String> {
+        let serialized = serde_json::to_vec_pretty(value)
+            .map_err(|err| format!("failed to serialize {}: {err}", self.name))?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("failed to create {}: {err}", parent.display()))?;
+        }
+        atomic_write(&self.path, &serialized).map_err(|err| {
+            format!(
+                "failed to write {} {}: {err}",
+                self.name,
+                self.path.display()
+            )
+        })
+    }
+}
+
+pub(crate) fn now_unix_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|value| value.as_millis())
+        .unwrap_or(0)
+}
@@ -0,0 +1,274 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::atomic_write;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LedgerFile {
+    #[serde(default)]
+    entries: Vec<LedgerEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LedgerEntry {
+    source: PathBuf,
+    #[serde(default)]
+    thumbnails: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    outputs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Tracked {
+    thumbnails: Vec<PathBuf>,
+    /// The numbered JPEGs of a multi-image source that stays in place; their
+    /// names alone cannot tell them from unrelated files.
+    outputs: Vec<PathBuf>,
+}
+
+/// Thumbnails generated for each source, persisted so they can be removed
+/// once the source is deleted, even across restarts.
+///
+/// In `replace` mode the HEIC is gone right after conversion, so the JPEG is
+/// recorded as the source instead.
+pub(crate) struct Ledger {
+    path: PathBuf,
+    entries: BTreeMap<PathBuf, Tracked>,
+}
+
+impl Ledger {
+    /// Starts empty when the file is missing or unreadable; a corrupt ledger
+    /// must never keep the watcher from starting.
+    pub(crate) fn load(path: &Path) -> Self {
+        let entries = match fs::read(path) {
+            Ok(contents) => match serde_json::from_slice::<LedgerFile>(&contents) {
+                Ok(file) => file
+                    .entries
+                    .into_iter()
+                    .map(|entry| {
+                        let tracked = Tracked {
+                            thumbnails: entry.thumbnails,
+                            outputs: entry.outputs,
+                        };
+                        (entry.source, tracked)
+                    })
+                    .collect(),
+                Err(err) => {
+                    log::warn!("failed to parse ledger at {}: {err}", path.display());
+                    BTreeMap::new()
+                }
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => {
+                log::warn!("failed to read ledger at {}: {err}", path.display());
+                BTreeMap::new()
+            }
+        };
+        Self {
+            path: path.to_path_buf(),
+            entries,
+        }
+    }
+
+    /// Adds `thumbnails` to the files tracked for `source`; converting the
+    /// same source again keeps the earlier thumbnails tracked as well.
+    /// Non-empty `outputs` replace the numbered JPEGs recorded for it.
+    pub(crate) fn record(
+        &mut self,
+        source: &Path,
+        thumbnails: &[PathBuf],
+        outputs: &[PathBuf],
+    ) -> Result<(), String> {
+        if thumbnails.is_empty() && outputs.is_empty() {
+            return Ok(());
+        }
+        let tracked = self.entries.entry(source.to_path_buf()).or_default();
+        for thumbnail in thumbnails {
+            if !tracked.thumbnails.contains(thumbnail) {
+                tracked.thumbnails.push(thumbnail.clone());
+            }
+        }
+        if !outputs.is_empty() {
+            tracked.outputs = outputs.to_vec();
+        }
+        self.save()
+    }
+
+    /// Multi-image sources with a recorded output still in place, which must
+    /// not be converted again.
+    pub(crate) fn converted_sources(&self) -> HashSet<PathBuf> {
+        self.entries
+            .iter()
+            .filter(|(_, tracked)| tracked.outputs.iter().any(|output| output.exists()))
+            .map(|(source, _)| source.clone())
+            .collect()
+    }
+
+    /// Deletes the thumbnails of `source` if it is tracked and no longer exists.
+    pub(crate) fn remove_source(&mut self, source: &Path) -> Result<Vec<PathBuf>, String> {
+        if source.exists() {
+            return Ok(Vec::new());
+        }
+        let Some(tracked) = self.entries.remove(source) else {
+            return Ok(Vec::new());
+        };
+        let removed = remove_files(&tracked.thumbnails);
+        self.save()?;
+        Ok(removed)
+    }
+
+    /// Deletes the thumbnails of every tracked source that no longer exists.
+    pub(crate) fn remove_missing_sources(
+        &mut self,
+    ) -> Result<Vec<(PathBuf, Vec<PathBuf>)>, String> {
+        let missing: Vec<PathBuf> = self
+            .entries
+            .keys()
+            .filter(|source| !source.exists())
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            return Ok(Vec::new());
+        }
+        let removed = missing
+            .into_iter()
+            .filter_map(|source| {
+                let tracked = self.entries.remove(&source)?;
+                Some((source, remove_files(&tracked.thumbnails)))
+            })
+            .collect();
+        self.save()?;
+        Ok(removed)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let file = LedgerFile {
+            entries: self
+                .entries
+                .iter()
+                .map(|(source, tracked)| LedgerEntry {
+                    source: source.clone(),
+                    thumbnails: tracked.thumbnails.clone(),
+                    outputs: tracked.outputs.clone(),
+                })
+                .collect(),
+        };
+        let serialized = serde_json::to_vec_pretty(&file)
+            .map_err(|err| format!("failed to serialize ledger: {err}"))?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("failed to create {}: {err}", parent.display()))?;
+        }
+        atomic_write(&self.path, &serialized)
+            .map_err(|err| format!("failed to write ledger {}: {err}", self.path.display()))
+    }
+}
+
+/// Returns the files that were actually deleted; ones already gone are skipped.
+fn remove_files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    paths
+        .iter()
+        .filter(|path| match fs::remove_file(path) {
+            Ok(()) => true,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => false,
+            Err(err) => {
+                log::warn!("failed to remove {}: {err}", path.display());
+                false
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::unique_temp_dir_path;
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+        fs::write(path, b"x").expect("write file");
+    }
+
+    #[test]
+    fn deleting_source_removes_its_thumbnails_after_reload() {
+        let root = unique_temp_dir_path("ledger_reload");
+        let source = root.join("IMG_0001.heic");
+        let other = root.join("IMG_0002.heic");
+        let thumbs = [
+            root.join(".thumbs/IMG_0001_256.jpg"),
+            root.join(".thumbs/IMG_0001_1024.jpg"),
+        ];
+        let other_thumb = root.join(".thumbs/IMG_0002_256.jpg");
+        for path in [&source, &other, &other_thumb].into_iter().chain(&thumbs) {
+            touch(path);
+        }
+        let ledger_path = root.join("state/ledger.json");
+
+        let mut ledger = Ledger::load(&ledger_path);
+        ledger.record(&source, &thumbs, &[]).expect("record");
+        ledger
+            .record(&other, std::slice::from_ref(&other_thumb), &[])
+            .expect("record other");
+        assert!(ledger
+            .remove_source(&source)
+            .expect("still present")
+            .is_empty());
+
+        fs::remove_file(&source).expect("delete source");
+        let mut reloaded = Ledger::load(&ledger_path);
+        let removed = reloaded.remove_missing_sources().expect("remove missing");
+
+        assert_eq!(removed, vec![(source.clone(), thumbs.to_vec())]);
+        assert!(thumbs.iter().all(|path| !path.exists()));
+        assert!(other_thumb.exists());
+        assert!(Ledger::load(&ledger_path)
+            .remove_source(&source)
+            .expect("untracked")
+            .is_empty());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn corrupt_ledger_starts_empty() {
+        let root = unique_temp_dir_path("ledger_corrupt");
+        let ledger_path = root.join("ledger.json");
+        touch(&ledger_path);
+
+        let mut ledger = Ledger::load(&ledger_path);
+        assert!(ledger.entries.is_empty());
+        ledger
+            .record(&root.join("IMG_0001.jpg"), &[root.join("thumb.jpg")], &[])
+            .expect("record");
+        assert_eq!(Ledger::load(&ledger_path).entries.len(), 1);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn multi_image_source_counts_as_converted_while_an_output_exists() {
+        let root = unique_temp_dir_path("ledger_outputs");
+        let source = root.join("IMG_0003.heic");
+        let outputs = [root.join("IMG_0003_001.jpg"), root.join("IMG_0003_002.jpg")];
+        for path in [&source].into_iter().chain(&outputs) {
+            touch(path);
+        }
+        let ledger_path = root.join("ledger.json");
+
+        Ledger::load(&ledger_path)
+            .record(&source, &[], &outputs)
+            .expect("record");
+        let ledger = Ledger::load(&ledger_path);
+        assert_eq!(ledger.converted_sources(), HashSet::from([source.clone()]));
+
+        for output in &outputs {
+            fs::remove_file(output).expect("remove output");
+        }
+        assert!(ledger.converted_sources().is_empty());
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -1,36 +1,77 @@
+mod archive;
+mod cli;
+mod color;
 mod commands;
 mod config;
+mod converter;
+mod date;
+mod error;
+mod export;
+mod history;
+mod json_store;
+mod ledger;
+mod live_photo;
+mod metrics;
+mod notifications;
+mod sniff;
+mod stats;
+#[cfg(test)]
+mod test_support;
+mod trash;
 mod tray;
+mod ultra_hdr;
+mod undo;
 mod watcher;
 mod window;
 
-use std::sync::Mutex;
+use std::{sync::Mutex, thread};
 
 use commands::{
-    get_config, get_recent_logs, open_recent_logs_window, pick_watch_folder, set_paused,
-    update_config,
+    export_history, get_batch_progress, get_config, get_stats, get_trash_history,
+    open_logs_window, open_recent_logs_window, open_stats_window, pick_export_path, pick_watch_folder, query_history,
+    set_paused, undo_trash_moves, update_config, HistoryRangeDto, JobProgressDto,
 };
 use config::{AppConfig, ConfigStore};
-use tauri::{AppHandle, Manager, State};
-use watcher::WatchService;
+use notifications::{Notice, Notifier};
+use stats::StatsStore;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tray::{MenuRefresh, RecentJobs};
+use undo::TrashJournal;
+use watcher::{
+    BatchProgress, JobResult, ProgressEvent, ProgressListener, StatusListener, WatchService,
+    WatchStatus,
+};
 
 pub(crate) const EVENT_PAUSED_CHANGED: &str = "paused-changed";
 pub(crate) const EVENT_LOCALE_CHANGED: &str = "locale-changed";
+pub(crate) const EVENT_JOB_PROGRESS: &str = "job-progress";
+pub(crate) const EVENT_BATCH_PROGRESS: &str = "batch-progress";
+pub(crate) const EVENT_HISTORY_RANGE: &str = "history-range";
 
 pub(crate) struct AppState {
     pub(crate) config_store: Mutex<ConfigStore>,
     pub(crate) watch_service: Mutex<Option<WatchService>>,
+    pub(crate) watch_status: Mutex<WatchStatus>,
+    pub(crate) batch_progress: Mutex<BatchProgress>,
+    pub(crate) notifier: Notifier,
+    pub(crate) recent_jobs: Mutex<RecentJobs>,
+    pub(crate) menu_refresh: Mutex<MenuRefresh>,
 }
 
 pub(crate) fn restart_watch_service(app: &AppHandle) -> Result<(), String> {
     let state: State<'_, AppState> = app.state();
 
-    let config = {
+    let (config, ledger_path, journal_path, stats_path) = {
         let config_store = state
             .config_store
             .lock()
             .map_err(|err| format!("failed to lock config store: {err}"))?;
-        config_store.config().clone()
+        (
+            config_store.config().clone(),
+            config_store.ledger_path(),
+            config_store.trash_journal_path(),
+            config_store.stats_path(),
+        )
     };
 
     let mut watch_slot = state
@@ -41,29 +82,158 @@ pub(crate) fn restart_watch_service(app: &AppHandle) -> Result<(), String> {
     if let Some(existing) = watch_slot.take() {
         existing.stop();
     }
+    // Jobs still queued were dropped with the old dispatcher.
+    report_progress(app, ProgressEvent::Batch(BatchProgress::default()));
+    history::set_options(config.log_file);
+    metrics::configure(&config.metrics);
+    state.notifier.set_options(config.notifications);
+    // A new dispatcher reports low disk space again if it still applies.
+    tray::set_watch_status(app, WatchStatus::Running);
+    // The watch folders and whether the dispatcher runs may have changed.
+    tray::refresh(app);
 
     if should_start_watcher(&config) {
-        let service = WatchService::start(config)?;
+        let handle = app.clone();
+        let on_status: StatusListener =
+            Box::new(move |status| tray::set_watch_status(&handle, status));
+        let handle = app.clone();
+        let on_progress: ProgressListener = Box::new(move |event| report_progress(&handle, event));
+        let service = WatchService::start(
+            config,
+            &ledger_path,
+            TrashJournal::new(&journal_path),
+            StatsStore::new(&stats_path),
+            on_status,
+            on_progress,
+        )?;
         *watch_slot = Some(service);
     }
 
     Ok(())
 }
 
+/// Forwards dispatcher progress to the windows and the tray.
+fn report_progress(app: &AppHandle, event: ProgressEvent) {
+    let state: State<'_, AppState> = app.state();
+    let emitted = match event {
+        ProgressEvent::Job {
+            stage,
+            path,
+            result,
+        } => {
+            if let Some(JobResult::Failed(code)) = result {
+                let batch_started_unix_ms = state
+                    .batch_progress
+                    .lock()
+                    .ok()
+                    .and_then(|progress| progress.started_unix_ms);
+                state.notifier.failure(&path, code, batch_started_unix_ms);
+            }
+            if let Some(result) = &result {
+                tray::record_finished_job(app, &path, result);
+            }
+            app.emit(
+                EVENT_JOB_PROGRESS,
+                JobProgressDto::new(stage, &path, result),
+            )
+        }
+        ProgressEvent::Batch(progress) => {
+            tray::set_batch_progress(app, progress);
+            if !progress.is_active() && progress.total > 0 {
+                state.notifier.batch_done(progress);
+            }
+            app.emit(EVENT_BATCH_PROGRESS, progress)
+        }
+    };
+    if let Err(err) = emitted {
+        log::error!("failed to emit progress event: {err}");
+    }
+}
+
+/// Shows a notification from the notifier thread in the UI language. Each one
+/// gets a thread that waits for the click and then opens its records.
+fn show_notice(app: &AppHandle, notice: Notice) {
+    let state: State<'_, AppState> = app.state();
+    let locale = match state.config_store.lock() {
+        Ok(store) => store.config().locale,
+        Err(err) => {
+            log::error!("failed to lock app state: {err}");
+            return;
+        }
+    };
+    let (title, body) = notice.text(locale);
+    let range = HistoryRangeDto {
+        from_unix_ms: notice.from_unix_ms,
+        to_unix_ms: notice.to_unix_ms,
+    };
+    let handle = app.clone();
+    let spawned = thread::Builder::new()
+        .name("notification".to_string())
+        .spawn(move || {
+            let mut notification = notify_rust::Notification::new();
+            notification.summary(&title).body(&body).auto_icon();
+            #[cfg(target_os = "macos")]
+            {
+                let _ = notify_rust::set_application(if tauri::is_dev() {
+                    "com.apple.Terminal"
+                } else {
+                    &handle.config().identifier
+                });
+            }
+            let shown = match notification.show() {
+                Ok(shown) => shown,
+                Err(err) => {
+                    log::error!("failed to show notification: {err}");
+                    return;
+                }
+            };
+            shown.wait_for_action(|action| {
+                if action != "default" {
+                    return;
+                }
+                let app = handle.clone();
+                let opened = handle.run_on_main_thread(move || {
+                    if let Err(err) = open_logs_window(&app, Some(range)) {
+                        log::error!("failed to open logs window: {err}");
+                    }
+                });
+                if let Err(err) = opened {
+                    log::error!("failed to schedule logs window: {err}");
+                }
+            });
+        });
+    if let Err(err) = spawned {
+        log::error!("failed to spawn notification thread: {err}");
+    }
+}
+
 fn should_start_watcher(config: &AppConfig) -> bool {
     !config.paused && !config.watch_folders.is_empty()
 }
 
+/// Handles command-line subcommands such as `undo`; `None` means none was
+/// given and the app should start.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    cli::run(args)
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .invoke_handler(tauri::generate_handler![
+            export_history,
+            get_batch_progress,
             get_config,
-            get_recent_logs,
+            get_stats,
+            get_trash_history,
             open_recent_logs_window,
+            open_stats_window,
+            pick_export_path,
             pick_watch_folder,
+            query_history,
             update_config,
-            set_paused
+            set_paused,
+            undo_trash_moves
         ])
         .setup(|app| {
             if cfg!(debug_assertions) {
@@ -76,8 +246,17 @@ pub fn run() {
 
             let config_dir = app.path().app_config_dir()?;
             let config_store = ConfigStore::load_or_init(&config_dir)?;
+            history::init(
+                &config::history_dir_in(&app.path().app_data_dir()?),
+                config_store.config().log_file,
+            );
             let paused = config_store.config().paused;
             let locale = config_store.config().locale;
+            let handle = app.handle().clone();
+            let notifier = Notifier::start(
+                config_store.config().notifications,
+                Box::new(move |notice| show_notice(&handle, notice)),
+            )?;
             log::info!(
                 "config loaded from {}",
                 config_store.config_path().display()
@@ -86,6 +265,11 @@ pub fn run() {
             app.manage(AppState {
                 config_store: Mutex::new(config_store),
                 watch_service: Mutex::new(None),
+                watch_status: Mutex::new(WatchStatus::default()),
+                batch_progress: Mutex::new(BatchProgress::default()),
+                notifier,
+                recent_jobs: Mutex::new(RecentJobs::default()),
+                menu_refresh: Mutex::new(MenuRefresh::default()),
             });
 
             if let Err(err) = restart_watch_service(&app.handle()) {
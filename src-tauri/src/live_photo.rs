@@ -0,0 +1,314 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+const APPLE_MAKER_NOTE_HEADER: &[u8] = b"Apple iOS\0";
+const APPLE_MAKER_NOTE_IFD_OFFSET: usize = 14;
+const APPLE_CONTENT_IDENTIFIER_TAG: u16 = 0x0011;
+const EXIF_TYPE_ASCII: u16 = 2;
+const QUICKTIME_CONTENT_IDENTIFIER_KEY: &[u8] = b"com.apple.quicktime.content.identifier";
+const QUICKTIME_TYPE_UTF8: u32 = 1;
+/// `moov` of a Live Photo clip is a few KiB; anything far larger is not one.
+const MAX_MOOV_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Finds the video of the Live Photo `input_path` belongs to: a `.mov` in the
+/// same folder carrying the same content identifier, preferring the one that
+/// shares the file stem.
+pub(crate) fn find_companion(input_path: &Path) -> Option<PathBuf> {
+    let identifier = heic_content_identifier(input_path)?;
+    let parent = input_path.parent()?;
+    let stem = input_path.file_stem()?;
+
+    let mut candidates: Vec<PathBuf> = fs::read_dir(parent)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_movie_extension(path) && path.is_file())
+        .collect();
+    candidates.sort_by_key(|path| path.file_stem() != Some(stem));
+
+    candidates
+        .into_iter()
+        .find(|path| match mov_content_identifier(path) {
+            Ok(found) => found.as_deref() == Some(identifier.as_str()),
+            Err(err) => {
+                log::debug!("skipping live photo candidate {}: {err}", path.display());
+                false
+            }
+        })
+}
+
+fn is_movie_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|value| value.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("mov"))
+}
+
+fn heic_content_identifier(input_path: &Path) -> Option<String> {
+    let file = File::open(input_path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+    let field = exif.get_field(exif::Tag::MakerNote, exif::In::PRIMARY)?;
+    match &field.value {
+        exif::Value::Undefined(bytes, _) => content_identifier_from_maker_note(bytes),
+        _ => None,
+    }
+}
+
+/// Reads tag 0x0011 from an Apple MakerNote: a big-endian IFD after a 14 byte
+/// header, with offsets relative to the start of the MakerNote.
+fn content_identifier_from_maker_note(maker_note: &[u8]) -> Option<String> {
+    if !maker_note.starts_with(APPLE_MAKER_NOTE_HEADER) {
+        return None;
+    }
+    let read_u16 = |at: usize| -> Option<u16> {
+        Some(u16::from_be_bytes(
+            maker_note.get(at..at + 2)?.try_into().ok()?,
+        ))
+    };
+    let read_u32 = |at: usize| -> Option<usize> {
+        Some(u32::from_be_bytes(maker_note.get(at..at + 4)?.try_into().ok()?) as usize)
+    };
+
+    let count = read_u16(APPLE_MAKER_NOTE_IFD_OFFSET)?;
+    (0..usize::from(count)).find_map(|index| {
+        let entry = APPLE_MAKER_NOTE_IFD_OFFSET + 2 + index * 12;
+        if read_u16(entry)? != APPLE_CONTENT_IDENTIFIER_TAG
+            || read_u16(entry + 2)? != EXIF_TYPE_ASCII
+        {
+            return None;
+        }
+        let length = read_u32(entry + 4)?;
+        let value_at = if length <= 4 {
+            entry + 8
+        } else {
+            read_u32(entry + 8)?
+        };
+        let raw = maker_note.get(value_at..value_at + length)?;
+        let text = String::from_utf8_lossy(raw);
+        let text = text.trim_end_matches('\0').trim();
+        (!text.is_empty()).then(|| text.to_string())
+    })
+}
+
+fn mov_content_identifier(path: &Path) -> Result<Option<String>, String> {
+    let mut file =
+        File::open(path).map_err(|err| format!("failed to open {}: {err}", path.display()))?;
+    let file_len = file
+        .metadata()
+        .map_err(|err| format!("failed to stat {}: {err}", path.display()))?
+        .len();
+
+    // Top-level atoms are walked by header only; `mdat` can be large.
+    let mut offset = 0u64;
+    while offset + 8 <= file_len {
+        let mut header = [0u8; 16];
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut header[..8]))
+            .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        let kind: [u8; 4] = header[4..8].try_into().expect("4 bytes");
+        let (size, header_len) = match u32::from_be_bytes(header[..4].try_into().expect("4 bytes"))
+        {
+            0 => (file_len - offset, 8),
+            1 => {
+                file.read_exact(&mut header[8..16])
+                    .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+                (
+                    u64::from_be_bytes(header[8..16].try_into().expect("8 bytes")),
+                    16,
+                )
+            }
+            size => (u64::from(size), 8),
+        };
+        if size < header_len || offset + size > file_len {
+            return Err(format!(
+                "malformed atom at offset {offset} in {}",
+                path.display()
+            ));
+        }
+        if &kind == b"moov" {
+            if size > MAX_MOOV_BYTES {
+                return Ok(None);
+            }
+            let mut moov = vec![0u8; (size - header_len) as usize];
+            file.read_exact(&mut moov)
+                .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+            return Ok(content_identifier_from_moov(&moov));
+        }
+        offset += size;
+    }
+    Ok(None)
+}
+
+/// Looks the identifier up in `moov/meta`, where QuickTime stores `mdta` keys
+/// in `keys` and their values in `ilst` entries named by 1-based key index.
+fn content_identifier_from_moov(moov: &[u8]) -> Option<String> {
+    let meta = atoms(moov).find(|(kind, _)| kind == b"meta")?.1;
+    // QuickTime `meta` is a plain container, ISO BMFF `meta` has version/flags.
+    let meta = match meta.get(4..8) {
+        Some(b"hdlr") | Some(b"keys") | Some(b"ilst") => meta,
+        _ => meta.get(4..)?,
+    };
+    let keys = atoms(meta).find(|(kind, _)| kind == b"keys")?.1;
+    let ilst = atoms(meta).find(|(kind, _)| kind == b"ilst")?.1;
+
+    let entry_count = u32::from_be_bytes(keys.get(4..8)?.try_into().ok()?);
+    let mut at = 8;
+    let mut key_index = None;
+    for index in 1..=entry_count {
+        let size = u32::from_be_bytes(keys.get(at..at + 4)?.try_into().ok()?) as usize;
+        if size < 8 {
+            return None;
+        }
+        if keys.get(at + 8..at + size)? == QUICKTIME_CONTENT_IDENTIFIER_KEY {
+            key_index = Some(index);
+            break;
+        }
+        at += size;
+    }
+    let key_index = key_index?.to_be_bytes();
+
+    let item = atoms(ilst).find(|(kind, _)| *kind == key_index)?.1;
+    let data = atoms(item).find(|(kind, _)| kind == b"data")?.1;
+    if u32::from_be_bytes(data.get(0..4)?.try_into().ok()?) != QUICKTIME_TYPE_UTF8 {
+        return None;
+    }
+    let text = String::from_utf8_lossy(data.get(8..)?);
+    let text = text.trim_end_matches('\0').trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Iterates `(type, payload)` of the atoms packed in `data`, stopping at the
+/// first malformed size.
+fn atoms(data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    let mut offset = 0usize;
+    std::iter::from_fn(move || {
+        let header = data.get(offset..offset + 8)?;
+        let kind: [u8; 4] = header[4..8].try_into().ok()?;
+        let (size, header_len) = match u32::from_be_bytes(header[..4].try_into().ok()?) {
+            0 => (data.len() - offset, 8),
+            1 => (
+                usize::try_from(u64::from_be_bytes(
+                    data.get(offset + 8..offset + 16)?.try_into().ok()?,
+                ))
+                .ok()?,
+                16,
+            ),
+            size => (size as usize, 8),
+        };
+        if size < header_len {
+            return None;
+        }
+        let payload = data.get(offset + header_len..offset.checked_add(size)?)?;
+        offset += size;
+        Some((kind, payload))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::unique_temp_dir_path;
+
+    const IDENTIFIER: &str = "5B1A8F3C-4E2D-4C9A-9E61-0D2B7A1C3F44";
+
+    fn atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(kind);
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    fn live_photo_mov(identifier: &str) -> Vec<u8> {
+        let other_key = b"com.apple.quicktime.still-image-time";
+        let mut keys = vec![0, 0, 0, 0];
+        keys.extend_from_slice(&2u32.to_be_bytes());
+        for key in [&other_key[..], QUICKTIME_CONTENT_IDENTIFIER_KEY] {
+            keys.extend_from_slice(&((key.len() + 8) as u32).to_be_bytes());
+            keys.extend_from_slice(b"mdta");
+            keys.extend_from_slice(key);
+        }
+        let mut data = QUICKTIME_TYPE_UTF8.to_be_bytes().to_vec();
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(identifier.as_bytes());
+        let ilst = atom(&2u32.to_be_bytes(), &atom(b"data", &data));
+
+        let mut meta = atom(b"hdlr", &[0; 24]);
+        meta.extend(atom(b"keys", &keys));
+        meta.extend(atom(b"ilst", &ilst));
+        let mut moov = atom(b"mvhd", &[0; 100]);
+        moov.extend(atom(b"meta", &meta));
+
+        let mut file = atom(b"ftyp", b"qt  \0\0\0\0qt  ");
+        file.extend(atom(b"mdat", &[0xAB; 4096]));
+        file.extend(atom(b"moov", &moov));
+        file
+    }
+
+    fn maker_note(identifier: &str) -> Vec<u8> {
+        let mut note = APPLE_MAKER_NOTE_HEADER.to_vec();
+        note.extend_from_slice(&[0, 1]);
+        note.extend_from_slice(b"MM");
+        note.extend_from_slice(&2u16.to_be_bytes());
+        let value_at = APPLE_MAKER_NOTE_IFD_OFFSET + 2 + 2 * 12 + 4;
+        // A SHORT entry precedes the identifier.
+        note.extend_from_slice(&0x0008u16.to_be_bytes());
+        note.extend_from_slice(&3u16.to_be_bytes());
+        note.extend_from_slice(&1u32.to_be_bytes());
+        note.extend_from_slice(&[0, 7, 0, 0]);
+        note.extend_from_slice(&APPLE_CONTENT_IDENTIFIER_TAG.to_be_bytes());
+        note.extend_from_slice(&EXIF_TYPE_ASCII.to_be_bytes());
+        note.extend_from_slice(&((identifier.len() + 1) as u32).to_be_bytes());
+        note.extend_from_slice(&(value_at as u32).to_be_bytes());
+        note.extend_from_slice(&0u32.to_be_bytes());
+        note.extend_from_slice(identifier.as_bytes());
+        note.push(0);
+        note
+    }
+
+    #[test]
+    fn reads_content_identifier_from_apple_maker_note() {
+        assert_eq!(
+            content_identifier_from_maker_note(&maker_note(IDENTIFIER)).as_deref(),
+            Some(IDENTIFIER)
+        );
+        assert_eq!(content_identifier_from_maker_note(b"Nikon\0\x02\x10"), None);
+        let mut truncated = maker_note(IDENTIFIER);
+        truncated.truncate(40);
+        assert_eq!(content_identifier_from_maker_note(&truncated), None);
+    }
+
+    #[test]
+    fn reads_content_identifier_from_quicktime_metadata() {
+        let dir = unique_temp_dir_path("live_photo_mov");
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let mov = dir.join("IMG_0001.MOV");
+        fs::write(&mov, live_photo_mov(IDENTIFIER)).expect("write mov");
+        let plain = dir.join("clip.mov");
+        fs::write(&plain, atom(b"moov", &atom(b"mvhd", &[0; 100]))).expect("write plain");
+
+        assert_eq!(
+            mov_content_identifier(&mov).expect("parse").as_deref(),
+            Some(IDENTIFIER)
+        );
+        assert_eq!(mov_content_identifier(&plain).expect("parse"), None);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn malformed_mov_is_an_error() {
+        let dir = unique_temp_dir_path("live_photo_bad_mov");
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let mov = dir.join("IMG_0002.MOV");
+        let mut bytes = atom(b"ftyp", b"qt  ");
+        bytes[3] = 0xFF;
+        fs::write(&mov, bytes).expect("write mov");
+
+        assert!(mov_content_identifier(&mov).is_err());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
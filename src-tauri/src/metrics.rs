@@ -0,0 +1,374 @@
+use std::{
+    fmt::Write as _,
+    io::{self, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::Sender;
+
+use crate::{config::MetricsOptions, stats::JobOutcome};
+
+/// Upper bounds of the conversion duration histogram, in seconds.
+const DURATION_BUCKETS_SECS: [f64; 8] = [0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0];
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_REQUEST_HEAD_BYTES: usize = 8 * 1024;
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Counters and gauges fed by the dispatcher and the workers. They outlive
+/// watch service restarts so the counters stay monotonic.
+static METRICS: Mutex<MetricsState> = Mutex::new(MetricsState::new());
+
+/// The endpoint, while `metrics.enabled` is set.
+static SERVER: Mutex<Option<MetricsServer>> = Mutex::new(None);
+
+#[derive(Debug)]
+struct MetricsState {
+    queue_depth: u64,
+    in_flight: u64,
+    succeeded: u64,
+    failed: u64,
+    skipped: u64,
+    /// Per bucket of `DURATION_BUCKETS_SECS`, not cumulative.
+    duration_buckets: [u64; DURATION_BUCKETS_SECS.len()],
+    duration_count: u64,
+    duration_sum_ms: u64,
+    rescans: u64,
+    last_rescan_ms: u64,
+    watcher_errors: u64,
+}
+
+struct MetricsServer {
+    port: u16,
+    stop_tx: Sender<()>,
+    join_handle: thread::JoinHandle<()>,
+}
+
+/// Jobs waiting in the dispatcher and jobs handed to a worker.
+pub(crate) fn set_queue(queue_depth: usize, in_flight: usize) {
+    update(|state| {
+        state.queue_depth = queue_depth as u64;
+        state.in_flight = in_flight as u64;
+    });
+}
+
+pub(crate) fn record_job(outcome: &JobOutcome) {
+    update(|state| match outcome {
+        JobOutcome::Converted(details) => {
+            state.succeeded += 1;
+            state.duration_count += 1;
+            state.duration_sum_ms += details.duration_ms;
+            let secs = details.duration_ms as f64 / 1000.0;
+            if let Some(bucket) = DURATION_BUCKETS_SECS
+                .iter()
+                .position(|bound| secs <= *bound)
+            {
+                state.duration_buckets[bucket] += 1;
+            }
+        }
+        JobOutcome::Failed(_) => state.failed += 1,
+        JobOutcome::Skipped => state.skipped += 1,
+    });
+}
+
+pub(crate) fn record_rescan(duration: Duration) {
+    update(|state| {
+        state.rescans += 1;
+        state.last_rescan_ms = duration.as_millis() as u64;
+    });
+}
+
+/// Errors reported by the file system watcher, or a watcher that failed to start.
+pub(crate) fn record_watcher_error() {
+    update(|state| state.watcher_errors += 1);
+}
+
+fn update(apply: impl FnOnce(&mut MetricsState)) {
+    match METRICS.lock() {
+        Ok(mut state) => apply(&mut state),
+        Err(err) => log::error!("failed to lock metrics: {err}"),
+    }
+}
+
+/// Starts, moves or stops the endpoint to match `options`.
+pub(crate) fn configure(options: &MetricsOptions) {
+    let mut server = match SERVER.lock() {
+        Ok(server) => server,
+        Err(err) => {
+            log::error!("failed to lock metrics server: {err}");
+            return;
+        }
+    };
+    let wanted = options.enabled.then_some(options.port);
+    if server.as_ref().map(|server| server.port) == wanted {
+        return;
+    }
+    if let Some(existing) = server.take() {
+        existing.stop();
+    }
+    if let Some(port) = wanted {
+        match MetricsServer::start(port) {
+            Ok(started) => {
+                log::info!("metrics endpoint listening on http://127.0.0.1:{port}/metrics");
+                *server = Some(started);
+            }
+            Err(err) => log::error!("failed to start metrics endpoint on port {port}: {err}"),
+        }
+    }
+}
+
+impl MetricsServer {
+    /// Only listens on the loopback interface.
+    fn start(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let (stop_tx, stop_rx) = crossbeam_channel::bounded::<()>(1);
+        let join_handle = thread::Builder::new()
+            .name("metrics-endpoint".to_string())
+            .spawn(move || loop {
+                if stop_rx.try_recv().is_ok() {
+                    break;
+                }
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if let Err(err) = serve_connection(stream) {
+                            log::debug!("metrics request failed: {err}");
+                        }
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                    Err(err) => {
+                        log::warn!("metrics endpoint accept failed: {err}");
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                }
+            })?;
+        Ok(Self {
+            port,
+            stop_tx,
+            join_handle,
+        })
+    }
+
+    fn stop(self) {
+        let _ = self.stop_tx.send(());
+        if let Err(err) = self.join_handle.join() {
+            log::error!("failed to join metrics endpoint: {err:?}");
+        }
+    }
+}
+
+fn serve_connection(mut stream: TcpStream) -> io::Result<()> {
+    // Accepted sockets inherit non-blocking mode on some platforms.
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut head = Vec::new();
+    let mut buffer = [0_u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+        if head.len() > MAX_REQUEST_HEAD_BYTES {
+            break;
+        }
+    }
+    let request_line = String::from_utf8_lossy(&head)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let (status, body) = respond(&request_line);
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+fn respond(request_line: &str) -> (&'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next(), parts.next());
+    match (method, target) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render()),
+        (Some("GET"), Some(_)) => ("404 Not Found", "not found; try /metrics\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "only GET is supported\n".to_string(),
+        ),
+    }
+}
+
+fn render() -> String {
+    match METRICS.lock() {
+        Ok(state) => state.render(),
+        Err(err) => format!("# failed to lock metrics: {err}\n"),
+    }
+}
+
+impl MetricsState {
+    const fn new() -> Self {
+        Self {
+            queue_depth: 0,
+            in_flight: 0,
+            succeeded: 0,
+            failed: 0,
+            skipped: 0,
+            duration_buckets: [0; DURATION_BUCKETS_SECS.len()],
+            duration_count: 0,
+            duration_sum_ms: 0,
+            rescans: 0,
+            last_rescan_ms: 0,
+            watcher_errors: 0,
+        }
+    }
+
+    /// Prometheus text exposition format.
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            for (suffix, value) in samples {
+                let _ = writeln!(out, "{name}{suffix} {value}");
+            }
+        };
+        metric(
+            "heic_ready_queue_depth",
+            "gauge",
+            "Jobs waiting for a worker or for disk space.",
+            &[(String::new(), self.queue_depth.to_string())],
+        );
+        metric(
+            "heic_ready_jobs_in_flight",
+            "gauge",
+            "Jobs being processed by a worker.",
+            &[(String::new(), self.in_flight.to_string())],
+        );
+        metric(
+            "heic_ready_conversions_total",
+            "counter",
+            "Finished jobs by result.",
+            &[
+                (
+                    "{result=\"success\"}".to_string(),
+                    self.succeeded.to_string(),
+                ),
+                ("{result=\"failure\"}".to_string(), self.failed.to_string()),
+                ("{result=\"skip\"}".to_string(), self.skipped.to_string()),
+            ],
+        );
+        let mut cumulative = 0;
+        let mut buckets: Vec<(String, String)> = DURATION_BUCKETS_SECS
+            .iter()
+            .zip(self.duration_buckets)
+            .map(|(bound, count)| {
+                cumulative += count;
+                (format!("_bucket{{le=\"{bound}\"}}"), cumulative.to_string())
+            })
+            .collect();
+        buckets.push((
+            "_bucket{le=\"+Inf\"}".to_string(),
+            self.duration_count.to_string(),
+        ));
+        buckets.push((
+            "_sum".to_string(),
+            (self.duration_sum_ms as f64 / 1000.0).to_string(),
+        ));
+        buckets.push(("_count".to_string(), self.duration_count.to_string()));
+        metric(
+            "heic_ready_conversion_duration_seconds",
+            "histogram",
+            "Time from a stable source to finished outputs, for successful conversions.",
+            &buckets,
+        );
+        metric(
+            "heic_ready_rescans_total",
+            "counter",
+            "Periodic rescans of the watch folders.",
+            &[(String::new(), self.rescans.to_string())],
+        );
+        metric(
+            "heic_ready_last_rescan_duration_seconds",
+            "gauge",
+            "Duration of the latest rescan.",
+            &[(
+                String::new(),
+                (self.last_rescan_ms as f64 / 1000.0).to_string(),
+            )],
+        );
+        metric(
+            "heic_ready_watcher_errors_total",
+            "counter",
+            "File system watcher errors.",
+            &[(String::new(), self.watcher_errors.to_string())],
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::ConversionDetails;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut state = MetricsState::new();
+        state.succeeded = 3;
+        state.duration_buckets[1] = 2;
+        state.duration_buckets[4] = 1;
+        state.duration_count = 4;
+        state.duration_sum_ms = 70_500;
+        state.rescans = 2;
+        state.last_rescan_ms = 1_250;
+        let text = state.render();
+
+        assert!(text.contains("# TYPE heic_ready_conversion_duration_seconds histogram\n"));
+        assert!(text.contains("heic_ready_conversion_duration_seconds_bucket{le=\"0.25\"} 0\n"));
+        assert!(text.contains("heic_ready_conversion_duration_seconds_bucket{le=\"0.5\"} 2\n"));
+        assert!(text.contains("heic_ready_conversion_duration_seconds_bucket{le=\"60\"} 3\n"));
+        assert!(text.contains("heic_ready_conversion_duration_seconds_bucket{le=\"+Inf\"} 4\n"));
+        assert!(text.contains("heic_ready_conversion_duration_seconds_sum 70.5\n"));
+        assert!(text.contains("heic_ready_conversions_total{result=\"success\"} 3\n"));
+        assert!(text.contains("heic_ready_last_rescan_duration_seconds 1.25\n"));
+        assert_eq!(
+            respond("POST /metrics HTTP/1.1").0,
+            "405 Method Not Allowed"
+        );
+        assert_eq!(respond("GET / HTTP/1.1").0, "404 Not Found");
+    }
+
+    #[test]
+    fn endpoint_serves_recorded_jobs() {
+        let details = ConversionDetails {
+            duration_ms: 300,
+            ..ConversionDetails::default()
+        };
+        record_job(&JobOutcome::Converted(&details));
+        record_job(&JobOutcome::Failed("decode"));
+
+        let server = MetricsServer::start(0).expect("start server");
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, server.port)).expect("connect");
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .expect("send request");
+        let mut response = String::new();
+        stream.read_to_string(&mut response).expect("read response");
+        server.stop();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains("heic_ready_queue_depth "));
+        // Other tests may record jobs as well, so only check the lines exist.
+        assert!(response.contains("heic_ready_conversions_total{result=\"failure\"} "));
+        assert!(response.contains("heic_ready_conversion_duration_seconds_bucket{le=\"0.5\"} "));
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+
+use crate::{
+    config::{AppLocale, NotificationOptions},
+    watcher::BatchProgress,
+};
+
+/// How long the notifier thread sleeps when nothing is due.
+const IDLE_WAIT: Duration = Duration::from_secs(3600);
+
+/// What one desktop notification says, and which records it covers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Notice {
+    pub(crate) kind: NoticeKind,
+    pub(crate) from_unix_ms: u128,
+    pub(crate) to_unix_ms: u128,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum NoticeKind {
+    /// Failed jobs; `first` is the earliest of them.
+    Failures {
+        count: usize,
+        first: PathBuf,
+        code: &'static str,
+    },
+    /// Batches that finished within one window, added up.
+    BatchesDone { converted: usize, failed: usize },
+}
+
+pub(crate) type NoticeSink = Box<dyn Fn(Notice) + Send>;
+
+/// Turns failures and finished batches into few notifications: the first
+/// failure is shown at once and later ones wait for the end of its window;
+/// finished batches are summed over one window.
+struct Coalescer {
+    window: Duration,
+    /// Failures are held until this time once one was shown.
+    failures_quiet_until: Option<Instant>,
+    failures: Option<(Notice, Instant)>,
+    batches: Option<(Notice, Instant)>,
+}
+
+enum Message {
+    Options(NotificationOptions),
+    Failure {
+        path: PathBuf,
+        code: &'static str,
+        batch_started_unix_ms: Option<u128>,
+    },
+    BatchDone(BatchProgress),
+}
+
+/// Handle of the notifier thread; it stops once the handle is dropped.
+pub(crate) struct Notifier {
+    tx: Sender<Message>,
+}
+
+impl Notifier {
+    pub(crate) fn start(options: NotificationOptions, sink: NoticeSink) -> Result<Self, String> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        thread::Builder::new()
+            .name("notifier".to_string())
+            .spawn(move || run_notifier(options, &rx, &*sink))
+            .map_err(|err| format!("failed to spawn notifier: {err}"))?;
+        Ok(Self { tx })
+    }
+
+    pub(crate) fn set_options(&self, options: NotificationOptions) {
+        self.send(Message::Options(options));
+    }
+
+    /// The notice covers the records from the start of the failed job's batch.
+    pub(crate) fn failure(
+        &self,
+        path: &Path,
+        code: &'static str,
+        batch_started_unix_ms: Option<u128>,
+    ) {
+        self.send(Message::Failure {
+            path: path.to_path_buf(),
+            code,
+            batch_started_unix_ms,
+        });
+    }
+
+    pub(crate) fn batch_done(&self, progress: BatchProgress) {
+        self.send(Message::BatchDone(progress));
+    }
+
+    fn send(&self, message: Message) {
+        if self.tx.send(message).is_err() {
+            log::error!("notifier stopped; notification dropped");
+        }
+    }
+}
+
+fn run_notifier(mut options: NotificationOptions, rx: &Receiver<Message>, sink: &dyn Fn(Notice)) {
+    let mut coalescer = Coalescer::new(window_of(&options));
+    loop {
+        let wait = coalescer.next_due().map_or(IDLE_WAIT, |due| {
+            due.saturating_duration_since(Instant::now())
+        });
+        let message = match rx.recv_timeout(wait) {
+            Ok(message) => Some(message),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let now = Instant::now();
+        let now_ms = unix_ms_now();
+        let mut notices = Vec::new();
+        match message {
+            Some(Message::Options(next)) => {
+                options = next;
+                // Pending notices were collected under the old settings.
+                coalescer = Coalescer::new(window_of(&options));
+            }
+            Some(Message::Failure {
+                path,
+                code,
+                batch_started_unix_ms,
+            }) if options.enabled => {
+                let since = batch_started_unix_ms.unwrap_or(now_ms);
+                notices.extend(coalescer.failure(&path, code, now, since, now_ms));
+            }
+            Some(Message::BatchDone(progress)) if options.enabled => {
+                coalescer.batch_done(&progress, now, now_ms);
+            }
+            Some(_) | None => {}
+        }
+        notices.extend(coalescer.due(now));
+        for notice in notices {
+            sink(notice);
+        }
+    }
+}
+
+fn window_of(options: &NotificationOptions) -> Duration {
+    Duration::from_secs(options.coalesce_secs)
+}
+
+fn unix_ms_now() -> u128 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|value| value.as_millis())
+        .unwrap_or(0)
+}
+
+impl Coalescer {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            failures_quiet_until: None,
+            failures: None,
+            batches: None,
+        }
+    }
+
+    fn failure(
+        &mut self,
+        path: &Path,
+        code: &'static str,
+        now: Instant,
+        since_ms: u128,
+        now_ms: u128,
+    ) -> Option<Notice> {
+        let quiet = self.failures_quiet_until.is_some_and(|until| now < until);
+        if !quiet {
+            self.failures_quiet_until = Some(now + self.window);
+            return Some(Notice {
+                kind: NoticeKind::Failures {
+                    count: 1,
+                    first: path.to_path_buf(),
+                    code,
+                },
+                from_unix_ms: since_ms,
+                to_unix_ms: now_ms,
+            });
+        }
+        match &mut self.failures {
+            Some((notice, _)) => {
+                if let NoticeKind::Failures { count, .. } = &mut notice.kind {
+                    *count += 1;
+                }
+                notice.from_unix_ms = notice.from_unix_ms.min(since_ms);
+                notice.to_unix_ms = now_ms;
+            }
+            None => {
+                let due = self.failures_quiet_until.unwrap_or(now);
+                self.failures = Some((
+                    Notice {
+                        kind: NoticeKind::Failures {
+                            count: 1,
+                            first: path.to_path_buf(),
+                            code,
+                        },
+                        from_unix_ms: since_ms,
+                        to_unix_ms: now_ms,
+                    },
+                    due,
+                ));
+            }
+        }
+        None
+    }
+
+    /// Batches that only skipped files are not worth a notification.
+    fn batch_done(&mut self, progress: &BatchProgress, now: Instant, now_ms: u128) {
+        if progress.converted + progress.failed == 0 {
+            return;
+        }
+        let from_unix_ms = progress.started_unix_ms.unwrap_or(now_ms);
+        match &mut self.batches {
+            Some((notice, _)) => {
+                if let NoticeKind::BatchesDone { converted, failed } = &mut notice.kind {
+                    *converted += progress.converted;
+                    *failed += progress.failed;
+                }
+                notice.from_unix_ms = notice.from_unix_ms.min(from_unix_ms);
+                notice.to_unix_ms = now_ms;
+            }
+            None => {
+                self.batches = Some((
+                    Notice {
+                        kind: NoticeKind::BatchesDone {
+                            converted: progress.converted,
+                            failed: progress.failed,
+                        },
+                        from_unix_ms,
+                        to_unix_ms: now_ms,
+                    },
+                    now + self.window,
+                ));
+            }
+        }
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        [&self.failures, &self.batches]
+            .into_iter()
+            .filter_map(|pending| pending.as_ref().map(|(_, due)| *due))
+            .min()
+    }
+
+    fn due(&mut self, now: Instant) -> Vec<Notice> {
+        let mut notices = Vec::new();
+        if self.failures.as_ref().is_some_and(|(_, due)| *due <= now) {
+            notices.extend(self.failures.take().map(|(notice, _)| notice));
+            // Failures right after the held ones wait for another window.
+            self.failures_quiet_until = Some(now + self.window);
+        }
+        if self.batches.as_ref().is_some_and(|(_, due)| *due <= now) {
+            notices.extend(self.batches.take().map(|(notice, _)| notice));
+        }
+        notices
+    }
+}
+
+impl Notice {
+    /// Title and body in the UI language.
+    pub(crate) fn text(&self, locale: AppLocale) -> (String, String) {
+        match (&self.kind, locale) {
+            (NoticeKind::Failures { count: 1, .. }, AppLocale::En) => {
+                ("Conversion failed".to_string(), self.first_failure())
+            }
+            (NoticeKind::Failures { count: 1, .. }, AppLocale::Ja) => {
+                ("変換に失敗しました".to_string(), self.first_failure())
+            }
+            (NoticeKind::Failures { count, .. }, AppLocale::En) => (
+                format!("{count} files failed to convert"),
+                format!("{} and {} more", self.first_failure(), count - 1),
+            ),
+            (NoticeKind::Failures { count, .. }, AppLocale::Ja) => (
+                format!("{count} 件の変換に失敗しました"),
+                format!("{} ほか {} 件", self.first_failure(), count - 1),
+            ),
+            (NoticeKind::BatchesDone { converted, failed }, AppLocale::En) => {
+                let photos = if *converted == 1 { "photo" } else { "photos" };
+                let body = match failed {
+                    0 => format!("Converted {converted} {photos}"),
+                    _ => format!("Converted {converted} {photos}, {failed} failed"),
+                };
+                ("Conversion finished".to_string(), body)
+            }
+            (NoticeKind::BatchesDone { converted, failed }, AppLocale::Ja) => {
+                let body = match failed {
+                    0 => format!("{converted} 枚を変換しました"),
+                    _ => format!("{converted} 枚を変換、{failed} 件失敗しました"),
+                };
+                ("変換が完了しました".to_string(), body)
+            }
+        }
+    }
+
+    fn first_failure(&self) -> String {
+        match &self.kind {
+            NoticeKind::Failures { first, code, .. } => {
+                let name = first
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| first.display().to_string());
+                format!("{name} ({code})")
+            }
+            NoticeKind::BatchesDone { .. } => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_and_batches_are_coalesced_per_window() {
+        let window = Duration::from_secs(10);
+        let mut coalescer = Coalescer::new(window);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let batch = |converted, failed| BatchProgress {
+            converted,
+            failed,
+            started_unix_ms: Some(1_000),
+            ..BatchProgress::default()
+        };
+
+        let first = coalescer
+            .failure(Path::new("/inbox/a.heic"), "decode", at(0), 1_000, 5_000)
+            .expect("first failure is shown at once");
+        assert_eq!(
+            first.text(AppLocale::En),
+            (
+                "Conversion failed".to_string(),
+                "a.heic (decode)".to_string()
+            )
+        );
+        assert!(coalescer
+            .failure(Path::new("/inbox/b.heic"), "io", at(2), 6_500, 7_000)
+            .is_none());
+        assert!(coalescer
+            .failure(Path::new("/inbox/c.heic"), "io", at(3), 1_000, 8_000)
+            .is_none());
+        coalescer.batch_done(&batch(0, 0), at(4), 9_000);
+        coalescer.batch_done(&batch(46, 1), at(4), 9_000);
+        coalescer.batch_done(&batch(2, 2), at(9), 14_000);
+        assert!(coalescer.due(at(9)).is_empty());
+        assert_eq!(coalescer.next_due(), Some(at(10)));
+
+        let held = coalescer.due(at(10));
+        assert_eq!(held.len(), 1);
+        assert_eq!((held[0].from_unix_ms, held[0].to_unix_ms), (1_000, 8_000));
+        assert_eq!(
+            held[0].text(AppLocale::Ja),
+            (
+                "2 件の変換に失敗しました".to_string(),
+                "b.heic (io) ほか 1 件".to_string()
+            )
+        );
+
+        let batches = coalescer.due(at(14));
+        assert_eq!(batches.len(), 1);
+        assert_eq!(
+            (batches[0].from_unix_ms, batches[0].to_unix_ms),
+            (1_000, 14_000)
+        );
+        assert_eq!(
+            batches[0].text(AppLocale::En).1,
+            "Converted 48 photos, 3 failed"
+        );
+        assert!(coalescer
+            .failure(Path::new("/inbox/d.heic"), "io", at(30), 40_000, 40_000)
+            .is_some());
+    }
+}
@@ -0,0 +1,381 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::config::InputFormat;
+
+/// Enough for an `ftyp` box with a long list of compatible brands.
+const HEADER_LEN: u64 = 256;
+const TIFF_DNG_VERSION_TAG: u16 = 0xC612;
+const TIFF_IFD_ENTRY_LEN: usize = 12;
+const MAX_TIFF_IFD_ENTRIES: usize = 4096;
+/// Shorter than this, a file cannot hold any of the supported formats.
+const MIN_HEADER_LEN: usize = 12;
+const MAX_TOP_LEVEL_BOXES: usize = 1024;
+const JPEG_SOI: [u8; 3] = [0xFF, 0xD8, 0xFF];
+
+const HEIF_BRANDS: [&[u8; 4]; 12] = [
+    b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"hevm", b"hevs", b"mif1", b"msf1",
+    b"avci", b"avcs",
+];
+const AVIF_BRANDS: [&[u8; 4]; 2] = [b"avif", b"avis"];
+/// Structural HEIF brands shared by HEIC and AVIF files.
+const GENERIC_BRANDS: [&[u8; 4]; 2] = [b"mif1", b"msf1"];
+
+/// What a candidate file turned out to contain.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Sniffed {
+    Image(InputFormat),
+    /// Already a JPEG, whatever its extension says.
+    Jpeg,
+    /// A recognized container whose structure is cut short or malformed.
+    Corrupt(String),
+    Unknown,
+}
+
+/// Identifies the content of `path` from its leading bytes, regardless of its
+/// extension.
+pub(crate) fn sniff(path: &Path) -> io::Result<Sniffed> {
+    let mut file = File::open(path)?;
+    sniff_reader(&mut file)
+}
+
+fn sniff_reader<R: Read + Seek>(reader: &mut R) -> io::Result<Sniffed> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let mut header = Vec::new();
+    reader.by_ref().take(HEADER_LEN).read_to_end(&mut header)?;
+
+    if header.starts_with(&JPEG_SOI) {
+        return Ok(Sniffed::Jpeg);
+    }
+    if header.get(4..8) == Some(b"ftyp".as_slice()) {
+        return sniff_iso_bmff(reader, &header, file_len);
+    }
+    if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WEBP" {
+        let riff_len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let declared = u64::from(riff_len) + 8;
+        if declared > file_len {
+            return Ok(Sniffed::Corrupt(format!(
+                "truncated RIFF: {declared} bytes declared, {file_len} available"
+            )));
+        }
+        return Ok(Sniffed::Image(InputFormat::Webp));
+    }
+    if let Some(big_endian) = tiff_byte_order(&header) {
+        // DNG is TIFF with a DNGVersion tag in the first IFD.
+        let format = if has_dng_version(reader, &header, big_endian)? {
+            InputFormat::Dng
+        } else {
+            InputFormat::Tiff
+        };
+        return Ok(Sniffed::Image(format));
+    }
+    if header.len() < MIN_HEADER_LEN {
+        return Ok(Sniffed::Corrupt(format!(
+            "file is only {file_len} bytes long"
+        )));
+    }
+    Ok(Sniffed::Unknown)
+}
+
+/// Checks the `ftyp` box, then walks the top-level boxes so a file cut off
+/// mid-download is caught before the decoder sees it.
+fn sniff_iso_bmff<R: Read + Seek>(
+    reader: &mut R,
+    header: &[u8],
+    file_len: u64,
+) -> io::Result<Sniffed> {
+    let box_len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    if box_len < 16 || !box_len.is_multiple_of(4) {
+        return Ok(Sniffed::Corrupt(format!("invalid ftyp box size {box_len}")));
+    }
+    if header.len() < 16 {
+        return Ok(Sniffed::Corrupt("truncated ftyp box".to_string()));
+    }
+    let Some(format) = iso_bmff_format(header, box_len) else {
+        return Ok(Sniffed::Unknown);
+    };
+    if let Some(reason) = find_truncated_box(reader, file_len)? {
+        return Ok(Sniffed::Corrupt(reason));
+    }
+    Ok(Sniffed::Image(format))
+}
+
+fn find_truncated_box<R: Read + Seek>(reader: &mut R, file_len: u64) -> io::Result<Option<String>> {
+    let mut offset = 0u64;
+    for _ in 0..MAX_TOP_LEVEL_BOXES {
+        if offset >= file_len {
+            return Ok(None);
+        }
+        let remaining = file_len - offset;
+        if remaining < 8 {
+            return Ok(Some(format!("truncated box header at offset {offset}")));
+        }
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut box_header = [0u8; 8];
+        reader.read_exact(&mut box_header)?;
+        let kind = String::from_utf8_lossy(&box_header[4..8]).into_owned();
+        let (size, header_len) = match u32::from_be_bytes([
+            box_header[0],
+            box_header[1],
+            box_header[2],
+            box_header[3],
+        ]) {
+            // The last box may run to the end of the file.
+            0 => return Ok(None),
+            1 => {
+                if remaining < 16 {
+                    return Ok(Some(format!(
+                        "truncated {kind} box header at offset {offset}"
+                    )));
+                }
+                let mut large = [0u8; 8];
+                reader.read_exact(&mut large)?;
+                (u64::from_be_bytes(large), 16)
+            }
+            size => (u64::from(size), 8),
+        };
+        if size < header_len {
+            return Ok(Some(format!(
+                "invalid {kind} box size {size} at offset {offset}"
+            )));
+        }
+        if size > remaining {
+            return Ok(Some(format!(
+                "truncated {kind} box at offset {offset}: {size} bytes declared, {remaining} available"
+            )));
+        }
+        offset += size;
+    }
+    Ok(None)
+}
+
+/// The major brand decides; a generic `mif1` major brand is refined by the
+/// compatible brands, since AVIF files often declare it as well.
+fn iso_bmff_format(header: &[u8], box_len: usize) -> Option<InputFormat> {
+    let end = box_len.min(header.len());
+    let major: &[u8] = &header[8..12];
+    // Skip the minor version at 12..16.
+    let compatible: Vec<&[u8]> = header[16..end].chunks_exact(4).collect();
+
+    let is_avif = |brand: &[u8]| AVIF_BRANDS.iter().any(|known| brand == known.as_slice());
+    let is_heif = |brand: &[u8]| HEIF_BRANDS.iter().any(|known| brand == known.as_slice());
+    let is_generic = |brand: &[u8]| GENERIC_BRANDS.iter().any(|known| brand == known.as_slice());
+    if is_avif(major) {
+        return Some(InputFormat::Avif);
+    }
+    if is_heif(major) && !is_generic(major) {
+        return Some(InputFormat::Heic);
+    }
+    if compatible.iter().any(|brand| is_avif(brand)) {
+        return Some(InputFormat::Avif);
+    }
+    if is_heif(major) || compatible.iter().any(|brand| is_heif(brand)) {
+        return Some(InputFormat::Heic);
+    }
+    None
+}
+
+fn tiff_byte_order(header: &[u8]) -> Option<bool> {
+    match header.get(0..4)? {
+        [b'I', b'I', 42, 0] => Some(false),
+        [b'M', b'M', 0, 42] => Some(true),
+        _ => None,
+    }
+}
+
+fn has_dng_version<R: Read + Seek>(
+    reader: &mut R,
+    header: &[u8],
+    big_endian: bool,
+) -> io::Result<bool> {
+    let Some(offset) = header.get(4..8) else {
+        return Ok(false);
+    };
+    let read_u16 = |bytes: [u8; 2]| {
+        if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    };
+    let offset = [offset[0], offset[1], offset[2], offset[3]];
+    let offset = if big_endian {
+        u32::from_be_bytes(offset)
+    } else {
+        u32::from_le_bytes(offset)
+    };
+
+    reader.seek(SeekFrom::Start(u64::from(offset)))?;
+    let mut count = [0u8; 2];
+    if reader.read_exact(&mut count).is_err() {
+        return Ok(false);
+    }
+    let count = usize::from(read_u16(count)).min(MAX_TIFF_IFD_ENTRIES);
+    let mut entries = Vec::with_capacity(count * TIFF_IFD_ENTRY_LEN);
+    reader
+        .by_ref()
+        .take((count * TIFF_IFD_ENTRY_LEN) as u64)
+        .read_to_end(&mut entries)?;
+    Ok(entries
+        .chunks_exact(TIFF_IFD_ENTRY_LEN)
+        .any(|entry| read_u16([entry[0], entry[1]]) == TIFF_DNG_VERSION_TAG))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn ftyp(major: &[u8; 4], compatible: &[&[u8; 4]]) -> Vec<u8> {
+        let len = 16 + compatible.len() * 4;
+        let mut bytes = (len as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(b"ftyp");
+        bytes.extend_from_slice(major);
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        for brand in compatible {
+            bytes.extend_from_slice(*brand);
+        }
+        // The next box must not be mistaken for more brands.
+        bytes.extend_from_slice(&[0, 0, 0, 8]);
+        bytes.extend_from_slice(b"avif");
+        bytes
+    }
+
+    fn tiff(big_endian: bool, tags: &[u16]) -> Vec<u8> {
+        let u16_bytes = |value: u16| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        let mut bytes = if big_endian {
+            vec![b'M', b'M', 0, 42, 0, 0, 0, 8]
+        } else {
+            vec![b'I', b'I', 42, 0, 8, 0, 0, 0]
+        };
+        bytes.extend_from_slice(&u16_bytes(tags.len() as u16));
+        for tag in tags {
+            bytes.extend_from_slice(&u16_bytes(*tag));
+            bytes.extend_from_slice(&[0; TIFF_IFD_ENTRY_LEN - 2]);
+        }
+        bytes
+    }
+
+    fn sniff_bytes(bytes: Vec<u8>) -> Sniffed {
+        sniff_reader(&mut Cursor::new(bytes)).expect("sniff")
+    }
+
+    #[test]
+    fn iso_bmff_brands_distinguish_heic_and_avif() {
+        assert_eq!(
+            sniff_bytes(ftyp(b"heic", &[b"mif1", b"heic"])),
+            Sniffed::Image(InputFormat::Heic)
+        );
+        assert_eq!(
+            sniff_bytes(ftyp(b"msf1", &[b"hevc"])),
+            Sniffed::Image(InputFormat::Heic)
+        );
+        assert_eq!(
+            sniff_bytes(ftyp(b"mif1", &[b"mif1", b"miaf", b"MA1B", b"avif"])),
+            Sniffed::Image(InputFormat::Avif)
+        );
+        assert_eq!(
+            sniff_bytes(ftyp(b"avis", &[])),
+            Sniffed::Image(InputFormat::Avif)
+        );
+        assert_eq!(
+            sniff_bytes(ftyp(b"heic", &[b"mif1", b"avif"])),
+            Sniffed::Image(InputFormat::Heic)
+        );
+        assert_eq!(sniff_bytes(ftyp(b"qt  ", &[b"qt  "])), Sniffed::Unknown);
+    }
+
+    #[test]
+    fn webp_and_tiff_family_are_detected() {
+        let mut webp = b"RIFF".to_vec();
+        webp.extend_from_slice(&[8, 0, 0, 0]);
+        webp.extend_from_slice(b"WEBPVP8 ");
+        assert_eq!(sniff_bytes(webp), Sniffed::Image(InputFormat::Webp));
+
+        assert_eq!(
+            sniff_bytes(tiff(false, &[0x0100, 0x0101])),
+            Sniffed::Image(InputFormat::Tiff)
+        );
+        assert_eq!(
+            sniff_bytes(tiff(false, &[0x0100, TIFF_DNG_VERSION_TAG])),
+            Sniffed::Image(InputFormat::Dng)
+        );
+        assert_eq!(
+            sniff_bytes(tiff(true, &[TIFF_DNG_VERSION_TAG])),
+            Sniffed::Image(InputFormat::Dng)
+        );
+    }
+
+    #[test]
+    fn unknown_content_is_not_detected() {
+        assert_eq!(sniff_bytes(b"plain text notes".to_vec()), Sniffed::Unknown);
+        // A TIFF header pointing past the end of the file is plain TIFF.
+        assert_eq!(
+            sniff_bytes(vec![b'I', b'I', 42, 0, 0xFF, 0, 0, 0]),
+            Sniffed::Image(InputFormat::Tiff)
+        );
+    }
+
+    #[test]
+    fn jpeg_is_recognized_whatever_the_name() {
+        assert_eq!(
+            sniff_bytes(vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 16]),
+            Sniffed::Jpeg
+        );
+    }
+
+    #[test]
+    fn iso_bmff_box_walk_accepts_large_and_open_ended_boxes() {
+        let mut bytes = ftyp(b"heic", &[b"mif1"]);
+        // 64-bit size: 16 header bytes + 4 payload bytes.
+        bytes.extend_from_slice(&[0, 0, 0, 1]);
+        bytes.extend_from_slice(b"mdat");
+        bytes.extend_from_slice(&20u64.to_be_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        // Size 0: runs to the end of the file.
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        bytes.extend_from_slice(b"free");
+        bytes.extend_from_slice(&[0; 32]);
+        assert_eq!(sniff_bytes(bytes), Sniffed::Image(InputFormat::Heic));
+    }
+
+    #[test]
+    fn truncated_or_malformed_files_are_corrupt() {
+        let corrupt = |bytes: Vec<u8>| match sniff_bytes(bytes) {
+            Sniffed::Corrupt(reason) => reason,
+            other => panic!("expected corrupt, got {other:?}"),
+        };
+
+        let mut cut_off = ftyp(b"heic", &[b"mif1"]);
+        cut_off.extend_from_slice(&4096u32.to_be_bytes());
+        cut_off.extend_from_slice(b"mdat");
+        cut_off.extend_from_slice(&[0; 100]);
+        assert!(corrupt(cut_off).contains("truncated mdat box"));
+
+        let mut bad_ftyp = ftyp(b"heic", &[]);
+        bad_ftyp[3] = 10;
+        assert!(corrupt(bad_ftyp).contains("invalid ftyp box size 10"));
+
+        let mut dangling = ftyp(b"heic", &[]);
+        dangling.extend_from_slice(&[0, 0, 0]);
+        assert!(corrupt(dangling).contains("truncated box header"));
+
+        let mut webp = b"RIFF".to_vec();
+        webp.extend_from_slice(&1000u32.to_le_bytes());
+        webp.extend_from_slice(b"WEBPVP8 ");
+        assert!(corrupt(webp).contains("truncated RIFF"));
+
+        assert!(corrupt(Vec::new()).contains("only 0 bytes"));
+        assert!(corrupt(vec![0, 0, 0, 24, b'f', b't']).contains("only 6 bytes"));
+    }
+}
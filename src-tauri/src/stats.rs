@@ -0,0 +1,321 @@
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    date::civil_from_days,
+    json_store::{now_unix_ms, JsonStore},
+    watcher::ConversionDetails,
+};
+
+/// Per-day counters older than this are dropped.
+const DAYS_KEPT: usize = 400;
+const TOP_FOLDER_LIMIT: usize = 5;
+const MS_PER_DAY: u128 = 24 * 60 * 60 * 1000;
+
+/// Serializes read-modify-write cycles of the stats file; every worker
+/// records its jobs.
+static STATS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StatsFile {
+    /// Keyed by UTC day, `YYYY-MM-DD`.
+    #[serde(default)]
+    days: BTreeMap<String, DayCounters>,
+    /// Keyed by watch folder, never pruned.
+    #[serde(default)]
+    folders: BTreeMap<PathBuf, FolderCounters>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct DayCounters {
+    converted: u64,
+    failed: u64,
+    skipped: u64,
+    bytes_in: u64,
+    bytes_out: u64,
+    /// Total over the converted jobs.
+    duration_ms: u64,
+    failures_by_code: BTreeMap<String, u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FolderCounters {
+    pub converted: u64,
+    pub failed: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+/// How one job ended, as far as the statistics care.
+pub(crate) enum JobOutcome<'a> {
+    Converted(&'a ConversionDetails),
+    /// With the `ConvertError` code.
+    Failed(&'static str),
+    Skipped,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct DayStats {
+    pub date: String,
+    pub converted: u64,
+    pub failed: u64,
+    pub skipped: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CodeStats {
+    pub code: String,
+    pub count: u64,
+    /// Share of the converted and failed jobs in the period.
+    pub rate: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FolderStats {
+    pub folder: PathBuf,
+    #[serde(flatten)]
+    pub counters: FolderCounters,
+}
+
+/// Aggregates for the stats view. `days` covers the requested period, oldest
+/// first and without gaps; the folders are all-time.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct StatsSummary {
+    pub days: Vec<DayStats>,
+    pub converted: u64,
+    pub failed: u64,
+    pub skipped: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub average_duration_ms: Option<u64>,
+    pub failure_rate: f64,
+    pub failures_by_code: Vec<CodeStats>,
+    pub top_folders: Vec<FolderStats>,
+}
+
+/// Conversion counters per day and per watch folder, persisted next to the
+/// config so they survive restarts.
+#[derive(Clone, Debug)]
+pub(crate) struct StatsStore {
+    store: JsonStore,
+}
+
+impl StatsStore {
+    pub(crate) fn new(path: &Path) -> Self {
+        Self {
+            store: JsonStore::new(path, "stats", &STATS_LOCK),
+        }
+    }
+
+    /// Counts one job under today and `folder`, the watch folder it came from.
+    pub(crate) fn record(&self, folder: Option<&Path>, outcome: JobOutcome) -> Result<(), String> {
+        self.record_at(now_unix_ms(), folder, outcome)
+    }
+
+    fn record_at(
+        &self,
+        now_unix_ms: u128,
+        folder: Option<&Path>,
+        outcome: JobOutcome,
+    ) -> Result<(), String> {
+        let _guard = self.store.lock()?;
+        let mut file: StatsFile = self.store.load();
+        let day = file.days.entry(day_key(now_unix_ms)).or_default();
+        let mut folder_counters =
+            folder.map(|folder| file.folders.entry(folder.to_path_buf()).or_default());
+        match outcome {
+            JobOutcome::Converted(details) => {
+                let bytes_in = details.source_bytes.unwrap_or(0);
+                day.converted += 1;
+                day.bytes_in += bytes_in;
+                day.bytes_out += details.output_bytes;
+                day.duration_ms += details.duration_ms;
+                if let Some(counters) = folder_counters.as_mut() {
+                    counters.converted += 1;
+                    counters.bytes_in += bytes_in;
+                    counters.bytes_out += details.output_bytes;
+                }
+            }
+            JobOutcome::Failed(code) => {
+                day.failed += 1;
+                *day.failures_by_code.entry(code.to_string()).or_default() += 1;
+                if let Some(counters) = folder_counters.as_mut() {
+                    counters.failed += 1;
+                }
+            }
+            JobOutcome::Skipped => day.skipped += 1,
+        }
+        if file.days.len() > DAYS_KEPT {
+            let excess = file.days.len() - DAYS_KEPT;
+            let expired: Vec<String> = file.days.keys().take(excess).cloned().collect();
+            for key in expired {
+                file.days.remove(&key);
+            }
+        }
+        self.store.save(&file)
+    }
+
+    /// Aggregates the last `days` days up to and including today.
+    pub(crate) fn summary(&self, days: u32) -> Result<StatsSummary, String> {
+        self.summary_at(now_unix_ms(), days)
+    }
+
+    fn summary_at(&self, now_unix_ms: u128, days: u32) -> Result<StatsSummary, String> {
+        let file: StatsFile = {
+            let _guard = self.store.lock()?;
+            self.store.load()
+        };
+        let days = (days as usize).clamp(1, DAYS_KEPT);
+        let mut total = DayCounters::default();
+        let mut day_stats = Vec::with_capacity(days);
+        for offset in (0..days as u128).rev() {
+            let date = day_key(now_unix_ms.saturating_sub(offset * MS_PER_DAY));
+            let counters = file.days.get(&date).cloned().unwrap_or_default();
+            total.converted += counters.converted;
+            total.failed += counters.failed;
+            total.skipped += counters.skipped;
+            total.bytes_in += counters.bytes_in;
+            total.bytes_out += counters.bytes_out;
+            total.duration_ms += counters.duration_ms;
+            for (code, count) in &counters.failures_by_code {
+                *total.failures_by_code.entry(code.clone()).or_default() += count;
+            }
+            day_stats.push(DayStats {
+                date,
+                converted: counters.converted,
+                failed: counters.failed,
+                skipped: counters.skipped,
+                bytes_in: counters.bytes_in,
+                bytes_out: counters.bytes_out,
+            });
+        }
+
+        let attempts = total.converted + total.failed;
+        let rate = |count: u64| {
+            if attempts == 0 {
+                0.0
+            } else {
+                count as f64 / attempts as f64
+            }
+        };
+        let mut failures_by_code: Vec<CodeStats> = total
+            .failures_by_code
+            .iter()
+            .map(|(code, count)| CodeStats {
+                code: code.clone(),
+                count: *count,
+                rate: rate(*count),
+            })
+            .collect();
+        failures_by_code.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.code.cmp(&b.code)));
+        let mut top_folders: Vec<FolderStats> = file
+            .folders
+            .into_iter()
+            .map(|(folder, counters)| FolderStats { folder, counters })
+            .collect();
+        top_folders.sort_by_key(|folder| Reverse(folder.counters.converted));
+        top_folders.truncate(TOP_FOLDER_LIMIT);
+
+        Ok(StatsSummary {
+            days: day_stats,
+            converted: total.converted,
+            failed: total.failed,
+            skipped: total.skipped,
+            bytes_in: total.bytes_in,
+            bytes_out: total.bytes_out,
+            average_duration_ms: total.duration_ms.checked_div(total.converted),
+            failure_rate: rate(total.failed),
+            failures_by_code,
+            top_folders,
+        })
+    }
+}
+
+fn day_key(unix_ms: u128) -> String {
+    let (year, month, day) = civil_from_days((unix_ms / MS_PER_DAY) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::unique_temp_dir_path;
+    use std::fs;
+
+    fn converted(source_bytes: u64, output_bytes: u64, duration_ms: u64) -> ConversionDetails {
+        ConversionDetails {
+            policy: "coexist".to_string(),
+            source_bytes: Some(source_bytes),
+            output_bytes,
+            duration_ms,
+            ..ConversionDetails::default()
+        }
+    }
+
+    #[test]
+    fn summary_aggregates_days_codes_and_folders() {
+        let dir = unique_temp_dir_path("stats");
+        let store = StatsStore::new(&dir.join("stats.json"));
+        let inbox = Path::new("/inbox");
+        let camera = Path::new("/camera");
+        // 2025-10-17 and 2025-10-18, UTC.
+        let yesterday = 1_760_700_000_000;
+        let today = yesterday + MS_PER_DAY;
+        let first = converted(1_000, 400, 100);
+        let second = converted(3_000, 1_000, 300);
+        let third = converted(500, 200, 200);
+        store
+            .record_at(yesterday, Some(inbox), JobOutcome::Converted(&first))
+            .expect("record");
+        store
+            .record_at(today, Some(inbox), JobOutcome::Converted(&second))
+            .expect("record");
+        store
+            .record_at(today, Some(camera), JobOutcome::Converted(&third))
+            .expect("record");
+        store
+            .record_at(today, Some(camera), JobOutcome::Failed("decode"))
+            .expect("record");
+        store
+            .record_at(today, None, JobOutcome::Skipped)
+            .expect("record");
+
+        let summary = store.summary_at(today, 3).expect("summary");
+        let dates: Vec<&str> = summary.days.iter().map(|day| day.date.as_str()).collect();
+        assert_eq!(dates, vec!["2025-10-16", "2025-10-17", "2025-10-18"]);
+        assert_eq!(summary.days[2].converted, 2);
+        assert_eq!(
+            (summary.converted, summary.failed, summary.skipped),
+            (3, 1, 1)
+        );
+        assert_eq!((summary.bytes_in, summary.bytes_out), (4_500, 1_600));
+        assert_eq!(summary.average_duration_ms, Some(200));
+        assert_eq!(summary.failure_rate, 0.25);
+        assert_eq!(
+            summary.failures_by_code,
+            vec![CodeStats {
+                code: "decode".to_string(),
+                count: 1,
+                rate: 0.25,
+            }]
+        );
+        assert_eq!(summary.top_folders[0].folder, inbox);
+        assert_eq!(summary.top_folders[0].counters.converted, 2);
+        assert_eq!(summary.top_folders[1].counters.failed, 1);
+
+        let today_only = store.summary_at(today, 1).expect("summary");
+        assert_eq!(today_only.converted, 2);
+        assert_eq!(today_only.average_duration_ms, Some(250));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
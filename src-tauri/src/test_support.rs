@@ -0,0 +1,19 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+fn stamp() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time")
+        .as_nanos()
+}
+
+pub(crate) fn unique_temp_file_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("heic-ready_{}_{name}", stamp()))
+}
+
+pub(crate) fn unique_temp_dir_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("heic-ready_dir_{}_{name}", stamp()))
+}
@@ -0,0 +1,535 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::error::ConvertError;
+
+/// `EXDEV`: the trash is on another volume, so the file has to be copied.
+const CROSS_DEVICE_LINK: i32 = 18;
+const OVERWRITE_CHUNK: usize = 64 * 1024;
+
+/// Moves `path` into the user's trash and returns where it ended up.
+pub(crate) fn move_to_trash(path: &Path) -> Result<PathBuf, ConvertError> {
+    platform::move_to_trash(path)
+}
+
+/// The directory files from `folder` are trashed into, so replace mode can
+/// check it is writable before it is needed.
+pub(crate) fn trash_dir_for(folder: &Path) -> Result<PathBuf, String> {
+    platform::trash_dir_for(folder)
+}
+
+/// Whether `path` lies in a trash directory, where the watcher must not
+/// convert the originals replace mode moved there.
+pub(crate) fn is_in_trash(path: &Path) -> bool {
+    path.ancestors().skip(1).any(is_trash_dir)
+}
+
+/// `~/.Trash`, a volume's `.Trashes`, `.Trash/$uid` or `.Trash-$uid`, or the
+/// freedesktop.org home trash.
+pub(crate) fn is_trash_dir(dir: &Path) -> bool {
+    let has_trash_name = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            name == ".Trash"
+                || name == ".Trashes"
+                || name.strip_prefix(".Trash-").is_some_and(|uid| {
+                    !uid.is_empty() && uid.bytes().all(|byte| byte.is_ascii_digit())
+                })
+        });
+    has_trash_name || platform::is_home_trash(dir)
+}
+
+/// Moves a file trashed by [`move_to_trash`] back to `original`, which must
+/// not exist yet, and drops its trash metadata.
+pub(crate) fn restore_from_trash(trashed: &Path, original: &Path) -> Result<(), ConvertError> {
+    if fs::symlink_metadata(original).is_ok() {
+        return Err(ConvertError::Trash(format!(
+            "{} already exists",
+            original.display()
+        )));
+    }
+    if fs::symlink_metadata(trashed).is_err() {
+        return Err(ConvertError::Trash(format!(
+            "{} is no longer in the trash",
+            trashed.display()
+        )));
+    }
+    if let Some(parent) = original.parent() {
+        fs::create_dir_all(parent).map_err(|err| {
+            ConvertError::from_io(
+                ConvertError::Trash,
+                format_args!("failed to create {}", parent.display()),
+                &err,
+            )
+        })?;
+    }
+    rename_or_copy(trashed, original)?;
+    platform::forget_trashed(trashed);
+    Ok(())
+}
+
+/// Deletes `path` for good, overwriting its contents with zeros first when
+/// `overwrite` is set.
+pub(crate) fn delete_permanently(path: &Path, overwrite: bool) -> Result<(), ConvertError> {
+    if overwrite {
+        overwrite_with_zeros(path).map_err(|err| {
+            ConvertError::from_io(
+                ConvertError::Delete,
+                format_args!("failed to overwrite {}", path.display()),
+                &err,
+            )
+        })?;
+    }
+    fs::remove_file(path).map_err(|err| {
+        ConvertError::from_io(
+            ConvertError::Delete,
+            format_args!("failed to delete {}", path.display()),
+            &err,
+        )
+    })
+}
+
+fn overwrite_with_zeros(path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut remaining = file.metadata()?.len();
+    let zeros = [0u8; OVERWRITE_CHUNK];
+    while remaining > 0 {
+        let chunk = remaining.min(OVERWRITE_CHUNK as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()
+}
+
+fn rename_or_copy(path: &Path, destination: &Path) -> Result<(), ConvertError> {
+    move_file(path, destination, ConvertError::Trash)
+}
+
+/// Renames `path` to `destination`, copying and removing it when they are on
+/// different volumes. Failures are reported as `stage`.
+pub(crate) fn move_file(
+    path: &Path,
+    destination: &Path,
+    stage: fn(String) -> ConvertError,
+) -> Result<(), ConvertError> {
+    match fs::rename(path, destination) {
+        Ok(()) => Ok(()),
+        Err(err) if err.raw_os_error() == Some(CROSS_DEVICE_LINK) => {
+            fs::copy(path, destination).map_err(|copy_err| {
+                ConvertError::from_io(
+                    stage,
+                    format_args!("failed to copy file to {}", destination.display()),
+                    &copy_err,
+                )
+            })?;
+            match fs::remove_file(path) {
+                Ok(()) => Ok(()),
+                Err(remove_err) => {
+                    // Recovery: remove the copy so the source remains authoritative.
+                    let _ = fs::remove_file(destination);
+                    Err(ConvertError::from_io(
+                        stage,
+                        "failed to remove source after copy; rolled back copied file",
+                        &remove_err,
+                    ))
+                }
+            }
+        }
+        Err(err) => Err(ConvertError::from_io(
+            stage,
+            format_args!("failed to move file to {}", destination.display()),
+            &err,
+        )),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::*;
+    use crate::watcher::unique_destination_path;
+
+    pub(super) fn move_to_trash(path: &Path) -> Result<PathBuf, ConvertError> {
+        let trash_dir = user_trash_dir().map_err(ConvertError::Trash)?;
+        fs::create_dir_all(&trash_dir).map_err(|err| {
+            ConvertError::from_io(
+                ConvertError::Trash,
+                format_args!("failed to create trash dir {}", trash_dir.display()),
+                &err,
+            )
+        })?;
+        let destination = unique_destination_path(&trash_dir, path);
+        rename_or_copy(path, &destination)?;
+        Ok(destination)
+    }
+
+    pub(super) fn trash_dir_for(_folder: &Path) -> Result<PathBuf, String> {
+        user_trash_dir()
+    }
+
+    /// `~/.Trash` keeps no metadata of its own.
+    pub(super) fn forget_trashed(_trashed: &Path) {}
+
+    /// `~/.Trash` is already recognized by its name.
+    pub(super) fn is_home_trash(_dir: &Path) -> bool {
+        false
+    }
+
+    fn user_trash_dir() -> Result<PathBuf, String> {
+        let home = std::env::var_os("HOME").ok_or_else(|| "HOME is not set".to_string())?;
+        Ok(PathBuf::from(home).join(".Trash"))
+    }
+}
+
+/// The freedesktop.org Trash specification: files go to `files/` with a
+/// `.trashinfo` in `info/` recording where they came from, so file managers
+/// can list and restore them.
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::{
+        fs::{DirBuilder, OpenOptions},
+        io::{self, Write},
+        os::unix::{
+            ffi::OsStrExt,
+            fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+        },
+    };
+
+    use percent_encoding::{percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+    use super::*;
+
+    /// RFC 2396 escaping of the `Path=` key, keeping `/` readable.
+    const PATH_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+        .remove(b'/')
+        .remove(b'-')
+        .remove(b'_')
+        .remove(b'.')
+        .remove(b'~');
+    const STICKY_BIT: u32 = 0o1000;
+
+    struct TrashDir {
+        root: PathBuf,
+        /// Set for a volume's own trash, whose info files hold paths
+        /// relative to its top directory.
+        topdir: Option<PathBuf>,
+    }
+
+    pub(super) fn move_to_trash(path: &Path) -> Result<PathBuf, ConvertError> {
+        let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+        let trash = trash_for(parent).map_err(ConvertError::Trash)?;
+        let recorded = match &trash.topdir {
+            Some(topdir) => path.strip_prefix(topdir).unwrap_or(path),
+            None => path,
+        };
+        trash_into(&trash.root, path, recorded)
+    }
+
+    pub(super) fn trash_dir_for(folder: &Path) -> Result<PathBuf, String> {
+        Ok(trash_for(folder)?.root.join("files"))
+    }
+
+    /// Removes the `.trashinfo` of a file taken back out of `files/`.
+    pub(super) fn forget_trashed(trashed: &Path) {
+        let (Some(files), Some(name)) = (trashed.parent(), trashed.file_name()) else {
+            return;
+        };
+        let Some(root) = files.parent() else {
+            return;
+        };
+        let mut info_name = name.to_os_string();
+        info_name.push(".trashinfo");
+        let info_path = root.join("info").join(info_name);
+        if let Err(err) = fs::remove_file(&info_path) {
+            if err.kind() != io::ErrorKind::NotFound {
+                log::warn!("failed to remove {}: {err}", info_path.display());
+            }
+        }
+    }
+
+    pub(super) fn is_home_trash(dir: &Path) -> bool {
+        home_trash_root().is_ok_and(|root| dir == root)
+    }
+
+    /// The home trash when `dir` shares its volume, otherwise the volume's own
+    /// trash; if that cannot be used the home trash still takes the file as a copy.
+    fn trash_for(dir: &Path) -> Result<TrashDir, String> {
+        let home = home_trash()?;
+        let device = device_of(dir)?;
+        if device_of(&home)? == device {
+            return Ok(TrashDir {
+                root: home,
+                topdir: None,
+            });
+        }
+        let topdir = mount_point(dir, device);
+        match topdir_trash(&topdir) {
+            Ok(root) => Ok(TrashDir {
+                root,
+                topdir: Some(topdir),
+            }),
+            Err(err) => {
+                log::warn!(
+                    "falling back to the home trash for {}: {err}",
+                    topdir.display()
+                );
+                Ok(TrashDir {
+                    root: home,
+                    topdir: None,
+                })
+            }
+        }
+    }
+
+    fn home_trash() -> Result<PathBuf, String> {
+        let root = home_trash_root()?;
+        create_trash_dirs(&root)?;
+        Ok(root)
+    }
+
+    fn home_trash_root() -> Result<PathBuf, String> {
+        let data_home = match std::env::var_os("XDG_DATA_HOME").map(PathBuf::from) {
+            Some(path) if path.is_absolute() => path,
+            _ => {
+                let home = std::env::var_os("HOME").ok_or_else(|| "HOME is not set".to_string())?;
+                PathBuf::from(home).join(".local/share")
+            }
+        };
+        Ok(data_home.join("Trash"))
+    }
+
+    /// Prefers an administrator-provided `$topdir/.Trash/$uid`, which is only
+    /// trusted when `.Trash` is a real sticky directory, then `$topdir/.Trash-$uid`.
+    pub(super) fn topdir_trash(topdir: &Path) -> Result<PathBuf, String> {
+        let uid = current_uid();
+        let shared = topdir.join(".Trash");
+        if let Ok(metadata) = fs::symlink_metadata(&shared) {
+            if metadata.is_dir() && metadata.permissions().mode() & STICKY_BIT != 0 {
+                let root = shared.join(uid.to_string());
+                if create_trash_dirs(&root).is_ok() {
+                    return Ok(root);
+                }
+            }
+        }
+        let root = topdir.join(format!(".Trash-{uid}"));
+        create_trash_dirs(&root)?;
+        let metadata = fs::symlink_metadata(&root)
+            .map_err(|err| format!("failed to inspect {}: {err}", root.display()))?;
+        if !metadata.is_dir() || metadata.uid() != uid {
+            return Err(format!("{} is not a trash owned by us", root.display()));
+        }
+        Ok(root)
+    }
+
+    fn create_trash_dirs(root: &Path) -> Result<(), String> {
+        for dir in [root.join("files"), root.join("info")] {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(&dir)
+                .map_err(|err| format!("failed to create {}: {err}", dir.display()))?;
+        }
+        Ok(())
+    }
+
+    fn device_of(path: &Path) -> Result<u64, String> {
+        fs::metadata(path)
+            .map(|metadata| metadata.dev())
+            .map_err(|err| format!("failed to inspect {}: {err}", path.display()))
+    }
+
+    /// The topmost ancestor of `dir` still on `device`.
+    fn mount_point(dir: &Path, device: u64) -> PathBuf {
+        dir.ancestors()
+            .take_while(|ancestor| device_of(ancestor).is_ok_and(|dev| dev == device))
+            .last()
+            .unwrap_or(dir)
+            .to_path_buf()
+    }
+
+    fn current_uid() -> u32 {
+        // SAFETY: getuid has no preconditions and cannot fail.
+        unsafe { libc::getuid() }
+    }
+
+    /// Reserves a free name by creating its `.trashinfo` first, as the spec
+    /// requires, then moves the file under that name.
+    pub(super) fn trash_into(
+        root: &Path,
+        path: &Path,
+        recorded: &Path,
+    ) -> Result<PathBuf, ConvertError> {
+        let info = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            percent_encode(recorded.as_os_str().as_bytes(), PATH_ENCODE_SET),
+            chrono::Local::now().format("%Y-%m-%dT%H:%M:%S")
+        );
+        for index in 0usize.. {
+            let name = numbered_file_name(path, index);
+            let destination = root.join("files").join(&name);
+            if fs::symlink_metadata(&destination).is_ok() {
+                continue;
+            }
+            let info_path = root.join("info").join(format!("{name}.trashinfo"));
+            let mut file = match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info_path)
+            {
+                Ok(file) => file,
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => {
+                    return Err(ConvertError::from_io(
+                        ConvertError::Trash,
+                        format_args!("failed to create {}", info_path.display()),
+                        &err,
+                    ))
+                }
+            };
+            let moved = file
+                .write_all(info.as_bytes())
+                .map_err(|err| {
+                    ConvertError::from_io(
+                        ConvertError::Trash,
+                        format_args!("failed to write {}", info_path.display()),
+                        &err,
+                    )
+                })
+                .and_then(|()| rename_or_copy(path, &destination));
+            if let Err(err) = moved {
+                let _ = fs::remove_file(&info_path);
+                return Err(err);
+            }
+            return Ok(destination);
+        }
+        unreachable!("ran out of trash names")
+    }
+
+    fn numbered_file_name(path: &Path, index: usize) -> String {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "untitled".to_string());
+        if index == 0 {
+            return file_name;
+        }
+        let stem = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or(file_name);
+        match path.extension() {
+            Some(ext) => format!("{stem} ({index}).{}", ext.to_string_lossy()),
+            None => format!("{stem} ({index})"),
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::platform::{topdir_trash, trash_into};
+    use super::*;
+    use crate::test_support::unique_temp_dir_path;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn trash_directories_are_recognized_by_name() {
+        assert!(is_in_trash(Path::new(
+            "/media/usb/.Trash-1000/files/x.heic"
+        )));
+        assert!(is_in_trash(Path::new(
+            "/media/usb/.Trash/1000/files/x.heic"
+        )));
+        assert!(!is_in_trash(Path::new("/media/usb/.Trash-old/x.heic")));
+        assert!(!is_in_trash(Path::new("/media/usb/Trash/x.heic")));
+    }
+
+    #[test]
+    fn trashed_files_get_trashinfo_and_unique_names() {
+        let root = unique_temp_dir_path("xdg_trash");
+        let trash = root.join("Trash");
+        let source = root.join("my photos/IMG 0001.heic");
+        fs::create_dir_all(source.parent().expect("parent")).expect("create dir");
+        fs::create_dir_all(trash.join("files")).expect("create files");
+        fs::create_dir_all(trash.join("info")).expect("create info");
+
+        fs::write(&source, b"first").expect("write source");
+        let first = trash_into(&trash, &source, &source).expect("trash first");
+        fs::write(&source, b"second").expect("write source again");
+        let second = trash_into(&trash, &source, Path::new("my photos/IMG 0001.heic"))
+            .expect("trash second");
+
+        assert_eq!(first, trash.join("files/IMG 0001.heic"));
+        assert_eq!(second, trash.join("files/IMG 0001 (1).heic"));
+        assert_eq!(fs::read(&second).expect("read trashed"), b"second");
+        assert!(!source.exists());
+        let info =
+            fs::read_to_string(trash.join("info/IMG 0001.heic.trashinfo")).expect("read trashinfo");
+        let mut lines = info.lines();
+        assert_eq!(lines.next(), Some("[Trash Info]"));
+        let encoded = root.join("my%20photos/IMG%200001.heic");
+        assert_eq!(
+            lines.next(),
+            Some(format!("Path={}", encoded.display()).as_str())
+        );
+        assert!(lines
+            .next()
+            .is_some_and(|line| line.starts_with("DeletionDate=") && line.len() == 32));
+        let relative = fs::read_to_string(trash.join("info/IMG 0001 (1).heic.trashinfo"))
+            .expect("read second trashinfo");
+        assert!(relative.contains("\nPath=my%20photos/IMG%200001.heic\n"));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn restore_moves_file_back_and_drops_trashinfo() {
+        let root = unique_temp_dir_path("xdg_restore");
+        let trash = root.join("Trash");
+        let source = root.join("photos/IMG_0001.heic");
+        fs::create_dir_all(source.parent().expect("parent")).expect("create dir");
+        fs::create_dir_all(trash.join("files")).expect("create files");
+        fs::create_dir_all(trash.join("info")).expect("create info");
+        fs::write(&source, b"original").expect("write source");
+        let trashed = trash_into(&trash, &source, &source).expect("trash");
+
+        fs::write(&source, b"newer").expect("write newer");
+        assert!(restore_from_trash(&trashed, &source).is_err());
+        assert!(trashed.exists());
+
+        fs::remove_dir_all(source.parent().expect("parent")).expect("remove photos");
+        restore_from_trash(&trashed, &source).expect("restore");
+        assert_eq!(fs::read(&source).expect("read restored"), b"original");
+        assert!(!trashed.exists());
+        assert!(!trash.join("info/IMG_0001.heic.trashinfo").exists());
+        assert!(restore_from_trash(&trashed, &source).is_err());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn volume_trash_prefers_sticky_shared_dir() {
+        let topdir = unique_temp_dir_path("topdir_trash");
+        fs::create_dir_all(&topdir).expect("create topdir");
+
+        let own = topdir_trash(&topdir).expect("own trash");
+        let own_name = own
+            .file_name()
+            .expect("name")
+            .to_string_lossy()
+            .into_owned();
+        assert!(own_name.starts_with(".Trash-"));
+        assert!(own.join("files").is_dir() && own.join("info").is_dir());
+
+        let shared = topdir.join(".Trash");
+        fs::create_dir(&shared).expect("create shared");
+        assert_eq!(topdir_trash(&topdir).expect("not sticky"), own);
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o1777)).expect("set sticky");
+        let uid = own_name.trim_start_matches(".Trash-");
+        assert_eq!(
+            topdir_trash(&topdir).expect("shared trash"),
+            shared.join(uid)
+        );
+        let _ = fs::remove_dir_all(topdir);
+    }
+}
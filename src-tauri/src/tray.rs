@@ -1,3 +1,11 @@
+use std::{
+    collections::{BTreeSet, VecDeque},
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
+
 use tauri::{
     menu::{Menu, MenuItem, PredefinedMenuItem, Submenu},
     tray::TrayIconBuilder,
@@ -5,9 +13,10 @@ use tauri::{
 };
 
 use crate::{
-    commands::{open_recent_logs_window, LocaleDto},
+    commands::{open_recent_logs_window, open_stats_window, LocaleDto},
     config::AppLocale,
     restart_watch_service,
+    watcher::{BatchProgress, JobResult, WatchService, WatchStatus},
     window::show_settings_window,
     AppState, EVENT_LOCALE_CHANGED, EVENT_PAUSED_CHANGED,
 };
@@ -18,17 +27,95 @@ const MENU_TOGGLE_ID: &str = "pause_resume";
 const MENU_SETTINGS_GROUP_ID: &str = "settings_group";
 const MENU_OPEN_SETTINGS_ID: &str = "open_settings";
 const MENU_LANGUAGE_GROUP_ID: &str = "language_group";
+const MENU_RECENT_GROUP_ID: &str = "recent_conversions";
+const MENU_NO_CONVERSIONS_ID: &str = "no_conversions";
+const MENU_OPEN_FOLDER_GROUP_ID: &str = "open_watch_folder";
+const MENU_RESCAN_ID: &str = "rescan_now";
+const MENU_RETRY_FAILED_ID: &str = "retry_failed";
+/// Followed by the output path to reveal.
+const MENU_REVEAL_PREFIX: &str = "reveal:";
+/// Followed by the watch folder to open.
+const MENU_OPEN_FOLDER_PREFIX: &str = "open_folder:";
 const MENU_RECENT_LOGS_ID: &str = "recent_logs";
+const MENU_STATS_ID: &str = "stats";
 const MENU_LANG_EN_ID: &str = "lang_en";
 const MENU_LANG_JA_ID: &str = "lang_ja";
 const MENU_QUIT_ID: &str = "quit";
+const RECENT_CONVERSION_LIMIT: usize = 10;
+/// Per-job menu changes are shown at most this often during a batch.
+const JOB_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Finished jobs offered in the "Recent Conversions" submenu.
+#[derive(Debug, Default)]
+pub(crate) struct RecentJobs {
+    /// Newest first.
+    outputs: VecDeque<PathBuf>,
+    /// Sources whose latest attempt failed.
+    failed: BTreeSet<PathBuf>,
+}
+
+impl RecentJobs {
+    /// Returns whether the submenu changed.
+    fn record(&mut self, source: &Path, result: &JobResult) -> bool {
+        match result {
+            JobResult::Converted(output) => {
+                self.failed.remove(source);
+                self.outputs.retain(|known| known != output);
+                self.outputs.push_front(output.clone());
+                self.outputs.truncate(RECENT_CONVERSION_LIMIT);
+                true
+            }
+            JobResult::Failed(_) => self.failed.insert(source.to_path_buf()),
+            JobResult::Skipped => self.failed.remove(source),
+        }
+    }
+}
+
+/// Throttles the menu rebuilds asked for by every finished job.
+#[derive(Debug, Default)]
+pub(crate) struct MenuRefresh {
+    refreshed_at: Option<Instant>,
+    /// A rebuild is waiting for the interval to pass.
+    scheduled: bool,
+}
+
+impl MenuRefresh {
+    /// Returns how long to wait before rebuilding, or `None` when the
+    /// scheduled rebuild will pick up the change.
+    fn schedule(&mut self, now: Instant) -> Option<Duration> {
+        if self.scheduled {
+            return None;
+        }
+        self.scheduled = true;
+        Some(self.refreshed_at.map_or(Duration::ZERO, |at| {
+            JOB_REFRESH_INTERVAL.saturating_sub(now.duration_since(at))
+        }))
+    }
+
+    fn start(&mut self, now: Instant) {
+        self.scheduled = false;
+        self.refreshed_at = Some(now);
+    }
+}
 
 fn build_tray_menu(app: &AppHandle, paused: bool, locale: AppLocale) -> tauri::Result<Menu<Wry>> {
-    let status_text = match (paused, locale) {
-        (true, AppLocale::En) => "🔴 Paused",
-        (true, AppLocale::Ja) => "🔴 Paused",
-        (false, AppLocale::En) => "🟢 Ready",
-        (false, AppLocale::Ja) => "🟢 Ready",
+    let watch_status = current_watch_status(app);
+    let progress = current_batch_progress(app);
+    let status_text = match (paused, watch_status, locale) {
+        (true, _, AppLocale::En) => "🔴 Paused".to_string(),
+        (true, _, AppLocale::Ja) => "🔴 Paused".to_string(),
+        (false, WatchStatus::LowDiskSpace, AppLocale::En) => {
+            "🟠 Waiting for disk space".to_string()
+        }
+        (false, WatchStatus::LowDiskSpace, AppLocale::Ja) => "🟠 空き容量待ち".to_string(),
+        (false, WatchStatus::Running, AppLocale::En) if progress.is_active() => {
+            format!("🔵 Converting {}/{}", progress.completed, progress.total)
+        }
+        (false, WatchStatus::Running, AppLocale::Ja) if progress.is_active() => {
+            format!("🔵 変換中 {}/{}", progress.completed, progress.total)
+        }
+        (false, WatchStatus::Running, AppLocale::En) => "🟢 Ready".to_string(),
+        (false, WatchStatus::Running, AppLocale::Ja) => "🟢 Ready".to_string(),
     };
     let toggle_text = match (paused, locale) {
         (true, AppLocale::En) => "Resume",
@@ -48,6 +135,10 @@ fn build_tray_menu(app: &AppHandle, paused: bool, locale: AppLocale) -> tauri::R
         AppLocale::En => "Recent Logs",
         AppLocale::Ja => "最近のログ",
     };
+    let stats_text = match locale {
+        AppLocale::En => "Statistics",
+        AppLocale::Ja => "統計",
+    };
     let language_text = match locale {
         AppLocale::En => "Language",
         AppLocale::Ja => "言語",
@@ -73,7 +164,7 @@ fn build_tray_menu(app: &AppHandle, paused: bool, locale: AppLocale) -> tauri::R
         AppLocale::Ja => "終了",
     };
 
-    let status = MenuItem::with_id(app, MENU_STATUS_ID, status_text, false, None::<&str>)?;
+    let status = MenuItem::with_id(app, MENU_STATUS_ID, &status_text, false, None::<&str>)?;
     let toggle = MenuItem::with_id(app, MENU_TOGGLE_ID, toggle_text, true, None::<&str>)?;
     let open_settings = MenuItem::with_id(
         app,
@@ -82,8 +173,10 @@ fn build_tray_menu(app: &AppHandle, paused: bool, locale: AppLocale) -> tauri::R
         true,
         None::<&str>,
     )?;
+    let recent_menu = build_recent_menu(app, paused, locale)?;
     let recent_logs =
         MenuItem::with_id(app, MENU_RECENT_LOGS_ID, recent_logs_text, true, None::<&str>)?;
+    let stats = MenuItem::with_id(app, MENU_STATS_ID, stats_text, true, None::<&str>)?;
     let lang_en = MenuItem::with_id(
         app,
         MENU_LANG_EN_ID,
@@ -125,10 +218,123 @@ fn build_tray_menu(app: &AppHandle, paused: bool, locale: AppLocale) -> tauri::R
 
     Menu::with_items(
         app,
-        &[&status, &separator, &toggle, &settings_menu, &recent_logs, &quit],
+        &[
+            &status,
+            &separator,
+            &toggle,
+            &settings_menu,
+            &recent_menu,
+            &recent_logs,
+            &stats,
+            &quit,
+        ],
     )
 }
 
+/// Recent outputs to reveal, then actions on the watch folders.
+fn build_recent_menu(
+    app: &AppHandle,
+    paused: bool,
+    locale: AppLocale,
+) -> tauri::Result<Submenu<Wry>> {
+    let recent_text = match locale {
+        AppLocale::En => "Recent Conversions",
+        AppLocale::Ja => "最近の変換",
+    };
+    let no_conversions_text = match locale {
+        AppLocale::En => "No conversions yet",
+        AppLocale::Ja => "変換したファイルはありません",
+    };
+    let open_folder_text = match locale {
+        AppLocale::En => "Open Watch Folder",
+        AppLocale::Ja => "監視フォルダを開く",
+    };
+    let rescan_text = match locale {
+        AppLocale::En => "Rescan Now",
+        AppLocale::Ja => "今すぐ再スキャン",
+    };
+    let (outputs, failed) = current_recent_jobs(app);
+    let retry_text = match (failed, locale) {
+        (0, AppLocale::En) => "Retry Failed".to_string(),
+        (0, AppLocale::Ja) => "失敗したファイルを再試行".to_string(),
+        (count, AppLocale::En) => format!("Retry Failed ({count})"),
+        (count, AppLocale::Ja) => format!("失敗したファイルを再試行 ({count})"),
+    };
+
+    let recent_menu = Submenu::with_id(app, MENU_RECENT_GROUP_ID, recent_text, true)?;
+    for output in &outputs {
+        let name = output.file_name().map_or_else(
+            || output.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        recent_menu.append(&MenuItem::with_id(
+            app,
+            format!("{MENU_REVEAL_PREFIX}{}", output.display()),
+            name,
+            true,
+            None::<&str>,
+        )?)?;
+    }
+    if outputs.is_empty() {
+        let none = MenuItem::with_id(
+            app,
+            MENU_NO_CONVERSIONS_ID,
+            no_conversions_text,
+            false,
+            None::<&str>,
+        )?;
+        recent_menu.append(&none)?;
+    }
+    recent_menu.append(&PredefinedMenuItem::separator(app)?)?;
+    let watch_folders = current_watch_folders(app);
+    let watching = !paused && !watch_folders.is_empty();
+    match watch_folders.as_slice() {
+        [] => recent_menu.append(&MenuItem::with_id(
+            app,
+            MENU_OPEN_FOLDER_GROUP_ID,
+            open_folder_text,
+            false,
+            None::<&str>,
+        )?)?,
+        [folder] => recent_menu.append(&MenuItem::with_id(
+            app,
+            format!("{MENU_OPEN_FOLDER_PREFIX}{}", folder.display()),
+            open_folder_text,
+            true,
+            None::<&str>,
+        )?)?,
+        folders => {
+            let folders_menu =
+                Submenu::with_id(app, MENU_OPEN_FOLDER_GROUP_ID, open_folder_text, true)?;
+            for folder in folders {
+                folders_menu.append(&MenuItem::with_id(
+                    app,
+                    format!("{MENU_OPEN_FOLDER_PREFIX}{}", folder.display()),
+                    folder.display().to_string(),
+                    true,
+                    None::<&str>,
+                )?)?;
+            }
+            recent_menu.append(&folders_menu)?;
+        }
+    }
+    recent_menu.append(&MenuItem::with_id(
+        app,
+        MENU_RESCAN_ID,
+        rescan_text,
+        watching,
+        None::<&str>,
+    )?)?;
+    recent_menu.append(&MenuItem::with_id(
+        app,
+        MENU_RETRY_FAILED_ID,
+        &retry_text,
+        watching && failed > 0,
+        None::<&str>,
+    )?)?;
+    Ok(recent_menu)
+}
+
 pub(crate) fn setup_tray(app: &AppHandle, paused: bool, locale: AppLocale) -> tauri::Result<()> {
     let menu = build_tray_menu(app, paused, locale)?;
     let mut tray_builder = TrayIconBuilder::with_id(TRAY_ID)
@@ -166,15 +372,28 @@ pub(crate) fn setup_tray(app: &AppHandle, paused: bool, locale: AppLocale) -> ta
                 set_paused_and_refresh_ui(app, paused);
             }
             MENU_OPEN_SETTINGS_ID => show_settings_window(app),
+            MENU_RESCAN_ID => with_watch_service(app, WatchService::rescan_now),
+            MENU_RETRY_FAILED_ID => retry_failed(app),
             MENU_RECENT_LOGS_ID => {
                 if let Err(err) = open_recent_logs_window(app.clone()) {
                     log::error!("failed to open recent logs window: {err}");
                 }
             }
+            MENU_STATS_ID => {
+                if let Err(err) = open_stats_window(app.clone()) {
+                    log::error!("failed to open stats window: {err}");
+                }
+            }
             MENU_LANG_EN_ID => set_locale_and_refresh_ui(app, AppLocale::En),
             MENU_LANG_JA_ID => set_locale_and_refresh_ui(app, AppLocale::Ja),
             MENU_QUIT_ID => app.exit(0),
-            _ => {}
+            id => {
+                if let Some(output) = id.strip_prefix(MENU_REVEAL_PREFIX) {
+                    open_in_file_manager(Path::new(output), true);
+                } else if let Some(folder) = id.strip_prefix(MENU_OPEN_FOLDER_PREFIX) {
+                    open_in_file_manager(Path::new(folder), false);
+                }
+            }
         })
         .build(app)?;
 
@@ -239,6 +458,255 @@ fn set_locale_and_refresh_ui(app: &AppHandle, locale: AppLocale) {
     }
 }
 
+fn current_watch_status(app: &AppHandle) -> WatchStatus {
+    let state: State<'_, AppState> = app.state();
+    let status = match state.watch_status.lock() {
+        Ok(status) => *status,
+        Err(err) => {
+            log::error!("failed to lock watch status: {err}");
+            WatchStatus::default()
+        }
+    };
+    status
+}
+
+/// Called from the dispatcher thread whenever it holds or releases jobs, so
+/// the menu is only rebuilt through `refresh`.
+pub(crate) fn set_watch_status(app: &AppHandle, status: WatchStatus) {
+    let state: State<'_, AppState> = app.state();
+    match state.watch_status.lock() {
+        Ok(current) if *current == status => return,
+        Ok(mut current) => *current = status,
+        Err(err) => {
+            log::error!("failed to lock watch status: {err}");
+            return;
+        }
+    }
+
+    refresh(app);
+}
+
+fn current_batch_progress(app: &AppHandle) -> BatchProgress {
+    let state: State<'_, AppState> = app.state();
+    let progress = match state.batch_progress.lock() {
+        Ok(progress) => *progress,
+        Err(err) => {
+            log::error!("failed to lock batch progress: {err}");
+            BatchProgress::default()
+        }
+    };
+    progress
+}
+
+/// Called from the dispatcher thread; the menu is only rebuilt when the
+/// "Converting n/m" line changes, and at most once per `JOB_REFRESH_INTERVAL`.
+pub(crate) fn set_batch_progress(app: &AppHandle, progress: BatchProgress) {
+    let state: State<'_, AppState> = app.state();
+    let label = |progress: &BatchProgress| {
+        progress
+            .is_active()
+            .then_some((progress.completed, progress.total))
+    };
+    match state.batch_progress.lock() {
+        Ok(mut current) => {
+            let unchanged = label(&current) == label(&progress);
+            *current = progress;
+            if unchanged {
+                return;
+            }
+        }
+        Err(err) => {
+            log::error!("failed to lock batch progress: {err}");
+            return;
+        }
+    }
+
+    refresh_throttled(app);
+}
+
+fn current_recent_jobs(app: &AppHandle) -> (Vec<PathBuf>, usize) {
+    let state: State<'_, AppState> = app.state();
+    let recent = match state.recent_jobs.lock() {
+        Ok(jobs) => (jobs.outputs.iter().cloned().collect(), jobs.failed.len()),
+        Err(err) => {
+            log::error!("failed to lock recent jobs: {err}");
+            (Vec::new(), 0)
+        }
+    };
+    recent
+}
+
+fn current_watch_folders(app: &AppHandle) -> Vec<PathBuf> {
+    let state: State<'_, AppState> = app.state();
+    let folders = match state.config_store.lock() {
+        Ok(store) => store.config().watch_folders.clone(),
+        Err(err) => {
+            log::error!("failed to lock app state: {err}");
+            Vec::new()
+        }
+    };
+    folders
+}
+
+/// Called from the dispatcher thread for every finished job; the menu is
+/// only rebuilt when the recent conversions or failures change, and at most
+/// once per `JOB_REFRESH_INTERVAL`.
+pub(crate) fn record_finished_job(app: &AppHandle, source: &Path, result: &JobResult) {
+    let state: State<'_, AppState> = app.state();
+    match state.recent_jobs.lock() {
+        Ok(mut jobs) => {
+            if !jobs.record(source, result) {
+                return;
+            }
+        }
+        Err(err) => {
+            log::error!("failed to lock recent jobs: {err}");
+            return;
+        }
+    }
+    refresh_throttled(app);
+}
+
+/// There is no dispatcher while watching is paused.
+fn with_watch_service(app: &AppHandle, action: impl FnOnce(&WatchService)) {
+    let state: State<'_, AppState> = app.state();
+    match state.watch_service.lock() {
+        Ok(service) => match service.as_ref() {
+            Some(service) => action(service),
+            None => log::info!("watching is paused; tray action ignored"),
+        },
+        Err(err) => log::error!("failed to lock watch service: {err}"),
+    };
+}
+
+fn retry_failed(app: &AppHandle) {
+    let state: State<'_, AppState> = app.state();
+    let paths: Vec<PathBuf> = match state.recent_jobs.lock() {
+        Ok(jobs) => jobs.failed.iter().cloned().collect(),
+        Err(err) => {
+            log::error!("failed to lock recent jobs: {err}");
+            return;
+        }
+    };
+    log::info!("retrying {} failed file(s) from the tray", paths.len());
+    with_watch_service(app, |service| service.retry(paths));
+}
+
+/// Opens `path` in the file manager, or with `reveal` shows the folder
+/// holding it. The file manager is not waited for on the main thread.
+fn open_in_file_manager(path: &Path, reveal: bool) {
+    let mut command = file_manager_command(path, reveal);
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            log::error!("failed to run {command:?}: {err}");
+            return;
+        }
+    };
+    let spawned = thread::Builder::new()
+        .name("file-manager".to_string())
+        .spawn(move || match child.wait() {
+            Ok(status) if status.success() => {}
+            Ok(status) => log::warn!("{command:?} exited with {status}"),
+            Err(err) => log::error!("failed to wait for {command:?}: {err}"),
+        });
+    if let Err(err) = spawned {
+        log::error!("failed to spawn file manager thread: {err}");
+    }
+}
+
+/// `open -R` selects the file in Finder.
+#[cfg(not(target_os = "linux"))]
+fn file_manager_command(path: &Path, reveal: bool) -> Command {
+    let mut command = Command::new("open");
+    if reveal {
+        command.arg("-R");
+    }
+    command.arg(path);
+    command
+}
+
+/// `xdg-open` cannot select a file, so revealing opens its folder instead.
+#[cfg(target_os = "linux")]
+fn file_manager_command(path: &Path, reveal: bool) -> Command {
+    let target = match path.parent() {
+        Some(parent) if reveal => parent,
+        _ => path,
+    };
+    let mut command = Command::new("xdg-open");
+    command.arg(target);
+    command
+}
+
+/// Rebuilds the menu with the current pause state and language on the main
+/// thread, without waiting for it. Menu calls from any other thread block
+/// until the main thread runs them, and the main thread may be joining the
+/// dispatcher that asked for the refresh.
+pub(crate) fn refresh(app: &AppHandle) {
+    let handle = app.clone();
+    if let Err(err) = app.run_on_main_thread(move || refresh_now(&handle)) {
+        log::error!("failed to schedule tray menu refresh: {err}");
+    }
+}
+
+/// Like `refresh`, but a burst of changes is shown once the interval since
+/// the previous rebuild has passed, so a long batch does not rebuild the
+/// menu for every file.
+fn refresh_throttled(app: &AppHandle) {
+    let state: State<'_, AppState> = app.state();
+    let delay = match state.menu_refresh.lock() {
+        Ok(mut throttle) => match throttle.schedule(Instant::now()) {
+            Some(delay) => delay,
+            None => return,
+        },
+        Err(err) => {
+            log::error!("failed to lock menu refresh: {err}");
+            return;
+        }
+    };
+    if delay.is_zero() {
+        start_throttled_refresh(app);
+        return;
+    }
+
+    let handle = app.clone();
+    let spawned = thread::Builder::new()
+        .name("tray-refresh".to_string())
+        .spawn(move || {
+            thread::sleep(delay);
+            start_throttled_refresh(&handle);
+        });
+    if let Err(err) = spawned {
+        log::error!("failed to spawn tray refresh thread: {err}");
+        start_throttled_refresh(app);
+    }
+}
+
+/// Changes recorded after this point schedule another rebuild.
+fn start_throttled_refresh(app: &AppHandle) {
+    let state: State<'_, AppState> = app.state();
+    match state.menu_refresh.lock() {
+        Ok(mut throttle) => throttle.start(Instant::now()),
+        Err(err) => {
+            log::error!("failed to lock menu refresh: {err}");
+            return;
+        }
+    }
+    refresh(app);
+}
+
+fn refresh_now(app: &AppHandle) {
+    let state: State<'_, AppState> = app.state();
+    let (paused, locale) = match state.config_store.lock() {
+        Ok(store) => (store.config().paused, store.config().locale),
+        Err(err) => {
+            log::error!("failed to lock app state: {err}");
+            return;
+        }
+    };
+    refresh_tray_menu(app, paused, locale);
+}
+
 fn refresh_tray_menu(app: &AppHandle, paused: bool, locale: AppLocale) {
     if let Some(tray) = app.tray_by_id(TRAY_ID) {
         match build_tray_menu(app, paused, locale) {
@@ -0,0 +1,238 @@
+use image::GrayImage;
+
+const SOI: [u8; 2] = [0xFF, 0xD8];
+const APP1: u8 = 0xE1;
+const APP2: u8 = 0xE2;
+const XMP_SIGNATURE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const MPF_SIGNATURE: &[u8] = b"MPF\0";
+const MPF_ENTRY_COUNT: u16 = 3;
+const MP_TYPE_PRIMARY: u32 = 0x0003_0000;
+
+/// Re-encodes an Apple HDR gain map (linear gain `1 + (headroom - 1) * g`)
+/// into the log2 encoding used by Ultra HDR with `GainMapMax = log2(headroom)`.
+pub(crate) fn apple_gain_map_to_ultra_hdr(gain_map: &GrayImage, headroom: f32) -> GrayImage {
+    let headroom = headroom.max(1.0 + f32::EPSILON);
+    let max_log = headroom.log2();
+    let mut lookup = [0u8; 256];
+    for (stored, slot) in lookup.iter_mut().enumerate() {
+        let gain = 1.0 + (headroom - 1.0) * srgb_to_linear(stored as f32 / 255.0);
+        *slot = ((gain.log2() / max_log).clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+
+    let mut converted = gain_map.clone();
+    for pixel in converted.pixels_mut() {
+        pixel.0[0] = lookup[usize::from(pixel.0[0])];
+    }
+    converted
+}
+
+/// Joins an SDR primary JPEG and a gain map JPEG into one Ultra HDR file:
+/// XMP container directory + MPF index in the primary, `hdrgm` metadata in the
+/// gain map, gain map appended after the primary's EOI.
+pub(crate) fn assemble(primary: &[u8], gain_map: &[u8], headroom: f32) -> Result<Vec<u8>, String> {
+    if !primary.starts_with(&SOI) || !gain_map.starts_with(&SOI) {
+        return Err("ultra hdr inputs must be jpeg streams".to_string());
+    }
+
+    let gain_map = insert_segment(
+        gain_map,
+        2,
+        &app_segment(APP1, XMP_SIGNATURE, gain_map_xmp(headroom).as_bytes())?,
+    );
+
+    let insert_at = end_of_app_segments(primary)?;
+    let xmp = app_segment(APP1, XMP_SIGNATURE, primary_xmp(gain_map.len()).as_bytes())?;
+    // The MPF segment size does not depend on the offsets it stores, so size
+    // it with placeholders first and fill in the real values afterwards.
+    let mpf_len = app_segment(APP2, MPF_SIGNATURE, &mpf_payload(0, 0, 0))?.len();
+    let primary_len = primary.len() + xmp.len() + mpf_len;
+    let mpf_header_offset = insert_at + xmp.len() + 4 + MPF_SIGNATURE.len();
+    let mpf = app_segment(
+        APP2,
+        MPF_SIGNATURE,
+        &mpf_payload(
+            primary_len as u32,
+            gain_map.len() as u32,
+            (primary_len - mpf_header_offset) as u32,
+        ),
+    )?;
+
+    let mut output = Vec::with_capacity(primary_len + gain_map.len());
+    output.extend_from_slice(&primary[..insert_at]);
+    output.extend_from_slice(&xmp);
+    output.extend_from_slice(&mpf);
+    output.extend_from_slice(&primary[insert_at..]);
+    output.extend_from_slice(&gain_map);
+    Ok(output)
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn primary_xmp(gain_map_len: usize) -> String {
+    format!(
+        r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description xmlns:Container="http://ns.google.com/photos/1.0/container/" xmlns:Item="http://ns.google.com/photos/1.0/container/item/" xmlns:hdrgm="http://ns.adobe.com/hdr-gain-map/1.0/" hdrgm:Version="1.0"><Container:Directory><rdf:Seq><rdf:li rdf:parseType="Resource"><Container:Item Item:Semantic="Primary" Item:Mime="image/jpeg"/></rdf:li><rdf:li rdf:parseType="Resource"><Container:Item Item:Semantic="GainMap" Item:Mime="image/jpeg" Item:Length="{gain_map_len}"/></rdf:li></rdf:Seq></Container:Directory></rdf:Description></rdf:RDF></x:xmpmeta>"#
+    )
+}
+
+fn gain_map_xmp(headroom: f32) -> String {
+    let max_log = headroom.max(1.0).log2();
+    format!(
+        r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description xmlns:hdrgm="http://ns.adobe.com/hdr-gain-map/1.0/" hdrgm:Version="1.0" hdrgm:GainMapMin="0" hdrgm:GainMapMax="{max_log:.6}" hdrgm:Gamma="1" hdrgm:OffsetSDR="0" hdrgm:OffsetHDR="0" hdrgm:HDRCapacityMin="0" hdrgm:HDRCapacityMax="{max_log:.6}" hdrgm:BaseRenditionIsHDR="False"/></rdf:RDF></x:xmpmeta>"#
+    )
+}
+
+/// Big-endian MP header with two MP entries: the primary image (offset 0)
+/// and the gain map, whose offset is relative to the MP header.
+fn mpf_payload(primary_len: u32, gain_map_len: u32, gain_map_offset: u32) -> Vec<u8> {
+    let entries_offset = 8 + 2 + u32::from(MPF_ENTRY_COUNT) * 12 + 4;
+    let mut payload = Vec::new();
+    payload.extend_from_slice(b"MM\0\x2A");
+    payload.extend_from_slice(&8u32.to_be_bytes());
+    payload.extend_from_slice(&MPF_ENTRY_COUNT.to_be_bytes());
+    // MPFVersion
+    payload.extend_from_slice(&0xB000u16.to_be_bytes());
+    payload.extend_from_slice(&7u16.to_be_bytes());
+    payload.extend_from_slice(&4u32.to_be_bytes());
+    payload.extend_from_slice(b"0100");
+    // NumberOfImages
+    payload.extend_from_slice(&0xB001u16.to_be_bytes());
+    payload.extend_from_slice(&4u16.to_be_bytes());
+    payload.extend_from_slice(&1u32.to_be_bytes());
+    payload.extend_from_slice(&2u32.to_be_bytes());
+    // MPEntry
+    payload.extend_from_slice(&0xB002u16.to_be_bytes());
+    payload.extend_from_slice(&7u16.to_be_bytes());
+    payload.extend_from_slice(&32u32.to_be_bytes());
+    payload.extend_from_slice(&entries_offset.to_be_bytes());
+    payload.extend_from_slice(&0u32.to_be_bytes());
+    for (attribute, size, offset) in [
+        (MP_TYPE_PRIMARY, primary_len, 0),
+        (0, gain_map_len, gain_map_offset),
+    ] {
+        payload.extend_from_slice(&attribute.to_be_bytes());
+        payload.extend_from_slice(&size.to_be_bytes());
+        payload.extend_from_slice(&offset.to_be_bytes());
+        payload.extend_from_slice(&[0; 4]);
+    }
+    payload
+}
+
+fn app_segment(marker: u8, signature: &[u8], payload: &[u8]) -> Result<Vec<u8>, String> {
+    let length = 2 + signature.len() + payload.len();
+    let length =
+        u16::try_from(length).map_err(|_| format!("jpeg segment too large: {length} bytes"))?;
+    let mut segment = vec![0xFF, marker];
+    segment.extend_from_slice(&length.to_be_bytes());
+    segment.extend_from_slice(signature);
+    segment.extend_from_slice(payload);
+    Ok(segment)
+}
+
+fn insert_segment(jpeg: &[u8], at: usize, segment: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(jpeg.len() + segment.len());
+    output.extend_from_slice(&jpeg[..at]);
+    output.extend_from_slice(segment);
+    output.extend_from_slice(&jpeg[at..]);
+    output
+}
+
+/// Offset just past the APPn segments that follow SOI, so EXIF stays first.
+fn end_of_app_segments(jpeg: &[u8]) -> Result<usize, String> {
+    let mut offset = SOI.len();
+    while let Some(&[0xFF, marker]) = jpeg.get(offset..offset + 2) {
+        if !(0xE0..=0xEF).contains(&marker) {
+            break;
+        }
+        let length = jpeg
+            .get(offset + 2..offset + 4)
+            .map(|raw| usize::from(u16::from_be_bytes([raw[0], raw[1]])))
+            .ok_or_else(|| "truncated jpeg segment".to_string())?;
+        offset += 2 + length;
+    }
+    if offset > jpeg.len() {
+        return Err("truncated jpeg segment".to_string());
+    }
+    Ok(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{codecs::jpeg::JpegEncoder, DynamicImage, ImageEncoder, Luma, RgbImage};
+
+    fn encode(image: DynamicImage, exif: Option<Vec<u8>>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = JpegEncoder::new_with_quality(&mut bytes, 90);
+        if let Some(exif) = exif {
+            encoder.set_exif_metadata(exif).expect("exif");
+        }
+        image.write_with_encoder(encoder).expect("encode");
+        bytes
+    }
+
+    fn read_u32(bytes: &[u8], at: usize) -> usize {
+        u32::from_be_bytes(bytes[at..at + 4].try_into().expect("u32")) as usize
+    }
+
+    #[test]
+    fn assembled_file_indexes_gain_map_through_mpf_and_xmp() {
+        let primary = encode(
+            DynamicImage::ImageRgb8(RgbImage::new(8, 8)),
+            Some(b"MM\0\x2A\0\0\0\x08\0\0\0\0\0\0".to_vec()),
+        );
+        let gain_map = encode(DynamicImage::ImageLuma8(GrayImage::new(4, 4)), None);
+
+        let output = assemble(&primary, &gain_map, 4.0).expect("assemble");
+
+        let mpf_at = output
+            .windows(MPF_SIGNATURE.len())
+            .position(|window| window == MPF_SIGNATURE)
+            .expect("mpf segment");
+        let header = mpf_at + MPF_SIGNATURE.len();
+        let entries = header + read_u32(&output, header + 8 + 2 + 2 * 12 + 8);
+        let primary_len = read_u32(&output, entries + 4);
+        let gain_map_len = read_u32(&output, entries + 16 + 4);
+        let gain_map_offset = read_u32(&output, entries + 16 + 8);
+
+        assert_eq!(primary_len + gain_map_len, output.len());
+        assert_eq!(header + gain_map_offset, primary_len);
+        assert_eq!(&output[primary_len..primary_len + 2], &SOI);
+        assert_eq!(&output[primary_len - 2..primary_len], &[0xFF, 0xD9]);
+
+        let xmp = String::from_utf8_lossy(&output[..primary_len]);
+        assert!(xmp.contains(&format!("Item:Length=\"{gain_map_len}\"")));
+        let gain_map_xmp = String::from_utf8_lossy(&output[primary_len..]);
+        assert!(gain_map_xmp.contains("hdrgm:GainMapMax=\"2.000000\""));
+
+        // Existing APPn segments, EXIF included, stay ahead of the new ones.
+        let find = |needle: &[u8]| output.windows(needle.len()).position(|w| w == needle);
+        assert!(find(b"Exif\0\0").expect("exif") < find(XMP_SIGNATURE).expect("xmp"));
+    }
+
+    #[test]
+    fn assemble_rejects_non_jpeg_input() {
+        assert!(assemble(b"not a jpeg", &SOI, 2.0).is_err());
+    }
+
+    #[test]
+    fn apple_gain_map_is_log_encoded_against_headroom() {
+        let mut gain_map = GrayImage::new(3, 1);
+        gain_map.put_pixel(0, 0, Luma([0]));
+        gain_map.put_pixel(1, 0, Luma([255]));
+        gain_map.put_pixel(2, 0, Luma([188]));
+
+        let converted = apple_gain_map_to_ultra_hdr(&gain_map, 4.0);
+
+        assert_eq!(converted.get_pixel(0, 0).0[0], 0);
+        assert_eq!(converted.get_pixel(1, 0).0[0], 255);
+        // sRGB 188 is ~0.5 linear: gain 2.5 -> log2(2.5) / log2(4) ~ 0.66.
+        let mid = converted.get_pixel(2, 0).0[0];
+        assert!((165..=172).contains(&mid), "mid gain encoded as {mid}");
+    }
+}
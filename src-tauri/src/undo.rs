@@ -0,0 +1,417 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    json_store::{now_unix_ms, JsonStore},
+    trash,
+};
+
+/// Older jobs are dropped once the journal holds this many.
+const JOURNAL_LIMIT: usize = 1000;
+
+/// Serializes read-modify-write cycles of the journal within the app; the
+/// workers and the logs window may touch it at the same time. The lock file
+/// next to the journal does the same for `heic-ready undo`, which runs in its
+/// own process.
+static JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+
+/// Held for one read-modify-write cycle; dropping it releases both locks.
+struct JournalLock {
+    _thread: MutexGuard<'static, ()>,
+    _file: fs::File,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JournalFile {
+    #[serde(default)]
+    jobs: Vec<TrashJob>,
+    #[serde(default)]
+    restored: Vec<RestoredOriginal>,
+}
+
+/// Files one conversion moved to the trash, with the outputs that replaced them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashJob {
+    pub id: u64,
+    pub timestamp_unix_ms: u128,
+    pub moves: Vec<TrashMove>,
+    /// JPEGs and sidecars written by the job.
+    #[serde(default)]
+    pub outputs: Vec<PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashMove {
+    pub original: PathBuf,
+    /// Where the file ended up; the trash may have renamed it, e.g.
+    /// `IMG_0001 (3).heic`.
+    pub trashed: PathBuf,
+}
+
+/// An original put back by undo. The watcher leaves it alone until its size
+/// or modification time changes, or `replace` mode would trash it again.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoredOriginal {
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl RestoredOriginal {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            path: path.to_path_buf(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    /// Whether the file is still exactly as it was restored.
+    fn is_unchanged(&self) -> bool {
+        Self::of(&self.path).as_ref() == Some(self)
+    }
+}
+
+/// Which recorded jobs to undo.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum UndoSelection {
+    Jobs(Vec<u64>),
+    /// Jobs recorded between both times (unix milliseconds, inclusive).
+    Range {
+        from_unix_ms: u128,
+        to_unix_ms: u128,
+    },
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct UndoReport {
+    pub restored: Vec<PathBuf>,
+    pub deleted_outputs: Vec<PathBuf>,
+    pub failed: Vec<UndoFailure>,
+}
+
+impl UndoReport {
+    /// One line for the recent log and the command line.
+    pub(crate) fn summary(&self) -> String {
+        let mut summary = format!("undo: restored {} original(s)", self.restored.len());
+        if !self.deleted_outputs.is_empty() {
+            summary.push_str(&format!(
+                ", deleted {} output(s)",
+                self.deleted_outputs.len()
+            ));
+        }
+        if !self.failed.is_empty() {
+            summary.push_str(&format!(", {} failed", self.failed.len()));
+        }
+        summary
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct UndoFailure {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Every move to the trash done in `replace` mode, persisted next to the
+/// config so originals can be put back exactly where they were.
+#[derive(Clone, Debug)]
+pub(crate) struct TrashJournal {
+    store: JsonStore,
+}
+
+impl TrashJournal {
+    pub(crate) fn new(path: &Path) -> Self {
+        Self {
+            store: JsonStore::new(path, "trash journal", &JOURNAL_LOCK),
+        }
+    }
+
+    /// Records one job and returns its id; jobs whose files were all taken
+    /// out of the trash since are pruned.
+    pub(crate) fn record(
+        &self,
+        moves: Vec<TrashMove>,
+        outputs: Vec<PathBuf>,
+    ) -> Result<u64, String> {
+        let _guard = self.lock()?;
+        let mut file: JournalFile = self.store.load();
+        file.jobs.retain(|job| {
+            job.moves
+                .iter()
+                .any(|moved| fs::symlink_metadata(&moved.trashed).is_ok())
+        });
+        file.restored.retain(RestoredOriginal::is_unchanged);
+        let id = file.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        file.jobs.push(TrashJob {
+            id,
+            timestamp_unix_ms: now_unix_ms(),
+            moves,
+            outputs,
+        });
+        if file.jobs.len() > JOURNAL_LIMIT {
+            let excess = file.jobs.len() - JOURNAL_LIMIT;
+            file.jobs.drain(..excess);
+        }
+        self.store.save(&file)?;
+        Ok(id)
+    }
+
+    /// Recorded jobs, newest first.
+    pub(crate) fn jobs(&self) -> Result<Vec<TrashJob>, String> {
+        let _guard = self.lock()?;
+        let mut jobs = self.store.load::<JournalFile>().jobs;
+        jobs.reverse();
+        Ok(jobs)
+    }
+
+    /// Originals put back by undo that did not change since.
+    pub(crate) fn restored(&self) -> Result<Vec<RestoredOriginal>, String> {
+        let _guard = self.lock()?;
+        let mut restored = self.store.load::<JournalFile>().restored;
+        restored.retain(RestoredOriginal::is_unchanged);
+        Ok(restored)
+    }
+
+    /// Puts the originals of the selected jobs back and, with
+    /// `delete_outputs`, deletes what they were converted to. Outputs are only
+    /// deleted once every original of their job is back; restored moves leave
+    /// the journal, failed ones stay so they can be retried. Restored
+    /// originals are remembered so the watcher does not convert them again.
+    pub(crate) fn undo(
+        &self,
+        selection: &UndoSelection,
+        delete_outputs: bool,
+    ) -> Result<UndoReport, String> {
+        let _guard = self.lock()?;
+        let mut file: JournalFile = self.store.load();
+        let mut report = UndoReport::default();
+        let mut restored = Vec::new();
+        for job in file.jobs.iter_mut().filter(|job| selection.matches(job)) {
+            job.moves.retain(|moved| {
+                match trash::restore_from_trash(&moved.trashed, &moved.original) {
+                    Ok(()) => {
+                        log::info!(
+                            "restored from trash: {} -> {}",
+                            moved.trashed.display(),
+                            moved.original.display()
+                        );
+                        report.restored.push(moved.original.clone());
+                        restored.extend(RestoredOriginal::of(&moved.original));
+                        false
+                    }
+                    Err(err) => {
+                        report.failed.push(UndoFailure {
+                            path: moved.original.clone(),
+                            reason: err.message().to_string(),
+                        });
+                        true
+                    }
+                }
+            });
+            if delete_outputs && job.moves.is_empty() {
+                report
+                    .deleted_outputs
+                    .extend(remove_outputs(&job.outputs, &mut report.failed));
+            }
+        }
+        file.jobs.retain(|job| !job.moves.is_empty());
+        file.restored.retain(|known| {
+            known.is_unchanged() && !restored.iter().any(|new| new.path == known.path)
+        });
+        file.restored.extend(restored);
+        if file.restored.len() > JOURNAL_LIMIT {
+            let excess = file.restored.len() - JOURNAL_LIMIT;
+            file.restored.drain(..excess);
+        }
+        self.store.save(&file)?;
+        Ok(report)
+    }
+
+    fn lock(&self) -> Result<JournalLock, String> {
+        let thread = self.store.lock()?;
+        let lock_path = self.lock_path();
+        if let Some(parent) = lock_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("failed to create {}: {err}", parent.display()))?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .and_then(|file| file.lock_exclusive().map(|()| file))
+            .map_err(|err| format!("failed to lock {}: {err}", lock_path.display()))?;
+        Ok(JournalLock {
+            _thread: thread,
+            _file: file,
+        })
+    }
+
+    /// `trash_journal.json.lock`
+    fn lock_path(&self) -> PathBuf {
+        let mut name = OsString::from(self.store.path().as_os_str());
+        name.push(".lock");
+        PathBuf::from(name)
+    }
+}
+
+impl UndoSelection {
+    fn matches(&self, job: &TrashJob) -> bool {
+        match self {
+            Self::Jobs(ids) => ids.contains(&job.id),
+            Self::Range {
+                from_unix_ms,
+                to_unix_ms,
+            } => (*from_unix_ms..=*to_unix_ms).contains(&job.timestamp_unix_ms),
+        }
+    }
+}
+
+fn remove_outputs(outputs: &[PathBuf], failed: &mut Vec<UndoFailure>) -> Vec<PathBuf> {
+    outputs
+        .iter()
+        .filter(|path| match fs::remove_file(path) {
+            Ok(()) => true,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => false,
+            Err(err) => {
+                failed.push(UndoFailure {
+                    path: path.to_path_buf(),
+                    reason: format!("failed to delete output: {err}"),
+                });
+                false
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::unique_temp_dir_path;
+
+    fn touch(path: &Path, contents: &[u8]) {
+        fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+        fs::write(path, contents).expect("write file");
+    }
+
+    #[test]
+    fn undo_restores_renamed_originals_and_deletes_outputs() {
+        let root = unique_temp_dir_path("undo");
+        let journal = TrashJournal::new(&root.join("state/trash_journal.json"));
+        let original = root.join("photos/IMG_0001.heic");
+        let trashed = root.join("trash/IMG_0001 (3).heic");
+        let output = root.join("photos/IMG_0001.jpg");
+        touch(&trashed, b"heic");
+        touch(&output, b"jpeg");
+        let other_original = root.join("photos/IMG_0002.heic");
+        let other_trashed = root.join("trash/IMG_0002.heic");
+        touch(&other_trashed, b"other");
+
+        let id = journal
+            .record(
+                vec![TrashMove {
+                    original: original.clone(),
+                    trashed: trashed.clone(),
+                }],
+                vec![output.clone()],
+            )
+            .expect("record");
+        let other_id = journal
+            .record(
+                vec![TrashMove {
+                    original: other_original.clone(),
+                    trashed: other_trashed.clone(),
+                }],
+                Vec::new(),
+            )
+            .expect("record other");
+        let listed: Vec<u64> = journal
+            .jobs()
+            .expect("jobs")
+            .iter()
+            .map(|job| job.id)
+            .collect();
+        assert_eq!(listed, vec![other_id, id]);
+
+        let report = journal
+            .undo(&UndoSelection::Jobs(vec![id]), true)
+            .expect("undo");
+        assert_eq!(report.restored, vec![original.clone()]);
+        assert_eq!(report.deleted_outputs, vec![output.clone()]);
+        assert!(report.failed.is_empty());
+        assert_eq!(fs::read(&original).expect("read restored"), b"heic");
+        assert!(!output.exists());
+        assert!(other_trashed.exists());
+        assert_eq!(journal.jobs().expect("jobs").len(), 1);
+        let restored = journal.restored().expect("restored");
+        assert_eq!(restored.len(), 1);
+        assert_eq!(
+            (restored[0].path.as_path(), restored[0].len),
+            (original.as_path(), 4)
+        );
+        fs::write(&original, b"edited").expect("edit restored");
+        assert!(journal.restored().expect("restored").is_empty());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn journal_lock_excludes_other_processes() {
+        let root = unique_temp_dir_path("undo_lock");
+        let journal = TrashJournal::new(&root.join("trash_journal.json"));
+        let guard = journal.lock().expect("lock");
+        let other = fs::File::open(root.join("trash_journal.json.lock")).expect("open lock file");
+        assert!(other.try_lock_exclusive().is_err());
+        drop(guard);
+        other.try_lock_exclusive().expect("lock after release");
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn blocked_restore_keeps_job_and_outputs() {
+        let root = unique_temp_dir_path("undo_blocked");
+        let journal = TrashJournal::new(&root.join("trash_journal.json"));
+        let original = root.join("photos/IMG_0001.heic");
+        let trashed = root.join("trash/IMG_0001.heic");
+        let output = root.join("photos/IMG_0001.jpg");
+        touch(&trashed, b"heic");
+        touch(&output, b"jpeg");
+        touch(&original, b"someone else");
+        journal
+            .record(
+                vec![TrashMove {
+                    original: original.clone(),
+                    trashed: trashed.clone(),
+                }],
+                vec![output.clone()],
+            )
+            .expect("record");
+
+        let everything = UndoSelection::Range {
+            from_unix_ms: 0,
+            to_unix_ms: u128::MAX,
+        };
+        let report = journal.undo(&everything, true).expect("undo");
+        assert!(report.restored.is_empty());
+        assert_eq!(report.failed.len(), 1);
+        assert!(trashed.exists() && output.exists());
+        assert_eq!(fs::read(&original).expect("read"), b"someone else");
+
+        fs::remove_file(&original).expect("remove blocker");
+        let report = journal.undo(&everything, false).expect("undo again");
+        assert_eq!(report.restored, vec![original.clone()]);
+        assert!(report.deleted_outputs.is_empty() && output.exists());
+        assert!(journal.jobs().expect("jobs").is_empty());
+        let _ = fs::remove_dir_all(root);
+    }
+}
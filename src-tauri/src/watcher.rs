@@ -2,28 +2,51 @@ use std::{
     collections::{HashMap, HashSet, VecDeque},
     fs,
     path::{Path, PathBuf},
-    process::Command,
-    sync::{Mutex, OnceLock},
+    sync::{Arc, Mutex, OnceLock},
     thread,
     time::{Duration, Instant, SystemTime},
 };
 
 use crossbeam_channel::{Receiver, Sender};
 use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::config::{AppConfig, OutputPolicy};
+use crate::{
+    archive,
+    config::{
+        AppConfig, InputFormat, LivePhotoPolicy, OutputPolicy, ReplaceMode, ThumbnailLocation,
+    },
+    converter::{self, ConvertOptions, ConvertReport, ConvertedImage, SidecarKind},
+    error::ConvertError,
+    history,
+    ledger::Ledger,
+    live_photo,
+    metrics,
+    sniff::{self, Sniffed},
+    stats::{JobOutcome, StatsStore},
+    trash,
+    undo::{TrashJournal, TrashMove},
+};
 
+const LIVE_PHOTO_SIDECAR_DIR: &str = "Live Photos";
+const THUMBNAIL_DIR: &str = ".thumbs";
 const STABLE_WINDOW: Duration = Duration::from_millis(300);
 const MAX_STABILIZE_RETRIES: usize = 3;
 const DEBOUNCE_WINDOW: Duration = Duration::from_millis(400);
 const DUPLICATE_EVENT_SUPPRESS_WINDOW: Duration = Duration::from_secs(2);
+/// Batch progress is reported at most this often while jobs are running.
+const PROGRESS_REPORT_INTERVAL: Duration = Duration::from_millis(500);
 const WORKER_COUNT: usize = 2;
 const RECENT_LOG_LIMIT: usize = 10;
 const MIN_RESCAN_INTERVAL_SECS: u64 = 15;
 const MAX_RESCAN_INTERVAL_SECS: u64 = 60 * 60;
 const TRASH_MOVE_RETRIES: usize = 3;
 const TRASH_RETRY_DELAY: Duration = Duration::from_millis(120);
+/// Free space a job is assumed to need, as a multiple of its source size:
+/// the decoded intermediate, the JPEG and its sidecars.
+const DISK_SPACE_ESTIMATE_FACTOR: u64 = 8;
+const LOW_DISK_SPACE_RECHECK_INTERVAL: Duration = Duration::from_secs(10);
+const BYTES_PER_MB: u64 = 1024 * 1024;
 
 static RECENT_LOGS: OnceLock<Mutex<VecDeque<RecentLogEntry>>> = OnceLock::new();
 
@@ -38,58 +61,266 @@ struct RecentLogEntry {
     timestamp_unix_ms: u128,
     path: String,
     result: &'static str,
+    code: Option<&'static str>,
     reason: String,
+    details: Option<ConversionDetails>,
+}
+
+impl RecentLogEntry {
+    fn new(
+        path: String,
+        result: &'static str,
+        code: Option<&'static str>,
+        reason: &str,
+        details: Option<ConversionDetails>,
+    ) -> Self {
+        Self {
+            timestamp_unix_ms: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|value| value.as_millis())
+                .unwrap_or(0),
+            path,
+            result,
+            code,
+            reason: reason.to_string(),
+            details,
+        }
+    }
+
+    fn to_recent_log(&self) -> RecentLog {
+        RecentLog {
+            timestamp_unix_ms: self.timestamp_unix_ms,
+            path: self.path.clone(),
+            result: self.result.to_string(),
+            code: self.code.map(str::to_string),
+            reason: self.reason.clone(),
+            details: self.details.clone(),
+        }
+    }
 }
 
-#[derive(Clone, Debug, Serialize)]
+/// Also the format of each line of the history file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RecentLog {
     pub timestamp_unix_ms: u128,
     pub path: String,
     pub result: String,
+    /// Stable `ConvertError` code for failures, skips and warnings.
+    pub code: Option<String>,
     pub reason: String,
+    /// Only on successful conversions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<ConversionDetails>,
+}
+
+/// What a conversion did with the source and what it wrote.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversionDetails {
+    /// `coexist`, `replace`, `replace_delete`, `replace_secure_delete` or `archive`.
+    pub policy: String,
+    pub outputs: Vec<String>,
+    /// Where the source is now in the Trash.
+    pub trashed_as: Option<String>,
+    pub archived_to: Option<String>,
+    pub source_bytes: Option<u64>,
+    /// JPEGs only, without sidecars.
+    pub output_bytes: u64,
+    /// From the stable source to the finished outputs and source policy.
+    pub duration_ms: u64,
+}
+
+struct ConvertedOutput {
+    path: PathBuf,
+    report: ConvertReport,
+    bytes: u64,
+}
+
+/// How a stable candidate is processed, decided by its content.
+#[derive(Debug, PartialEq, Eq)]
+enum SourceContent {
+    Decode(InputFormat),
+    /// Already a JPEG under another name.
+    Jpeg,
 }
 
 enum ConvertOutcome {
-    Success(PathBuf),
+    Success {
+        outputs: Vec<ConvertedOutput>,
+        details: ConversionDetails,
+    },
     SuccessWithWarning {
-        output_path: PathBuf,
+        outputs: Vec<ConvertedOutput>,
+        details: ConversionDetails,
         warning: String,
+        /// Code of the first warning caused by an error.
+        code: Option<&'static str>,
+    },
+}
+
+/// Dispatcher state shown in the tray while watching is not paused by the user.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum WatchStatus {
+    #[default]
+    Running,
+    /// Jobs are held until the output volume has room again.
+    LowDiskSpace,
+}
+
+pub(crate) type StatusListener = Box<dyn Fn(WatchStatus) + Send>;
+
+/// Progress of the current batch: every job queued since the queue was last
+/// empty. All zero while idle.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BatchProgress {
+    pub completed: usize,
+    pub total: usize,
+    pub queued: usize,
+    pub running: usize,
+    /// Split of `completed`.
+    pub converted: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Remaining time at the average pace of the batch so far.
+    pub eta_secs: Option<u64>,
+    /// When the first job of the batch was queued.
+    pub started_unix_ms: Option<u128>,
+}
+
+impl BatchProgress {
+    pub(crate) fn is_active(&self) -> bool {
+        self.queued + self.running > 0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStage {
+    Queued,
+    Started,
+    Finished,
+}
+
+/// How a finished job ended, as counted in the stats.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum JobResult {
+    /// With the first JPEG written.
+    Converted(PathBuf),
+    Failed(&'static str),
+    Skipped,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ProgressEvent {
+    Job {
+        stage: JobStage,
+        path: PathBuf,
+        /// Set for `JobStage::Finished`.
+        result: Option<JobResult>,
     },
+    Batch(BatchProgress),
+}
+
+pub(crate) type ProgressListener = Box<dyn Fn(ProgressEvent) + Send>;
+
+/// Counts the jobs of the current batch in the dispatcher and reports them.
+struct ProgressTracker {
+    listener: ProgressListener,
+    batch_started_at: Option<Instant>,
+    started_unix_ms: Option<u128>,
+    converted: usize,
+    failed: usize,
+    skipped: usize,
+    reported: BatchProgress,
+    reported_at: Option<Instant>,
+}
+
+/// Holds jobs back while the output volume is short on space.
+struct DiskSpaceGuard {
+    min_free_bytes: u64,
+    /// Set while jobs are held; the volume is checked again at this time.
+    recheck_at: Option<Instant>,
+}
+
+/// Why the next job has to wait.
+#[derive(Debug, PartialEq, Eq)]
+struct LowDiskSpace {
+    volume: PathBuf,
+    available: u64,
+    required: u64,
+}
+
+/// Requests to the dispatcher, handled on its next turn.
+enum WatchCommand {
+    Stop,
+    Rescan,
+    Retry(Vec<PathBuf>),
 }
 
 pub struct WatchService {
-    stop_tx: Sender<()>,
+    command_tx: Sender<WatchCommand>,
     join_handle: thread::JoinHandle<()>,
 }
 
 impl WatchService {
-    pub fn start(config: AppConfig) -> Result<Self, String> {
-        let (stop_tx, stop_rx) = crossbeam_channel::bounded::<()>(1);
+    pub(crate) fn start(
+        config: AppConfig,
+        ledger_path: &Path,
+        journal: TrashJournal,
+        stats: StatsStore,
+        on_status: StatusListener,
+        on_progress: ProgressListener,
+    ) -> Result<Self, String> {
+        let (command_tx, command_rx) = crossbeam_channel::unbounded::<WatchCommand>();
+        let ledger = Arc::new(Mutex::new(Ledger::load(ledger_path)));
 
         let join_handle = thread::Builder::new()
             .name("watch-dispatcher".to_string())
             .spawn(move || {
-                if let Err(err) = run_dispatcher(config, stop_rx) {
+                let progress = ProgressTracker::new(on_progress);
+                if let Err(err) = run_dispatcher(
+                    config, ledger, journal, stats, on_status, progress, command_rx,
+                ) {
                     log::error!("watch dispatcher stopped with error: {err}");
+                    metrics::record_watcher_error();
                 }
             })
             .map_err(|err| format!("failed to spawn watch dispatcher: {err}"))?;
 
         Ok(Self {
-            stop_tx,
+            command_tx,
             join_handle,
         })
     }
 
+    /// Scans the watch folders now instead of at the next rescan interval.
+    pub(crate) fn rescan_now(&self) {
+        let _ = self.command_tx.send(WatchCommand::Rescan);
+    }
+
+    /// Queues `paths` again even if they did not change since they failed.
+    pub(crate) fn retry(&self, paths: Vec<PathBuf>) {
+        let _ = self.command_tx.send(WatchCommand::Retry(paths));
+    }
+
+    /// Joins the dispatcher. Its listeners must never wait for the main
+    /// thread, which is often the one calling this.
     pub fn stop(self) {
-        let _ = self.stop_tx.send(());
+        let _ = self.command_tx.send(WatchCommand::Stop);
         if let Err(err) = self.join_handle.join() {
             log::error!("failed to join watch dispatcher: {err:?}");
         }
     }
 }
 
-fn run_dispatcher(config: AppConfig, stop_rx: Receiver<()>) -> Result<(), String> {
+fn run_dispatcher(
+    config: AppConfig,
+    ledger: Arc<Mutex<Ledger>>,
+    journal: TrashJournal,
+    stats: StatsStore,
+    on_status: StatusListener,
+    mut progress: ProgressTracker,
+    command_rx: Receiver<WatchCommand>,
+) -> Result<(), String> {
     if config.watch_folders.is_empty() {
         return Ok(());
     }
@@ -118,62 +349,122 @@ fn run_dispatcher(config: AppConfig, stop_rx: Receiver<()>) -> Result<(), String
     }
 
     let (job_tx, job_rx) = crossbeam_channel::unbounded::<PathBuf>();
-    let (done_tx, done_rx) = crossbeam_channel::unbounded::<PathBuf>();
-    let worker_handles = spawn_workers(job_rx, done_tx, config.clone());
+    let (done_tx, done_rx) = crossbeam_channel::unbounded::<(PathBuf, JobResult)>();
+    let worker_handles = spawn_workers(
+        job_rx,
+        done_tx,
+        config.clone(),
+        Arc::clone(&ledger),
+        journal.clone(),
+        stats,
+    );
 
+    // Originals put back by undo, reloaded on every rescan and event.
+    let mut restored = load_restored(&journal);
     let mut last_enqueued: HashMap<PathBuf, Instant> = HashMap::new();
     let mut last_signature: HashMap<PathBuf, FileSignature> = HashMap::new();
     let mut in_flight: HashSet<PathBuf> = HashSet::new();
+    // Jobs wait here until a worker is idle and the volume has room for them.
+    let mut pending: VecDeque<PathBuf> = VecDeque::new();
+    let mut running: HashMap<PathBuf, u64> = HashMap::new();
+    let mut disk_guard = DiskSpaceGuard {
+        min_free_bytes: config.min_free_space_mb.saturating_mul(BYTES_PER_MB),
+        recheck_at: None,
+    };
     enqueue_initial_pending_files(
-        &config,
-        &job_tx,
+        collect_watch_folder_files(&config, &restored, &ledger),
+        &mut pending,
         false,
         &mut last_enqueued,
         &mut last_signature,
         &mut in_flight,
+        &mut progress,
     );
     let rescan_interval = effective_rescan_interval_secs(config.rescan_interval_secs);
     let mut next_rescan_at = Instant::now() + Duration::from_secs(rescan_interval);
 
     loop {
-        if stop_rx.try_recv().is_ok() {
-            break;
+        match command_rx.try_recv() {
+            Ok(WatchCommand::Stop) => break,
+            Ok(WatchCommand::Rescan) => next_rescan_at = Instant::now(),
+            Ok(WatchCommand::Retry(paths)) => {
+                for path in paths.iter().filter(|path| is_target_file(path, &config)) {
+                    enqueue_conversion_job(
+                        &mut pending,
+                        path,
+                        true,
+                        &mut last_enqueued,
+                        &mut last_signature,
+                        &mut in_flight,
+                        &mut progress,
+                    );
+                }
+            }
+            Err(_) => {}
         }
-        drain_completed_jobs(&done_rx, &mut in_flight);
+        drain_completed_jobs(&done_rx, &mut in_flight, &mut running, &mut progress);
+        release_pending_jobs(
+            &job_tx,
+            &mut pending,
+            &mut running,
+            &mut in_flight,
+            &mut disk_guard,
+            &*on_status,
+            &mut progress,
+        );
 
         match event_rx.recv_timeout(Duration::from_millis(200)) {
             Ok(Ok(event)) => {
                 for path in event.paths {
-                    if is_target_file(&path) {
+                    if is_target_file(&path, &config) {
+                        if is_unchanged_restore_now(&path, &journal, &mut restored) {
+                            continue;
+                        }
                         enqueue_conversion_job(
-                            &job_tx,
+                            &mut pending,
                             &path,
                             false,
                             &mut last_enqueued,
                             &mut last_signature,
                             &mut in_flight,
+                            &mut progress,
                         );
+                    } else if !path.exists() {
+                        remove_thumbnails_of_deleted_source(&ledger, &path);
                     }
                 }
             }
-            Ok(Err(err)) => log::warn!("watch event error: {err}"),
+            Ok(Err(err)) => {
+                log::warn!("watch event error: {err}");
+                metrics::record_watcher_error();
+            }
             Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
             Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
         }
 
         if Instant::now() >= next_rescan_at {
+            let rescan_started = Instant::now();
+            remove_thumbnails_of_missing_sources(&ledger);
+            restored = load_restored(&journal);
             enqueue_initial_pending_files(
-                &config,
-                &job_tx,
+                collect_watch_folder_files(&config, &restored, &ledger),
+                &mut pending,
                 true,
                 &mut last_enqueued,
                 &mut last_signature,
                 &mut in_flight,
+                &mut progress,
             );
+            metrics::record_rescan(rescan_started.elapsed());
             next_rescan_at = Instant::now() + Duration::from_secs(rescan_interval);
         }
+        metrics::set_queue(pending.len(), running.len());
+        progress.report(pending.len(), running.len(), Instant::now());
     }
 
+    // Queued jobs are dropped with the dispatcher. The batch is not reported
+    // as ended from here: `stop` may be joining this thread on the main thread.
+    metrics::set_queue(0, 0);
     drop(job_tx);
     for handle in worker_handles {
         if let Err(err) = handle.join() {
@@ -185,35 +476,95 @@ fn run_dispatcher(config: AppConfig, stop_rx: Receiver<()>) -> Result<(), String
 }
 
 fn enqueue_initial_pending_files(
-    config: &AppConfig,
-    job_tx: &Sender<PathBuf>,
+    files: Vec<PathBuf>,
+    pending: &mut VecDeque<PathBuf>,
     allow_same_signature: bool,
     last_enqueued: &mut HashMap<PathBuf, Instant>,
     last_signature: &mut HashMap<PathBuf, FileSignature>,
     in_flight: &mut HashSet<PathBuf>,
+    progress: &mut ProgressTracker,
 ) {
-    for root in &config.watch_folders {
-        let files = collect_pending_files(root, config.recursive_watch);
-        for path in files {
-            enqueue_conversion_job(
-                job_tx,
-                &path,
-                allow_same_signature,
-                last_enqueued,
-                last_signature,
-                in_flight,
-            );
+    for path in files {
+        enqueue_conversion_job(
+            pending,
+            &path,
+            allow_same_signature,
+            last_enqueued,
+            last_signature,
+            in_flight,
+            progress,
+        );
+    }
+}
+
+/// Candidates in every watch folder, except originals put back by undo and
+/// multi-image sources whose numbered JPEGs are still there.
+fn collect_watch_folder_files(
+    config: &AppConfig,
+    restored: &HashMap<PathBuf, FileSignature>,
+    ledger: &Mutex<Ledger>,
+) -> Vec<PathBuf> {
+    let converted = match ledger.lock() {
+        Ok(ledger) => ledger.converted_sources(),
+        Err(err) => {
+            log::warn!("failed to lock ledger: {err}");
+            HashSet::new()
+        }
+    };
+    config
+        .watch_folders
+        .iter()
+        .flat_map(|root| collect_pending_files(root, config))
+        .filter(|path| !is_unchanged_restore(path, restored) && !converted.contains(path))
+        .collect()
+}
+
+/// A restored original is converted again once it changes.
+fn is_unchanged_restore(path: &Path, restored: &HashMap<PathBuf, FileSignature>) -> bool {
+    restored
+        .get(path)
+        .is_some_and(|known| file_signature(path).as_ref() == Some(known))
+}
+
+/// Like `is_unchanged_restore`, with the journal read again first:
+/// `heic-ready undo` puts originals back from its own process, and reading
+/// the journal waits until it has recorded them.
+fn is_unchanged_restore_now(
+    path: &Path,
+    journal: &TrashJournal,
+    restored: &mut HashMap<PathBuf, FileSignature>,
+) -> bool {
+    *restored = load_restored(journal);
+    is_unchanged_restore(path, restored)
+}
+
+fn load_restored(journal: &TrashJournal) -> HashMap<PathBuf, FileSignature> {
+    match journal.restored() {
+        Ok(restored) => restored
+            .into_iter()
+            .map(|original| {
+                let signature = FileSignature {
+                    len: original.len,
+                    modified: original.modified,
+                };
+                (original.path, signature)
+            })
+            .collect(),
+        Err(err) => {
+            log::warn!("failed to read restored originals: {err}");
+            HashMap::new()
         }
     }
 }
 
 fn enqueue_conversion_job(
-    job_tx: &Sender<PathBuf>,
+    pending: &mut VecDeque<PathBuf>,
     path: &Path,
     allow_same_signature: bool,
     last_enqueued: &mut HashMap<PathBuf, Instant>,
     last_signature: &mut HashMap<PathBuf, FileSignature>,
     in_flight: &mut HashSet<PathBuf>,
+    progress: &mut ProgressTracker,
 ) {
     let now = Instant::now();
     let Some(signature) = file_signature(path) else {
@@ -234,22 +585,114 @@ fn enqueue_conversion_job(
     last_enqueued.insert(path.to_path_buf(), now);
     last_signature.insert(path.to_path_buf(), signature);
     in_flight.insert(path.to_path_buf());
-    if let Err(err) = job_tx.send(path.to_path_buf()) {
-        log::error!("failed to enqueue path {}: {err}", path.display());
-        in_flight.remove(path);
+    pending.push_back(path.to_path_buf());
+    progress.queued(path, now);
+}
+
+/// Hands queued jobs to idle workers while the output volume has room for
+/// them plus the jobs already running; otherwise nothing starts until space
+/// frees up, which is checked again every `LOW_DISK_SPACE_RECHECK_INTERVAL`.
+fn release_pending_jobs(
+    job_tx: &Sender<PathBuf>,
+    pending: &mut VecDeque<PathBuf>,
+    running: &mut HashMap<PathBuf, u64>,
+    in_flight: &mut HashSet<PathBuf>,
+    disk_guard: &mut DiskSpaceGuard,
+    on_status: &dyn Fn(WatchStatus),
+    progress: &mut ProgressTracker,
+) {
+    let now = Instant::now();
+    if disk_guard.recheck_at.is_some_and(|at| now < at) {
+        return;
+    }
+    while running.len() < WORKER_COUNT {
+        let Some(path) = pending.pop_front() else {
+            break;
+        };
+        let estimate = estimate_job_bytes(&path);
+        let reserved: u64 = running.values().sum();
+        if let Err(low) = check_free_space(&path, estimate.saturating_add(reserved), disk_guard) {
+            if disk_guard.recheck_at.is_none() {
+                let reason = format!(
+                    "paused: {} MB free on the output volume, {} MB needed",
+                    low.available / BYTES_PER_MB,
+                    low.required.div_ceil(BYTES_PER_MB)
+                );
+                log::info!("{reason} ({})", low.volume.display());
+                push_recent_log(&low.volume, "info", &reason);
+                on_status(WatchStatus::LowDiskSpace);
+            }
+            disk_guard.recheck_at = Some(now + LOW_DISK_SPACE_RECHECK_INTERVAL);
+            pending.push_front(path);
+            return;
+        }
+        if disk_guard.recheck_at.take().is_some() {
+            log::info!("resumed: enough free space on the output volume");
+            push_recent_info("resumed: enough free space on the output volume");
+            on_status(WatchStatus::Running);
+        }
+        if let Err(err) = job_tx.send(path.clone()) {
+            log::error!("failed to enqueue path {}: {err}", path.display());
+            in_flight.remove(&path);
+            continue;
+        }
+        progress.started(&path);
+        running.insert(path, estimate);
     }
 }
 
+fn estimate_job_bytes(path: &Path) -> u64 {
+    fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+        .saturating_mul(DISK_SPACE_ESTIMATE_FACTOR)
+}
+
+/// Outputs are written next to the source, so its folder decides the volume.
+/// A volume that cannot be queried never holds jobs back; the worker reports
+/// the underlying access problem instead.
+fn check_free_space(
+    path: &Path,
+    needed: u64,
+    disk_guard: &DiskSpaceGuard,
+) -> Result<(), LowDiskSpace> {
+    let Some(volume) = path.parent() else {
+        return Ok(());
+    };
+    let available = match fs2::available_space(volume) {
+        Ok(available) => available,
+        Err(err) => {
+            log::warn!("failed to query free space of {}: {err}", volume.display());
+            return Ok(());
+        }
+    };
+    let required = needed.saturating_add(disk_guard.min_free_bytes);
+    if available < required {
+        return Err(LowDiskSpace {
+            volume: volume.to_path_buf(),
+            available,
+            required,
+        });
+    }
+    Ok(())
+}
+
 fn spawn_workers(
     job_rx: Receiver<PathBuf>,
-    done_tx: Sender<PathBuf>,
+    done_tx: Sender<(PathBuf, JobResult)>,
     config: AppConfig,
+    ledger: Arc<Mutex<Ledger>>,
+    journal: TrashJournal,
+    stats: StatsStore,
 ) -> Vec<thread::JoinHandle<()>> {
     let mut handles = Vec::with_capacity(WORKER_COUNT);
     for worker_id in 0..WORKER_COUNT {
         let worker_job_rx = job_rx.clone();
         let worker_done_tx = done_tx.clone();
         let worker_config = config.clone();
+        let worker_ledger = Arc::clone(&ledger);
+        let worker_journal = journal.clone();
+        let worker_stats = stats.clone();
         let builder = thread::Builder::new().name(format!("watch-worker-{worker_id}"));
         let handle = builder
             .spawn(move || {
@@ -258,6 +701,9 @@ fn spawn_workers(
                     worker_job_rx,
                     worker_done_tx,
                     worker_config,
+                    worker_ledger,
+                    worker_journal,
+                    worker_stats,
                 )
             })
             .expect("spawn worker thread");
@@ -269,8 +715,11 @@ fn spawn_workers(
 fn worker_loop(
     worker_id: usize,
     job_rx: Receiver<PathBuf>,
-    done_tx: Sender<PathBuf>,
+    done_tx: Sender<(PathBuf, JobResult)>,
     config: AppConfig,
+    ledger: Arc<Mutex<Ledger>>,
+    journal: TrashJournal,
+    stats: StatsStore,
 ) {
     loop {
         match job_rx.recv_timeout(Duration::from_millis(200)) {
@@ -278,46 +727,104 @@ fn worker_loop(
                 if is_lock_file(&path) {
                     log::info!("[worker {worker_id}] skipped lock file: {}", path.display());
                     push_recent_log(&path, "skip", "lock file");
-                    let _ = done_tx.send(path);
+                    let _ = done_tx.send((path, JobResult::Skipped));
                     continue;
                 }
 
-                match wait_for_stable_file(&path) {
+                let result = match wait_for_stable_file(&path) {
                     Ok(true) => {
                         log::info!("[worker {worker_id}] file is stable: {}", path.display());
-                        match convert_heic_file(&path, &config) {
-                            Ok(ConvertOutcome::Success(output_path)) => {
+                        let format = match inspect_source(&path, &config) {
+                            Ok(SourceContent::Decode(format)) => format,
+                            Ok(SourceContent::Jpeg) => {
+                                let result = match adopt_jpeg_source(worker_id, &path, &config) {
+                                    Ok(details) => record_stats(
+                                        &stats,
+                                        &config,
+                                        &path,
+                                        JobOutcome::Converted(&details),
+                                    ),
+                                    Err(err) => record_stats(
+                                        &stats,
+                                        &config,
+                                        &path,
+                                        JobOutcome::Failed(err.code()),
+                                    ),
+                                };
+                                let _ = done_tx.send((path, result));
+                                continue;
+                            }
+                            Err(err) => {
+                                log::warn!(
+                                    "[worker {worker_id}] skipped {}: [{}] {err}",
+                                    path.display(),
+                                    err.code()
+                                );
+                                push_recent_error(&path, "skip", &err);
+                                let result =
+                                    record_stats(&stats, &config, &path, JobOutcome::Skipped);
+                                let _ = done_tx.send((path, result));
+                                continue;
+                            }
+                        };
+                        match convert_heic_file(&path, format, &config, &ledger, &journal) {
+                            Ok(ConvertOutcome::Success { outputs, details }) => {
+                                let description = describe_outputs(&outputs);
                                 log::info!(
-                                    "[worker {worker_id}] converted to jpeg: {} -> {}",
+                                    "[worker {worker_id}] converted to jpeg: {} -> {} ({description})",
                                     path.display(),
-                                    output_path.display()
+                                    describe_output_paths(&outputs)
+                                );
+                                let result = record_stats(
+                                    &stats,
+                                    &config,
+                                    &path,
+                                    JobOutcome::Converted(&details),
                                 );
-                                push_recent_log(&path, "success", "converted to jpeg");
+                                push_recent_conversion(
+                                    &path,
+                                    None,
+                                    format!("converted to jpeg ({description})").as_str(),
+                                    details,
+                                );
+                                result
                             }
                             Ok(ConvertOutcome::SuccessWithWarning {
-                                output_path,
+                                outputs,
+                                details,
                                 warning,
+                                code,
                             }) => {
+                                let description = describe_outputs(&outputs);
                                 log::warn!(
-                                    "[worker {worker_id}] converted with warning {} -> {}: {}",
+                                    "[worker {worker_id}] converted with warning {} -> {} ({description}): {}",
                                     path.display(),
-                                    output_path.display(),
+                                    describe_output_paths(&outputs),
                                     warning
                                 );
-                                push_recent_log(
+                                let result = record_stats(
+                                    &stats,
+                                    &config,
+                                    &path,
+                                    JobOutcome::Converted(&details),
+                                );
+                                push_recent_conversion(
                                     &path,
-                                    "success",
-                                    format!("converted with warning: {warning}").as_str(),
+                                    code,
+                                    format!("converted with warning ({description}): {warning}")
+                                        .as_str(),
+                                    details,
                                 );
+                                result
                             }
                             Err(err) => {
-                                let category = classify_conversion_error(err.as_str());
-                                let detailed = format!("[{category}] {err}");
                                 log::error!(
-                                    "[worker {worker_id}] failed converting {}: {detailed}",
-                                    path.display()
+                                    "[worker {worker_id}] failed converting {}: [{}] {err}",
+                                    path.display(),
+                                    err.code()
                                 );
-                                push_recent_log(&path, "failure", detailed.as_str());
+                                push_recent_error(&path, "failure", &err);
+                                record_stats(&stats, &config, &path, JobOutcome::Failed(err.code()))
                             }
                         }
                     }
@@ -326,17 +833,29 @@ fn worker_loop(
                             "[worker {worker_id}] file did not stabilize within retry limit: {}",
                             path.display()
                         );
-                        push_recent_log(&path, "skip", "did not stabilize within retry limit");
+                        push_recent_error(
+                            &path,
+                            "skip",
+                            &ConvertError::Stabilize(
+                                "did not stabilize within retry limit".to_string(),
+                            ),
+                        );
+                        record_stats(&stats, &config, &path, JobOutcome::Skipped)
                     }
                     Err(err) => {
                         log::warn!(
                             "[worker {worker_id}] skipped file due to access error {}: {err}",
                             path.display()
                         );
-                        push_recent_log(&path, "skip", &format!("access error: {err}"));
+                        push_recent_error(
+                            &path,
+                            "skip",
+                            &ConvertError::from_io(ConvertError::Stabilize, "access error", &err),
+                        );
+                        record_stats(&stats, &config, &path, JobOutcome::Skipped)
                     }
-                }
-                let _ = done_tx.send(path);
+                };
+                let _ = done_tx.send((path, result));
             }
             Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
             Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
@@ -393,19 +912,130 @@ fn file_signature(path: &Path) -> Option<FileSignature> {
     }
 }
 
-fn drain_completed_jobs(done_rx: &Receiver<PathBuf>, in_flight: &mut HashSet<PathBuf>) {
-    while let Ok(path) = done_rx.try_recv() {
+fn drain_completed_jobs(
+    done_rx: &Receiver<(PathBuf, JobResult)>,
+    in_flight: &mut HashSet<PathBuf>,
+    running: &mut HashMap<PathBuf, u64>,
+    progress: &mut ProgressTracker,
+) {
+    while let Ok((path, result)) = done_rx.try_recv() {
         in_flight.remove(&path);
+        running.remove(&path);
+        progress.finished(&path, result);
     }
 }
 
-fn collect_pending_files(root: &Path, recursive: bool) -> Vec<PathBuf> {
+impl ProgressTracker {
+    fn new(listener: ProgressListener) -> Self {
+        Self {
+            listener,
+            batch_started_at: None,
+            started_unix_ms: None,
+            converted: 0,
+            failed: 0,
+            skipped: 0,
+            reported: BatchProgress::default(),
+            reported_at: None,
+        }
+    }
+
+    fn queued(&mut self, path: &Path, now: Instant) {
+        if self.batch_started_at.is_none() {
+            self.batch_started_at = Some(now);
+            self.started_unix_ms = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|value| value.as_millis())
+                .ok();
+        }
+        self.job(JobStage::Queued, path, None);
+    }
+
+    fn started(&mut self, path: &Path) {
+        self.job(JobStage::Started, path, None);
+    }
+
+    fn finished(&mut self, path: &Path, result: JobResult) {
+        match result {
+            JobResult::Converted(_) => self.converted += 1,
+            JobResult::Failed(_) => self.failed += 1,
+            JobResult::Skipped => self.skipped += 1,
+        }
+        self.job(JobStage::Finished, path, Some(result));
+    }
+
+    fn job(&self, stage: JobStage, path: &Path, result: Option<JobResult>) {
+        (self.listener)(ProgressEvent::Job {
+            stage,
+            path: path.to_path_buf(),
+            result,
+        });
+    }
+
+    /// Called on every dispatcher turn; changes are reported at most every
+    /// `PROGRESS_REPORT_INTERVAL`, except the end of a batch.
+    fn report(&mut self, queued: usize, running: usize, now: Instant) {
+        let Some(started_at) = self.batch_started_at else {
+            return;
+        };
+        let remaining = queued + running;
+        let completed = self.converted + self.failed + self.skipped;
+        let progress = BatchProgress {
+            completed,
+            total: completed + remaining,
+            queued,
+            running,
+            converted: self.converted,
+            failed: self.failed,
+            skipped: self.skipped,
+            eta_secs: estimate_eta_secs(now.duration_since(started_at), completed, remaining),
+            started_unix_ms: self.started_unix_ms,
+        };
+        if remaining == 0 {
+            (self.listener)(ProgressEvent::Batch(progress));
+            self.clear_batch();
+            return;
+        }
+        let unchanged = BatchProgress {
+            eta_secs: self.reported.eta_secs,
+            ..progress
+        } == self.reported;
+        let too_soon = self
+            .reported_at
+            .is_some_and(|at| now.duration_since(at) < PROGRESS_REPORT_INTERVAL);
+        if unchanged || too_soon {
+            return;
+        }
+        (self.listener)(ProgressEvent::Batch(progress));
+        self.reported = progress;
+        self.reported_at = Some(now);
+    }
+
+    fn clear_batch(&mut self) {
+        self.batch_started_at = None;
+        self.started_unix_ms = None;
+        self.converted = 0;
+        self.failed = 0;
+        self.skipped = 0;
+        self.reported = BatchProgress::default();
+        self.reported_at = None;
+    }
+}
+
+fn estimate_eta_secs(elapsed: Duration, completed: usize, remaining: usize) -> Option<u64> {
+    if completed == 0 {
+        return None;
+    }
+    let per_job = elapsed.as_secs_f64() / completed as f64;
+    Some((per_job * remaining as f64).ceil() as u64)
+}
+
+fn collect_pending_files(root: &Path, config: &AppConfig) -> Vec<PathBuf> {
     let mut pending = Vec::new();
-    collect_pending_files_impl(root, recursive, &mut pending);
+    collect_pending_files_impl(root, config, &mut pending);
     pending
 }
 
-fn collect_pending_files_impl(path: &Path, recursive: bool, out: &mut Vec<PathBuf>) {
+fn collect_pending_files_impl(path: &Path, config: &AppConfig, out: &mut Vec<PathBuf>) {
     let entries = match fs::read_dir(path) {
         Ok(entries) => entries,
         Err(err) => {
@@ -428,15 +1058,15 @@ fn collect_pending_files_impl(path: &Path, recursive: bool, out: &mut Vec<PathBu
         };
 
         if file_type.is_dir() {
-            if recursive {
-                collect_pending_files_impl(&entry_path, true, out);
+            if config.recursive_watch && !trash::is_trash_dir(&entry_path) {
+                collect_pending_files_impl(&entry_path, config, out);
             }
             continue;
         }
         if !file_type.is_file() {
             continue;
         }
-        if !is_target_extension(&entry_path) {
+        if !is_target_extension(&entry_path, config.input_formats_for(&entry_path)) {
             continue;
         }
         if has_jpeg_sibling(&entry_path) {
@@ -447,6 +1077,8 @@ fn collect_pending_files_impl(path: &Path, recursive: bool, out: &mut Vec<PathBu
     }
 }
 
+/// Multi-image outputs are numbered, and such names are common enough that
+/// the ledger tracks them instead; see `Ledger::converted_sources`.
 fn has_jpeg_sibling(path: &Path) -> bool {
     let Some(stem) = path.file_stem().and_then(|value| value.to_str()) else {
         return false;
@@ -457,27 +1089,333 @@ fn has_jpeg_sibling(path: &Path) -> bool {
     parent.join(format!("{stem}.jpg")).exists()
 }
 
-fn convert_heic_file(input_path: &Path, config: &AppConfig) -> Result<ConvertOutcome, String> {
-    let output_path = resolve_output_path(input_path);
-    let tmp_output_path = tmp_output_path_for(&output_path);
-    run_sips_convert(input_path, &tmp_output_path, config.jpeg_quality)?;
-    fs::rename(&tmp_output_path, &output_path).map_err(|err| {
-        format!(
-            "failed to finalize output {}: {err}",
-            output_path.display()
-        )
-    })?;
+fn convert_heic_file(
+    input_path: &Path,
+    format: InputFormat,
+    config: &AppConfig,
+    ledger: &Mutex<Ledger>,
+    journal: &TrashJournal,
+) -> Result<ConvertOutcome, ConvertError> {
+    let started = Instant::now();
+    let source_bytes = fs::metadata(input_path).ok().map(|metadata| metadata.len());
+    let images = converter::convert_source(
+        input_path,
+        format,
+        &ConvertOptions::for_source(config, input_path),
+    )?;
+    let output_paths = resolve_output_paths(input_path, images.len());
+    let sidecars = finalize_outputs(&images, &output_paths, config)?;
+    let thumbnails: Vec<PathBuf> = sidecars
+        .iter()
+        .filter(|(kind, _)| matches!(kind, SidecarKind::Thumbnail { .. }))
+        .map(|(_, path)| path.clone())
+        .collect();
+    let outputs: Vec<ConvertedOutput> = output_paths
+        .into_iter()
+        .zip(images)
+        .map(|(path, image)| ConvertedOutput {
+            path,
+            bytes: image.bytes.len() as u64,
+            report: image.report,
+        })
+        .collect();
+
+    let mut warnings = Vec::new();
+    let mut warning_code = None;
+    if let Some(max_bytes) = outputs
+        .iter()
+        .find_map(|output| output.report.missed_target_bytes)
+    {
+        warnings.push(format!(
+            "output exceeds target size of {max_bytes} bytes at minimum quality"
+        ));
+    }
+    // The HEIC's metadata identifies the video, so look before it is trashed.
+    let companion = match config.live_photo_policy {
+        LivePhotoPolicy::Keep => None,
+        _ => live_photo::find_companion(input_path),
+    };
+    let mut trash_moves = Vec::new();
+    let mut source_moved = false;
+    let mut archived_to: Option<PathBuf> = None;
+    match &config.output_policy {
+        OutputPolicy::Coexist => {}
+        OutputPolicy::Replace if config.replace_mode.is_permanent() => {
+            match delete_source_permanently(input_path, config) {
+                Ok(()) => {
+                    log::info!("deleted source permanently: {}", input_path.display());
+                    source_moved = true;
+                }
+                Err(err) => {
+                    warning_code.get_or_insert(err.code());
+                    warnings.push(format!("source kept in place: {err}"));
+                }
+            }
+        }
+        OutputPolicy::Replace => match move_file_to_trash(input_path) {
+            Ok(trashed) => {
+                trash_moves.push(TrashMove {
+                    original: input_path.to_path_buf(),
+                    trashed,
+                });
+                source_moved = true;
+            }
+            Err(err) => {
+                warning_code.get_or_insert(err.code());
+                warnings.push(format!("replace fallback to coexist: {err}"));
+            }
+        },
+        OutputPolicy::Archive(options) => {
+            match archive::move_to_archive(input_path, options, config) {
+                Ok(destination) => {
+                    log::info!(
+                        "archived source: {} -> {}",
+                        input_path.display(),
+                        destination.display()
+                    );
+                    archived_to = Some(destination);
+                    source_moved = true;
+                }
+                Err(err) => {
+                    warning_code.get_or_insert(err.code());
+                    warnings.push(format!("source kept in place: {err}"));
+                }
+            }
+        }
+    }
+    let trashed_as = trash_moves
+        .first()
+        .map(|moved| moved.trashed.display().to_string());
+    let moved_source = match &archived_to {
+        Some(destination) => MovedSource::Archived(destination),
+        None if !trash_moves.is_empty() => MovedSource::Trashed,
+        None => MovedSource::Kept,
+    };
+    if let (Some(companion), Some(output)) = (companion, outputs.first()) {
+        match apply_live_photo_policy(
+            &companion,
+            &output.path,
+            config.live_photo_policy,
+            moved_source,
+        ) {
+            Ok(Some(trashed)) => trash_moves.push(TrashMove {
+                original: companion,
+                trashed,
+            }),
+            Ok(None) => {}
+            Err(err) => warnings.push(format!("live photo video left in place: {err}")),
+        }
+    }
+    if !trash_moves.is_empty() {
+        let generated = outputs
+            .iter()
+            .map(|output| output.path.clone())
+            .chain(sidecars.into_iter().map(|(_, path)| path))
+            .collect();
+        if let Err(err) = journal.record(trash_moves, generated) {
+            warnings.push(format!("trash move cannot be undone: {err}"));
+        }
+    }
+    // Once the HEIC is trashed, archived or deleted the JPEG stands in for it.
+    let ledger_source = match outputs.first() {
+        Some(output) if source_moved => output.path.as_path(),
+        _ => input_path,
+    };
+    // Numbered JPEGs next to a kept source mark it as converted.
+    let numbered_outputs: Vec<PathBuf> = if !source_moved && outputs.len() > 1 {
+        outputs.iter().map(|output| output.path.clone()).collect()
+    } else {
+        Vec::new()
+    };
+    let recorded = ledger
+        .lock()
+        .map_err(|err| format!("failed to lock ledger: {err}"))
+        .and_then(|mut ledger| ledger.record(ledger_source, &thumbnails, &numbered_outputs));
+    if let Err(err) = recorded {
+        warnings.push(format!("thumbnails not tracked: {err}"));
+    }
+
+    let details = ConversionDetails {
+        policy: policy_label(config).to_string(),
+        outputs: outputs
+            .iter()
+            .map(|output| output.path.display().to_string())
+            .collect(),
+        trashed_as,
+        archived_to: archived_to.map(|destination| destination.display().to_string()),
+        source_bytes,
+        output_bytes: outputs.iter().map(|output| output.bytes).sum(),
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+    if !warnings.is_empty() {
+        return Ok(ConvertOutcome::SuccessWithWarning {
+            outputs,
+            details,
+            warning: warnings.join("; "),
+            code: warning_code,
+        });
+    }
+    Ok(ConvertOutcome::Success { outputs, details })
+}
+
+fn policy_label(config: &AppConfig) -> &'static str {
+    match (&config.output_policy, config.replace_mode) {
+        (OutputPolicy::Coexist, _) => "coexist",
+        (OutputPolicy::Replace, ReplaceMode::Trash) => "replace",
+        (OutputPolicy::Replace, ReplaceMode::Delete) => "replace_delete",
+        (OutputPolicy::Replace, ReplaceMode::SecureDelete) => "replace_secure_delete",
+        (OutputPolicy::Archive(_), _) => "archive",
+    }
+}
+
+struct PendingOutput<'a> {
+    bytes: &'a [u8],
+    path: PathBuf,
+    /// Only set for the JPEGs themselves; sidecars are not read back.
+    expected_dimensions: Option<(u32, u32)>,
+}
 
-    if matches!(config.output_policy, OutputPolicy::Replace) {
-        if let Err(err) = move_file_to_trash(input_path) {
-            return Ok(ConvertOutcome::SuccessWithWarning {
-                output_path,
-                warning: format!("replace fallback to coexist: {err}"),
+/// Writes every image and sidecar to its `.tmp` path first and only renames
+/// once all of them were written, so a failure never leaves a partial set behind.
+/// With `verify_outputs`, and always before archiving or deleting the source,
+/// each JPEG is also read back before anything is renamed. Returns where the
+/// sidecars were written.
+fn finalize_outputs(
+    images: &[ConvertedImage],
+    output_paths: &[PathBuf],
+    config: &AppConfig,
+) -> Result<Vec<(SidecarKind, PathBuf)>, ConvertError> {
+    let mut files: Vec<PendingOutput> = Vec::new();
+    let mut sidecars = Vec::new();
+    for (image, output_path) in images.iter().zip(output_paths) {
+        files.push(PendingOutput {
+            bytes: &image.bytes,
+            path: output_path.clone(),
+            expected_dimensions: Some(image.report.output_dimensions),
+        });
+        for sidecar in &image.sidecars {
+            let path = resolve_sidecar_path(output_path, sidecar.kind, config);
+            sidecars.push((sidecar.kind, path.clone()));
+            files.push(PendingOutput {
+                bytes: &sidecar.bytes,
+                path,
+                expected_dimensions: None,
             });
         }
     }
+    for PendingOutput { path, .. } in &files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|err| {
+                ConvertError::from_io(
+                    ConvertError::Finalize,
+                    format_args!("failed to create {}", parent.display()),
+                    &err,
+                )
+            })?;
+        }
+    }
+    let final_paths: Vec<PathBuf> = files.iter().map(|file| file.path.clone()).collect();
+    let tmp_paths: Vec<PathBuf> = final_paths
+        .iter()
+        .map(|path| tmp_output_path_for(path))
+        .collect();
+    let remove_all = |paths: &[PathBuf]| {
+        for path in paths {
+            if path.exists() {
+                let _ = fs::remove_file(path);
+            }
+        }
+    };
+
+    for (file, tmp_path) in files.iter().zip(&tmp_paths) {
+        if let Err(err) = converter::write_output(tmp_path, file.bytes) {
+            remove_all(&tmp_paths);
+            return Err(err);
+        }
+    }
+    if must_verify_outputs(config) {
+        for (file, tmp_path) in files.iter().zip(&tmp_paths) {
+            let Some(expected) = file.expected_dimensions else {
+                continue;
+            };
+            if let Err(err) = converter::verify_output(tmp_path, expected) {
+                remove_all(&tmp_paths);
+                return Err(ConvertError::Verify(err));
+            }
+        }
+    }
+    for (index, (tmp_path, final_path)) in tmp_paths.iter().zip(&final_paths).enumerate() {
+        if let Err(err) = fs::rename(tmp_path, final_path) {
+            remove_all(&final_paths[..index]);
+            remove_all(&tmp_paths[index..]);
+            return Err(ConvertError::from_io(
+                ConvertError::Finalize,
+                format_args!("failed to finalize output {}", final_path.display()),
+                &err,
+            ));
+        }
+    }
+    Ok(sidecars)
+}
+
+fn describe_outputs(outputs: &[ConvertedOutput]) -> String {
+    match outputs {
+        [single] => describe_report(&single.report),
+        _ => format!(
+            "{} images: {}",
+            outputs.len(),
+            outputs
+                .iter()
+                .map(|output| describe_report(&output.report))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ),
+    }
+}
+
+fn describe_output_paths(outputs: &[ConvertedOutput]) -> String {
+    outputs
+        .iter()
+        .map(|output| output.path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn describe_report(report: &ConvertReport) -> String {
+    let (source_width, source_height) = report.source_dimensions;
+    let (output_width, output_height) = report.output_dimensions;
+    let quality = report.jpeg_quality;
+    let hdr = if report.ultra_hdr { ", ultra hdr" } else { "" };
+    if report.source_dimensions == report.output_dimensions {
+        return format!("{output_width}x{output_height}, quality {quality}{hdr}");
+    }
+    format!(
+        "{source_width}x{source_height} -> {output_width}x{output_height}, quality {quality}{hdr}"
+    )
+}
+
+/// Output paths for `count` images: the usual `stem.jpg` for a single image,
+/// `stem_001.jpg`, `stem_002.jpg`, ... when a container yields several.
+fn resolve_output_paths(input_path: &Path, count: usize) -> Vec<PathBuf> {
+    if count <= 1 {
+        return vec![resolve_output_path(input_path)];
+    }
+    let Some(parent) = input_path.parent() else {
+        return (1..=count)
+            .map(|index| input_path.with_extension(format!("{index:03}.jpg")))
+            .collect();
+    };
+    let stem = input_path
+        .file_stem()
+        .and_then(|value| value.to_str())
+        .unwrap_or("converted");
+    (1..=count)
+        .map(|index| resolve_output_path_for_stem(parent, &numbered_stem(stem, index)))
+        .collect()
+}
 
-    Ok(ConvertOutcome::Success(output_path))
+fn numbered_stem(stem: &str, index: usize) -> String {
+    format!("{stem}_{index:03}")
 }
 
 fn resolve_output_path(input_path: &Path) -> PathBuf {
@@ -488,7 +1426,10 @@ fn resolve_output_path(input_path: &Path) -> PathBuf {
         .file_stem()
         .and_then(|value| value.to_str())
         .unwrap_or("converted");
+    resolve_output_path_for_stem(parent, stem)
+}
 
+fn resolve_output_path_for_stem(parent: &Path, stem: &str) -> PathBuf {
     let mut candidate = parent.join(format!("{stem}.jpg"));
     if !candidate.exists() {
         return candidate;
@@ -504,117 +1445,237 @@ fn resolve_output_path(input_path: &Path) -> PathBuf {
     }
 }
 
-fn tmp_output_path_for(output_path: &Path) -> PathBuf {
-    let file_name = output_path
-        .file_name()
+/// `IMG_0001.jpg` -> `IMG_0001_depth.png`, so sidecars sort next to their JPEG;
+/// thumbnails become `IMG_0001_256.jpg` in the configured thumbnail folder.
+fn sidecar_path_for(output_path: &Path, kind: SidecarKind, config: &AppConfig) -> PathBuf {
+    let stem = output_path
+        .file_stem()
         .map(|value| value.to_string_lossy().into_owned())
-        .unwrap_or_else(|| "output.jpg".to_string());
-    output_path.with_file_name(format!("{file_name}.tmp"))
+        .unwrap_or_else(|| "converted".to_string());
+    match kind {
+        SidecarKind::DepthMap => output_path.with_file_name(format!("{stem}_depth.png")),
+        SidecarKind::Thumbnail { size } => {
+            thumbnail_dir_for(output_path, config).join(format!("{stem}_{size}.jpg"))
+        }
+    }
 }
 
-fn run_sips_convert(input_path: &Path, output_path: &Path, quality: u8) -> Result<(), String> {
-    let quality_value = quality.to_string();
-    let output = Command::new("sips")
-        .arg("-s")
-        .arg("format")
-        .arg("jpeg")
-        .arg("-s")
-        .arg("formatOptions")
-        .arg(&quality_value)
-        .arg(input_path.as_os_str())
-        .arg("--out")
-        .arg(output_path.as_os_str())
-        .output()
-        .map_err(|err| format!("failed to run sips: {err}"))?;
-
-    if output.status.success() {
-        return Ok(());
-    }
+/// Like `sidecar_path_for`, but numbered the way `unique_destination_path`
+/// does (`IMG_0001_depth (1).png`) when the name is taken, so an existing file
+/// is never overwritten.
+fn resolve_sidecar_path(output_path: &Path, kind: SidecarKind, config: &AppConfig) -> PathBuf {
+    let candidate = sidecar_path_for(output_path, kind, config);
+    let dir = candidate.parent().unwrap_or_else(|| Path::new("."));
+    unique_destination_path(dir, &candidate)
+}
 
-    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-    if output_path.exists() {
-        let _ = fs::remove_file(output_path);
-    }
-    Err(format!(
-        "sips exited with status {}: {}",
-        output.status,
-        if stderr.is_empty() {
-            "no stderr output"
-        } else {
-            &stderr
-        }
-    ))
+fn thumbnail_dir_for(output_path: &Path, config: &AppConfig) -> PathBuf {
+    let parent = output_path.parent().unwrap_or_else(|| Path::new("."));
+    let location = config
+        .folder_options_for(output_path)
+        .and_then(|options| options.thumbnails.as_ref())
+        .map(|thumbnails| &thumbnails.location);
+    let Some(ThumbnailLocation::SeparateRoot { root }) = location else {
+        return parent.join(THUMBNAIL_DIR);
+    };
+    // Mirror the folder below its watch folder so equal names cannot collide.
+    root.join(config.path_below_watch_folder(parent))
 }
 
-fn classify_conversion_error(err: &str) -> &'static str {
-    let lower = err.to_ascii_lowercase();
-    if lower.contains("permission denied") || lower.contains("operation not permitted") {
-        return "permission";
-    }
-    if lower.contains("sips exited") {
-        return "decode";
+fn remove_thumbnails_of_deleted_source(ledger: &Mutex<Ledger>, source: &Path) {
+    let removed = match ledger.lock() {
+        Ok(mut ledger) => ledger.remove_source(source),
+        Err(err) => Err(format!("failed to lock ledger: {err}")),
+    };
+    match removed {
+        Ok(removed) => finish_thumbnail_removal(source, &removed),
+        Err(err) => log::warn!(
+            "failed to remove thumbnails of {}: {err}",
+            source.display()
+        ),
     }
-    "io"
 }
 
-fn move_file_to_trash(path: &Path) -> Result<(), String> {
-    let trash_dir = user_trash_dir()?;
-    fs::create_dir_all(&trash_dir)
-        .map_err(|err| format!("failed to create trash dir {}: {err}", trash_dir.display()))?;
-
-    let destination = unique_destination_path(&trash_dir, path);
-    let mut last_err = String::new();
-    for attempt in 1..=TRASH_MOVE_RETRIES {
-        match move_file_to_trash_once(path, &destination) {
-            Ok(()) => return Ok(()),
-            Err(err) => {
-                last_err = err;
-                if attempt < TRASH_MOVE_RETRIES {
-                    thread::sleep(TRASH_RETRY_DELAY);
-                }
+fn remove_thumbnails_of_missing_sources(ledger: &Mutex<Ledger>) {
+    let removed = match ledger.lock() {
+        Ok(mut ledger) => ledger.remove_missing_sources(),
+        Err(err) => Err(format!("failed to lock ledger: {err}")),
+    };
+    match removed {
+        Ok(removed) => {
+            for (source, thumbnails) in removed {
+                finish_thumbnail_removal(&source, &thumbnails);
             }
         }
+        Err(err) => log::warn!("failed to remove thumbnails of deleted sources: {err}"),
     }
-    Err(format!(
-        "failed to move file to trash after {} attempts: {}",
-        TRASH_MOVE_RETRIES, last_err
-    ))
 }
 
-fn move_file_to_trash_once(path: &Path, destination: &Path) -> Result<(), String> {
-    match fs::rename(path, destination) {
-        Ok(()) => Ok(()),
-        Err(err) if err.raw_os_error() == Some(18) => {
-            fs::copy(path, destination).map_err(|copy_err| {
-                format!(
-                    "failed to copy file to trash {}: {copy_err}",
+fn finish_thumbnail_removal(source: &Path, removed: &[PathBuf]) {
+    if removed.is_empty() {
+        return;
+    }
+    remove_empty_thumbnail_dirs(removed);
+    log::info!(
+        "removed {} thumbnails of deleted source {}",
+        removed.len(),
+        source.display()
+    );
+    push_recent_log(
+        source,
+        "info",
+        &format!("source deleted; removed {} thumbnails", removed.len()),
+    );
+}
+
+/// Drops `.thumbs` folders left empty; other folders may be the user's.
+fn remove_empty_thumbnail_dirs(removed: &[PathBuf]) {
+    let dirs: HashSet<&Path> = removed
+        .iter()
+        .filter_map(|path| path.parent())
+        .filter(|dir| dir.file_name().is_some_and(|name| name == THUMBNAIL_DIR))
+        .collect();
+    for dir in dirs {
+        // Fails while other thumbnails remain, which is the point.
+        let _ = fs::remove_dir(dir);
+    }
+}
+
+fn tmp_output_path_for(output_path: &Path) -> PathBuf {
+    let file_name = output_path
+        .file_name()
+        .map(|value| value.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "output.jpg".to_string());
+    output_path.with_file_name(format!("{file_name}.tmp"))
+}
+
+/// Where the source went after conversion; `TrashAlongside` sends the Live
+/// Photo video after it.
+#[derive(Clone, Copy, Debug)]
+enum MovedSource<'a> {
+    /// Kept in place or deleted permanently.
+    Kept,
+    Trashed,
+    /// With the archived path.
+    Archived(&'a Path),
+}
+
+/// Returns where the video went when it was moved to the trash.
+fn apply_live_photo_policy(
+    companion: &Path,
+    output_path: &Path,
+    policy: LivePhotoPolicy,
+    source: MovedSource<'_>,
+) -> Result<Option<PathBuf>, String> {
+    let destination = match policy {
+        LivePhotoPolicy::Keep => return Ok(None),
+        LivePhotoPolicy::TrashAlongside => match source {
+            MovedSource::Kept => return Ok(None),
+            MovedSource::Trashed => {
+                let trashed = move_file_to_trash(companion).map_err(|err| err.to_string())?;
+                log::info!("moved live photo video to trash: {}", companion.display());
+                return Ok(Some(trashed));
+            }
+            MovedSource::Archived(archived) => {
+                let destination =
+                    archive::move_alongside(companion, archived).map_err(|err| err.to_string())?;
+                log::info!(
+                    "archived live photo video: {} -> {}",
+                    companion.display(),
                     destination.display()
-                )
-            })?;
-            match fs::remove_file(path) {
-                Ok(()) => Ok(()),
-                Err(remove_err) => {
-                    // Recovery: remove copied trash file so source remains authoritative.
-                    let _ = fs::remove_file(destination);
-                    Err(format!(
-                        "failed to remove source after copy; rolled back copied trash file: {remove_err}"
-                    ))
-                }
+                );
+                return Ok(None);
             }
+        },
+        LivePhotoPolicy::RenameToOutput => {
+            let stem = output_path
+                .file_stem()
+                .map(|value| value.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "converted".to_string());
+            let ext = companion
+                .extension()
+                .map(|value| value.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "MOV".to_string());
+            let destination = companion.with_file_name(format!("{stem}.{ext}"));
+            if destination == companion {
+                return Ok(None);
+            }
+            if destination.exists() {
+                return Err(format!("{} already exists", destination.display()));
+            }
+            destination
+        }
+        LivePhotoPolicy::MoveToSidecarFolder => {
+            let parent = companion.parent().unwrap_or_else(|| Path::new("."));
+            let dir = parent.join(LIVE_PHOTO_SIDECAR_DIR);
+            fs::create_dir_all(&dir)
+                .map_err(|err| format!("failed to create {}: {err}", dir.display()))?;
+            unique_destination_path(&dir, companion)
         }
-        Err(err) => Err(format!(
-            "failed to move file to trash {}: {err}",
+    };
+    fs::rename(companion, &destination).map_err(|err| {
+        format!(
+            "failed to move {} to {}: {err}",
+            companion.display(),
             destination.display()
-        )),
+        )
+    })?;
+    log::info!(
+        "moved live photo video: {} -> {}",
+        companion.display(),
+        destination.display()
+    );
+    Ok(None)
+}
+
+/// The JPEG is the only copy left in the watch folder once the source is
+/// archived, and the only copy at all once it is deleted.
+fn must_verify_outputs(config: &AppConfig) -> bool {
+    config.verify_outputs
+        || match config.output_policy {
+            OutputPolicy::Coexist => false,
+            OutputPolicy::Replace => config.replace_mode.is_permanent(),
+            OutputPolicy::Archive(_) => true,
+        }
+}
+
+fn delete_source_permanently(path: &Path, config: &AppConfig) -> Result<(), ConvertError> {
+    if !config.confirm_permanent_delete {
+        return Err(ConvertError::Delete(
+            "permanent delete is not confirmed in the settings".to_string(),
+        ));
     }
+    trash::delete_permanently(path, config.replace_mode == ReplaceMode::SecureDelete)
 }
 
-fn user_trash_dir() -> Result<PathBuf, String> {
-    let home = std::env::var_os("HOME").ok_or_else(|| "HOME is not set".to_string())?;
-    Ok(PathBuf::from(home).join(".Trash"))
+fn move_file_to_trash(path: &Path) -> Result<PathBuf, ConvertError> {
+    let mut last_err = None;
+    for attempt in 1..=TRASH_MOVE_RETRIES {
+        match trash::move_to_trash(path) {
+            Ok(destination) => return Ok(destination),
+            Err(err) => {
+                last_err = Some(err);
+                if attempt < TRASH_MOVE_RETRIES {
+                    thread::sleep(TRASH_RETRY_DELAY);
+                }
+            }
+        }
+    }
+    // Keep the code of the last attempt, e.g. `permission`.
+    let last_err = last_err.unwrap_or_else(|| ConvertError::Trash("no attempt made".to_string()));
+    let message = format!(
+        "failed to move file to trash after {} attempts: {}",
+        TRASH_MOVE_RETRIES,
+        last_err.message()
+    );
+    Err(match last_err {
+        ConvertError::Permission(_) => ConvertError::Permission(message),
+        ConvertError::DiskFull(_) => ConvertError::DiskFull(message),
+        _ => ConvertError::Trash(message),
+    })
 }
 
-fn unique_destination_path(dir: &Path, source_path: &Path) -> PathBuf {
+pub(crate) fn unique_destination_path(dir: &Path, source_path: &Path) -> PathBuf {
     let file_name = source_path
         .file_name()
         .map(|name| name.to_string_lossy().into_owned())
@@ -647,14 +1708,63 @@ fn unique_destination_path(dir: &Path, source_path: &Path) -> PathBuf {
 }
 
 pub fn push_recent_info(reason: &str) {
-    push_recent_log_text("system".to_string(), "info", reason);
+    push_recent_log_text("system".to_string(), "info", None, reason);
 }
 
 fn push_recent_log(path: &Path, result: &'static str, reason: &str) {
-    push_recent_log_text(path.display().to_string(), result, reason);
+    push_recent_log_text(path.display().to_string(), result, None, reason);
+}
+
+fn push_recent_error(path: &Path, result: &'static str, err: &ConvertError) {
+    push_recent(recent_error_entry(path, result, err));
+}
+
+/// Failures keep the stable code of their `ConvertError` next to the message.
+fn recent_error_entry(path: &Path, result: &'static str, err: &ConvertError) -> RecentLogEntry {
+    RecentLogEntry::new(
+        path.display().to_string(),
+        result,
+        Some(err.code()),
+        err.message(),
+        None,
+    )
 }
 
-fn push_recent_log_text(path: String, result: &'static str, reason: &str) {
+fn push_recent_conversion(
+    path: &Path,
+    code: Option<&'static str>,
+    reason: &str,
+    details: ConversionDetails,
+) {
+    push_recent_entry(
+        path.display().to_string(),
+        "success",
+        code,
+        reason,
+        Some(details),
+    );
+}
+
+fn push_recent_log_text(
+    path: String,
+    result: &'static str,
+    code: Option<&'static str>,
+    reason: &str,
+) {
+    push_recent_entry(path, result, code, reason, None);
+}
+
+fn push_recent_entry(
+    path: String,
+    result: &'static str,
+    code: Option<&'static str>,
+    reason: &str,
+    details: Option<ConversionDetails>,
+) {
+    push_recent(RecentLogEntry::new(path, result, code, reason, details));
+}
+
+fn push_recent(entry: RecentLogEntry) {
     let logs = RECENT_LOGS.get_or_init(|| Mutex::new(VecDeque::with_capacity(RECENT_LOG_LIMIT)));
     let mut guard = match logs.lock() {
         Ok(guard) => guard,
@@ -664,18 +1774,11 @@ fn push_recent_log_text(path: String, result: &'static str, reason: &str) {
         }
     };
 
+    history::append(&entry.to_recent_log());
     if guard.len() >= RECENT_LOG_LIMIT {
         guard.pop_front();
     }
-    guard.push_back(RecentLogEntry {
-        timestamp_unix_ms: SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map(|value| value.as_millis())
-            .unwrap_or(0),
-        path,
-        result,
-        reason: reason.to_string(),
-    });
+    guard.push_back(entry);
 }
 
 pub fn get_recent_logs() -> Vec<RecentLog> {
@@ -691,12 +1794,7 @@ pub fn get_recent_logs() -> Vec<RecentLog> {
     guard
         .iter()
         .rev()
-        .map(|entry| RecentLog {
-            timestamp_unix_ms: entry.timestamp_unix_ms,
-            path: entry.path.clone(),
-            result: entry.result.to_string(),
-            reason: entry.reason.clone(),
-        })
+        .map(RecentLogEntry::to_recent_log)
         .collect()
 }
 
@@ -737,19 +1835,176 @@ fn wait_for_stable_file(path: &Path) -> Result<bool, std::io::Error> {
     Ok(false)
 }
 
-fn is_target_file(path: &Path) -> bool {
-    if !path.is_file() {
+fn is_target_file(path: &Path, config: &AppConfig) -> bool {
+    if !path.is_file() || trash::is_in_trash(path) {
         return false;
     }
 
-    is_target_extension(path)
+    is_target_extension(path, config.input_formats_for(path))
 }
 
-fn is_target_extension(path: &Path) -> bool {
+/// The extension only preselects candidates; `inspect_source` checks
+/// the content once the file is stable.
+fn is_target_extension(path: &Path, formats: &[InputFormat]) -> bool {
     let Some(ext) = path.extension().and_then(|value| value.to_str()) else {
         return false;
     };
-    ext.eq_ignore_ascii_case("heic") || ext.eq_ignore_ascii_case("heif")
+    formats
+        .iter()
+        .flat_map(|format| format.extensions())
+        .any(|target| ext.eq_ignore_ascii_case(target))
+}
+
+/// Decides from the content of `path` how it is handled; the error is the
+/// reason the file is skipped.
+fn inspect_source(path: &Path, config: &AppConfig) -> Result<SourceContent, ConvertError> {
+    match sniff::sniff(path) {
+        Ok(Sniffed::Image(format)) if config.input_formats_for(path).contains(&format) => {
+            Ok(SourceContent::Decode(format))
+        }
+        Ok(Sniffed::Image(format)) => Err(ConvertError::Unsupported(format!(
+            "{} content is not an accepted input format for this folder",
+            format.label()
+        ))),
+        Ok(Sniffed::Jpeg) => Ok(SourceContent::Jpeg),
+        Ok(Sniffed::Corrupt(reason)) => Err(ConvertError::Corrupt(reason)),
+        Ok(Sniffed::Unknown) => Err(ConvertError::Unsupported(
+            "unrecognized image content".to_string(),
+        )),
+        Err(err) => Err(ConvertError::from_io(ConvertError::Io, "access error", &err)),
+    }
+}
+
+fn adopt_jpeg_source(
+    worker_id: usize,
+    path: &Path,
+    config: &AppConfig,
+) -> Result<ConversionDetails, ConvertError> {
+    let started = Instant::now();
+    let source_bytes = fs::metadata(path).ok().map(|metadata| metadata.len());
+    match copy_or_rename_jpeg(path, &config.output_policy) {
+        Ok((output_path, action)) => {
+            log::info!(
+                "[worker {worker_id}] already a jpeg, {action}: {} -> {}",
+                path.display(),
+                output_path.display()
+            );
+            let mut reason = format!("already a jpeg; {action} to {}", output_path.display());
+            let mut code = None;
+            let mut archived_to = None;
+            if let OutputPolicy::Archive(options) = &config.output_policy {
+                match archive::move_to_archive(path, options, config) {
+                    Ok(destination) => archived_to = Some(destination.display().to_string()),
+                    Err(err) => {
+                        log::warn!(
+                            "[worker {worker_id}] failed archiving {}: {err}",
+                            path.display()
+                        );
+                        code = Some(err.code());
+                        reason.push_str(&format!("; source kept in place: {err}"));
+                    }
+                }
+            }
+            let details = ConversionDetails {
+                policy: policy_label(config).to_string(),
+                outputs: vec![output_path.display().to_string()],
+                trashed_as: None,
+                archived_to,
+                source_bytes,
+                output_bytes: source_bytes.unwrap_or(0),
+                duration_ms: started.elapsed().as_millis() as u64,
+            };
+            push_recent_conversion(path, code, &reason, details.clone());
+            Ok(details)
+        }
+        Err(err) => {
+            log::error!(
+                "[worker {worker_id}] failed adopting jpeg {}: [{}] {err}",
+                path.display(),
+                err.code()
+            );
+            push_recent_error(path, "failure", &err);
+            Err(err)
+        }
+    }
+}
+
+/// Counts the job under the watch folder it came from, and in the metrics;
+/// a stats file that cannot be written never fails the job.
+fn record_stats(
+    stats: &StatsStore,
+    config: &AppConfig,
+    path: &Path,
+    outcome: JobOutcome,
+) -> JobResult {
+    let folder = config
+        .watch_folders
+        .iter()
+        .filter(|folder| path.starts_with(folder))
+        .max_by_key(|folder| folder.as_os_str().len());
+    metrics::record_job(&outcome);
+    let result = match outcome {
+        JobOutcome::Converted(details) => JobResult::Converted(
+            details
+                .outputs
+                .first()
+                .map_or_else(|| path.to_path_buf(), PathBuf::from),
+        ),
+        JobOutcome::Failed(code) => JobResult::Failed(code),
+        JobOutcome::Skipped => JobResult::Skipped,
+    };
+    if let Err(err) = stats.record(folder.map(PathBuf::as_path), outcome) {
+        log::warn!("failed to record stats for {}: {err}", path.display());
+    }
+    result
+}
+
+/// Gives a source that already is a JPEG its output name without decoding:
+/// a rename in `replace` mode, otherwise a copy (archived afterwards in
+/// `archive` mode).
+fn copy_or_rename_jpeg(
+    input_path: &Path,
+    policy: &OutputPolicy,
+) -> Result<(PathBuf, &'static str), ConvertError> {
+    let output_path = resolve_output_path(input_path);
+    if matches!(policy, OutputPolicy::Replace) {
+        fs::rename(input_path, &output_path).map_err(|err| {
+            ConvertError::from_io(
+                ConvertError::Finalize,
+                format_args!(
+                    "failed to rename {} to {}",
+                    input_path.display(),
+                    output_path.display()
+                ),
+                &err,
+            )
+        })?;
+        return Ok((output_path, "renamed"));
+    }
+
+    let tmp_path = tmp_output_path_for(&output_path);
+    let copied = fs::copy(input_path, &tmp_path)
+        .map_err(|err| {
+            ConvertError::from_io(
+                ConvertError::Finalize,
+                format_args!("failed to copy to {}", tmp_path.display()),
+                &err,
+            )
+        })
+        .and_then(|_| {
+            fs::rename(&tmp_path, &output_path).map_err(|err| {
+                ConvertError::from_io(
+                    ConvertError::Finalize,
+                    format_args!("failed to finalize output {}", output_path.display()),
+                    &err,
+                )
+            })
+        });
+    if let Err(err) = copied {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+    Ok((output_path, "copied"))
 }
 
 fn is_lock_file(path: &Path) -> bool {
@@ -761,7 +2016,8 @@ fn is_lock_file(path: &Path) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::time::{SystemTime, UNIX_EPOCH};
+    use crate::config::{FolderOptions, ThumbnailOptions};
+    use crate::test_support::{unique_temp_dir_path, unique_temp_file_path};
 
     #[test]
     fn stable_file_returns_true() {
@@ -784,15 +2040,135 @@ mod tests {
         fs::write(&heif, b"x").expect("write heif");
         fs::write(&jpg, b"x").expect("write jpg");
 
-        assert!(is_target_file(&heic));
-        assert!(is_target_file(&heif));
-        assert!(!is_target_file(&jpg));
+        let config = AppConfig::default();
+        assert!(is_target_file(&heic, &config));
+        assert!(is_target_file(&heif, &config));
+        assert!(!is_target_file(&jpg, &config));
 
         let _ = fs::remove_file(heic);
         let _ = fs::remove_file(heif);
         let _ = fs::remove_file(jpg);
     }
 
+    #[test]
+    fn trash_directories_under_a_watch_folder_are_skipped() {
+        let dir = unique_temp_dir_path("trash_skip");
+        let photo = dir.join("album/IMG_0001.heic");
+        let trashed = dir.join(".Trash-1000/files/x.heic");
+        let shared = dir.join(".Trash/1000/files/y.heic");
+        for path in [&photo, &trashed, &shared] {
+            fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+            fs::write(path, b"heic").expect("write heic");
+        }
+        let config = AppConfig {
+            watch_folders: vec![dir.clone()],
+            recursive_watch: true,
+            ..AppConfig::default()
+        };
+
+        assert_eq!(collect_pending_files(&dir, &config), vec![photo.clone()]);
+        assert!(is_target_file(&photo, &config));
+        assert!(!is_target_file(&trashed, &config));
+        assert!(!is_target_file(&shared, &config));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn target_extension_accepts_image_sequences() {
+        assert!(is_target_extension(Path::new("/tmp/burst.heics"), InputFormat::DEFAULT));
+        assert!(is_target_extension(Path::new("/tmp/burst.AVCS"), InputFormat::DEFAULT));
+        assert!(!is_target_extension(Path::new("/tmp/clip.mov"), InputFormat::DEFAULT));
+    }
+
+    #[test]
+    fn target_extension_follows_folder_input_formats() {
+        let formats = [InputFormat::Avif, InputFormat::Dng, InputFormat::Tiff];
+        assert!(is_target_extension(Path::new("/tmp/render.AVIF"), &formats));
+        assert!(is_target_extension(Path::new("/tmp/IMG_0001.dng"), &formats));
+        assert!(is_target_extension(Path::new("/tmp/scan.tif"), &formats));
+        assert!(!is_target_extension(Path::new("/tmp/IMG_0001.heic"), &formats));
+        assert!(!is_target_extension(Path::new("/tmp/photo.webp"), &formats));
+    }
+
+    #[test]
+    fn input_format_is_decided_by_content_not_extension() {
+        let dir = unique_temp_dir_path("sniff_inbox");
+        fs::create_dir_all(&dir).expect("create dir");
+        // An AVIF saved with a .heic extension.
+        let mislabeled = dir.join("render.heic");
+        let mut ftyp = 24u32.to_be_bytes().to_vec();
+        ftyp.extend_from_slice(b"ftypavif\0\0\0\0mif1avif");
+        fs::write(&mislabeled, &ftyp).expect("write avif");
+        let unknown = dir.join("notes.heic");
+        fs::write(&unknown, b"plain text notes, not an image").expect("write text");
+
+        let mut config = AppConfig {
+            folder_options: vec![FolderOptions {
+                path: dir.clone(),
+                input_formats: Some(vec![InputFormat::Heic, InputFormat::Avif]),
+                ..FolderOptions::default()
+            }],
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            inspect_source(&mislabeled, &config),
+            Ok(SourceContent::Decode(InputFormat::Avif))
+        );
+        let err = inspect_source(&unknown, &config).expect_err("unknown content");
+        assert_eq!(err.code(), "unsupported");
+        assert!(err.message().contains("unrecognized"));
+
+        config.folder_options[0].input_formats = None;
+        let err = inspect_source(&mislabeled, &config).expect_err("avif not accepted");
+        assert!(err.message().contains("AVIF content"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn misnamed_jpeg_and_truncated_heic_are_recognized() {
+        let dir = unique_temp_dir_path("sniff_misnamed");
+        fs::create_dir_all(&dir).expect("create dir");
+        let jpeg = dir.join("android.heic");
+        fs::write(&jpeg, [0xFF, 0xD8, 0xFF, 0xE1, 0, 0, 0, 0, 0, 0, 0, 0]).expect("write jpeg");
+        let truncated = dir.join("IMG_0001.heic");
+        let mut heic = 16u32.to_be_bytes().to_vec();
+        heic.extend_from_slice(b"ftypheic\0\0\0\0");
+        heic.extend_from_slice(&4096u32.to_be_bytes());
+        heic.extend_from_slice(b"mdat");
+        fs::write(&truncated, &heic).expect("write truncated heic");
+
+        let config = AppConfig::default();
+        assert_eq!(inspect_source(&jpeg, &config), Ok(SourceContent::Jpeg));
+        let err = inspect_source(&truncated, &config).expect_err("truncated");
+        assert_eq!(err.code(), "corrupt");
+        assert!(err.message().starts_with("truncated mdat box"), "{err}");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn misnamed_jpeg_is_copied_in_coexist_and_renamed_in_replace() {
+        let dir = unique_temp_dir_path("adopt_jpeg");
+        fs::create_dir_all(&dir).expect("create dir");
+        let source = dir.join("android.heic");
+        fs::write(&source, b"jpeg bytes").expect("write source");
+        fs::write(dir.join("android.jpg"), b"existing").expect("write existing");
+
+        let (copied, action) =
+            copy_or_rename_jpeg(&source, &OutputPolicy::Coexist).expect("copy");
+        assert_eq!(action, "copied");
+        assert_eq!(copied, dir.join("android (1).jpg"));
+        assert_eq!(fs::read(&copied).expect("read copy"), b"jpeg bytes");
+        assert!(source.exists());
+
+        let (renamed, action) =
+            copy_or_rename_jpeg(&source, &OutputPolicy::Replace).expect("rename");
+        assert_eq!(action, "renamed");
+        assert_eq!(renamed, dir.join("android (2).jpg"));
+        assert!(!source.exists());
+        assert_eq!(fs::read(dir.join("android.jpg")).expect("read existing"), b"existing");
+        let _ = fs::remove_dir_all(dir);
+    }
+
     #[test]
     fn duplicate_signature_is_not_enqueued() {
         let path = PathBuf::from("/tmp/sample.heic");
@@ -843,6 +2219,183 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn restored_original_is_not_queued_again_until_it_changes() {
+        let dir = unique_temp_dir_path("restored");
+        let inbox = dir.join("inbox");
+        let original = inbox.join("IMG_0001.heic");
+        let trashed = dir.join("trash/IMG_0001.heic");
+        let untouched = inbox.join("IMG_0002.heic");
+        fs::create_dir_all(trashed.parent().expect("parent")).expect("create trash");
+        fs::create_dir_all(&inbox).expect("create inbox");
+        fs::write(&trashed, b"heic").expect("write trashed");
+        fs::write(&untouched, b"heic").expect("write untouched");
+        let journal = TrashJournal::new(&dir.join("trash_journal.json"));
+        let ledger = Mutex::new(Ledger::load(&dir.join("ledger.json")));
+        let id = journal
+            .record(
+                vec![TrashMove {
+                    original: original.clone(),
+                    trashed,
+                }],
+                Vec::new(),
+            )
+            .expect("record");
+        journal
+            .undo(&crate::undo::UndoSelection::Jobs(vec![id]), true)
+            .expect("undo");
+        let config = AppConfig {
+            watch_folders: vec![inbox.clone()],
+            ..AppConfig::default()
+        };
+
+        // As if undo ran in another process after the dispatcher started.
+        let mut restored = HashMap::new();
+        assert!(is_unchanged_restore_now(&original, &journal, &mut restored));
+        assert_eq!(
+            collect_watch_folder_files(&config, &restored, &ledger),
+            vec![untouched.clone()]
+        );
+
+        fs::write(&original, b"edited heic").expect("edit original");
+        let mut files = collect_watch_folder_files(&config, &load_restored(&journal), &ledger);
+        files.sort();
+        assert_eq!(files, vec![original, untouched]);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn jobs_wait_for_disk_space_and_resume_once_it_frees_up() {
+        let dir = unique_temp_dir_path("disk_space");
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let path = dir.join("IMG_4000.heic");
+        fs::write(&path, b"heic").expect("write heic");
+        let (job_tx, job_rx) = crossbeam_channel::unbounded::<PathBuf>();
+        let mut pending = VecDeque::from([path.clone()]);
+        let mut running = HashMap::new();
+        let mut in_flight = HashSet::from([path.clone()]);
+        let mut disk_guard = DiskSpaceGuard {
+            min_free_bytes: u64::MAX,
+            recheck_at: None,
+        };
+        let statuses = Mutex::new(Vec::new());
+        let on_status = |status| statuses.lock().expect("lock").push(status);
+        let mut progress = ProgressTracker::new(Box::new(|_| {}));
+
+        for _ in 0..2 {
+            release_pending_jobs(
+                &job_tx,
+                &mut pending,
+                &mut running,
+                &mut in_flight,
+                &mut disk_guard,
+                &on_status,
+                &mut progress,
+            );
+        }
+        assert!(job_rx.try_recv().is_err());
+        assert_eq!(pending, VecDeque::from([path.clone()]));
+        assert!(disk_guard.recheck_at.is_some());
+
+        disk_guard.min_free_bytes = 0;
+        release_pending_jobs(
+            &job_tx,
+            &mut pending,
+            &mut running,
+            &mut in_flight,
+            &mut disk_guard,
+            &on_status,
+            &mut progress,
+        );
+        assert!(job_rx.try_recv().is_err(), "rechecks wait for the interval");
+
+        disk_guard.recheck_at = Some(Instant::now());
+        release_pending_jobs(
+            &job_tx,
+            &mut pending,
+            &mut running,
+            &mut in_flight,
+            &mut disk_guard,
+            &on_status,
+            &mut progress,
+        );
+        assert_eq!(job_rx.try_recv().ok(), Some(path.clone()));
+        assert_eq!(running.get(&path), Some(&(4 * DISK_SPACE_ESTIMATE_FACTOR)));
+        assert_eq!(
+            *statuses.lock().expect("lock"),
+            vec![WatchStatus::LowDiskSpace, WatchStatus::Running]
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn progress_tracker_reports_batches_with_an_eta() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let mut progress = ProgressTracker::new(Box::new(move |event| {
+            sink.lock().expect("lock").push(event)
+        }));
+        let batches = |events: &Mutex<Vec<ProgressEvent>>| -> Vec<BatchProgress> {
+            events
+                .lock()
+                .expect("lock")
+                .iter()
+                .filter_map(|event| match event {
+                    ProgressEvent::Batch(progress) => Some(*progress),
+                    ProgressEvent::Job { .. } => None,
+                })
+                .collect()
+        };
+        let start = Instant::now();
+        let path = Path::new("/inbox/IMG_0001.heic");
+        let output = PathBuf::from("/inbox/IMG_0001.jpg");
+
+        progress.report(0, 0, start);
+        for _ in 0..4 {
+            progress.queued(path, start);
+        }
+        progress.started(path);
+        progress.report(3, 1, start);
+        progress.finished(path, JobResult::Converted(output.clone()));
+        progress.report(3, 0, start + Duration::from_millis(400));
+        let reported = batches(&events);
+        assert_eq!(reported.len(), 1, "changes within the report interval wait");
+        assert!(reported[0].started_unix_ms.is_some());
+        assert_eq!(
+            reported[0],
+            BatchProgress {
+                completed: 0,
+                total: 4,
+                queued: 3,
+                running: 1,
+                eta_secs: None,
+                ..reported[0]
+            }
+        );
+
+        progress.report(2, 1, start + Duration::from_secs(4));
+        progress.report(2, 1, start + Duration::from_secs(5));
+        progress.finished(path, JobResult::Failed("decode"));
+        progress.finished(path, JobResult::Skipped);
+        progress.finished(path, JobResult::Converted(output.clone()));
+        progress.report(0, 0, start + Duration::from_secs(6));
+        let reported = batches(&events);
+        assert_eq!(reported.len(), 3);
+        assert_eq!(reported[1].eta_secs, Some(12));
+        assert_eq!((reported[1].completed, reported[1].total), (1, 4));
+        assert!(!reported[2].is_active());
+        assert_eq!((reported[2].completed, reported[2].total), (4, 4));
+        assert_eq!(
+            (
+                reported[2].converted,
+                reported[2].failed,
+                reported[2].skipped
+            ),
+            (2, 1, 1)
+        );
+        assert_eq!(events.lock().expect("lock").len(), 3 + 4 + 1 + 4);
+    }
+
     #[test]
     fn jpeg_sibling_check_detects_existing_converted_file() {
         let dir = unique_temp_dir_path("sibling");
@@ -875,6 +2428,316 @@ mod tests {
         let _ = fs::remove_dir_all(dir);
     }
 
+    #[test]
+    fn multi_image_outputs_are_numbered_and_avoid_collisions() {
+        let dir = unique_temp_dir_path("multi");
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let heic = dir.join("IMG_0003.heic");
+        fs::write(&heic, b"x").expect("write heic");
+        fs::write(dir.join("IMG_0003_002.jpg"), b"y").expect("write collision");
+
+        let single = resolve_output_paths(&heic, 1);
+        assert_eq!(single, vec![dir.join("IMG_0003.jpg")]);
+
+        let numbered = resolve_output_paths(&heic, 3);
+        assert_eq!(
+            numbered,
+            vec![
+                dir.join("IMG_0003_001.jpg"),
+                dir.join("IMG_0003_002 (1).jpg"),
+                dir.join("IMG_0003_003.jpg"),
+            ]
+        );
+
+        // An unrelated numbered JPEG is no proof of a conversion; the ledger is.
+        fs::write(dir.join("IMG_0003_001.jpg"), b"z").expect("write unrelated jpeg");
+        assert!(!has_jpeg_sibling(&heic));
+        let config = AppConfig {
+            watch_folders: vec![dir.clone()],
+            ..AppConfig::default()
+        };
+        let ledger = Mutex::new(Ledger::load(&dir.join("state/ledger.json")));
+        assert_eq!(
+            collect_watch_folder_files(&config, &HashMap::new(), &ledger),
+            vec![heic.clone()]
+        );
+        ledger
+            .lock()
+            .expect("lock")
+            .record(&heic, &[], &numbered)
+            .expect("record outputs");
+        assert!(collect_watch_folder_files(&config, &HashMap::new(), &ledger).is_empty());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn live_photo_video_is_renamed_to_match_output() {
+        let dir = unique_temp_dir_path("live_photo_rename");
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let companion = dir.join("IMG_0001.MOV");
+        fs::write(&companion, b"mov").expect("write mov");
+
+        apply_live_photo_policy(
+            &companion,
+            &dir.join("IMG_0001 (1).jpg"),
+            LivePhotoPolicy::RenameToOutput,
+            MovedSource::Kept,
+        )
+        .expect("rename");
+
+        assert!(!companion.exists());
+        assert!(dir.join("IMG_0001 (1).MOV").exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn live_photo_video_moves_into_sidecar_folder_without_overwriting() {
+        let dir = unique_temp_dir_path("live_photo_sidecar");
+        let sidecar_dir = dir.join(LIVE_PHOTO_SIDECAR_DIR);
+        fs::create_dir_all(&sidecar_dir).expect("create temp dir");
+        fs::write(sidecar_dir.join("IMG_0002.MOV"), b"older").expect("write older");
+        let companion = dir.join("IMG_0002.MOV");
+        fs::write(&companion, b"mov").expect("write mov");
+
+        apply_live_photo_policy(
+            &companion,
+            &dir.join("IMG_0002.jpg"),
+            LivePhotoPolicy::MoveToSidecarFolder,
+            MovedSource::Kept,
+        )
+        .expect("move");
+
+        assert!(!companion.exists());
+        assert_eq!(
+            fs::read(sidecar_dir.join("IMG_0002 (1).MOV")).expect("moved"),
+            b"mov"
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn live_photo_video_stays_when_source_was_not_trashed() {
+        let dir = unique_temp_dir_path("live_photo_trash");
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let companion = dir.join("IMG_0003.MOV");
+        fs::write(&companion, b"mov").expect("write mov");
+
+        apply_live_photo_policy(
+            &companion,
+            &dir.join("IMG_0003.jpg"),
+            LivePhotoPolicy::TrashAlongside,
+            MovedSource::Kept,
+        )
+        .expect("no-op");
+
+        assert!(companion.exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn live_photo_video_follows_source_into_archive() {
+        let dir = unique_temp_dir_path("live_photo_archive");
+        let archive_dir = dir.join("archive/2024/05");
+        fs::create_dir_all(&archive_dir).expect("create archive dir");
+        fs::write(archive_dir.join("IMG_0004.MOV"), b"older").expect("write older");
+        let archived = archive_dir.join("IMG_0004.heic");
+        fs::write(&archived, b"heic").expect("write archived");
+        let companion = dir.join("IMG_0004.MOV");
+        fs::write(&companion, b"mov").expect("write mov");
+
+        let trashed = apply_live_photo_policy(
+            &companion,
+            &dir.join("IMG_0004.jpg"),
+            LivePhotoPolicy::TrashAlongside,
+            MovedSource::Archived(&archived),
+        )
+        .expect("archive");
+
+        assert_eq!(trashed, None);
+        assert!(!companion.exists());
+        assert_eq!(
+            fs::read(archive_dir.join("IMG_0004 (1).MOV")).expect("archived"),
+            b"mov"
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn depth_sidecar_is_named_after_output() {
+        assert_eq!(
+            sidecar_path_for(
+                Path::new("/tmp/drop/IMG_0001 (1).jpg"),
+                SidecarKind::DepthMap,
+                &AppConfig::default()
+            ),
+            PathBuf::from("/tmp/drop/IMG_0001 (1)_depth.png")
+        );
+    }
+
+    #[test]
+    fn thumbnails_go_to_subfolder_or_mirrored_root() {
+        let thumbnail = SidecarKind::Thumbnail { size: 256 };
+        let mut config = AppConfig {
+            watch_folders: vec![PathBuf::from("/tmp/drop")],
+            folder_options: vec![FolderOptions {
+                path: PathBuf::from("/tmp/drop"),
+                thumbnails: Some(ThumbnailOptions {
+                    sizes: vec![256],
+                    location: ThumbnailLocation::Subfolder,
+                }),
+                ..FolderOptions::default()
+            }],
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            sidecar_path_for(Path::new("/tmp/drop/IMG_0001.jpg"), thumbnail, &config),
+            PathBuf::from("/tmp/drop/.thumbs/IMG_0001_256.jpg")
+        );
+
+        config.folder_options[0].thumbnails = Some(ThumbnailOptions {
+            sizes: vec![256],
+            location: ThumbnailLocation::SeparateRoot {
+                root: PathBuf::from("/tmp/previews"),
+            },
+        });
+        assert_eq!(
+            sidecar_path_for(Path::new("/tmp/drop/2024/trip/IMG_0001.jpg"), thumbnail, &config),
+            PathBuf::from("/tmp/previews/2024/trip/IMG_0001_256.jpg")
+        );
+    }
+
+    #[test]
+    fn deleted_source_takes_thumbnails_and_empty_thumbs_folder_along() {
+        let root = unique_temp_dir_path("thumbnail_cleanup");
+        let thumbs_dir = root.join(THUMBNAIL_DIR);
+        fs::create_dir_all(&thumbs_dir).expect("create thumbs dir");
+        let source = root.join("IMG_0001.heic");
+        let thumbnail = thumbs_dir.join("IMG_0001_256.jpg");
+        fs::write(&source, b"heic").expect("write source");
+        fs::write(&thumbnail, b"jpeg").expect("write thumbnail");
+        let ledger = Mutex::new(Ledger::load(&root.join("ledger.json")));
+        ledger
+            .lock()
+            .expect("lock")
+            .record(&source, std::slice::from_ref(&thumbnail), &[])
+            .expect("record");
+
+        remove_thumbnails_of_deleted_source(&ledger, &source);
+        assert!(thumbnail.exists());
+
+        fs::remove_file(&source).expect("delete source");
+        remove_thumbnails_of_deleted_source(&ledger, &source);
+        assert!(!thumbnail.exists());
+        assert!(!thumbs_dir.exists());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn existing_sidecars_are_never_overwritten() {
+        let root = unique_temp_dir_path("sidecar_collision");
+        let thumbs_dir = root.join(THUMBNAIL_DIR);
+        fs::create_dir_all(&thumbs_dir).expect("create thumbs dir");
+        let output_path = root.join("IMG_0001.jpg");
+        fs::write(root.join("IMG_0001_depth.png"), b"older depth").expect("write depth");
+        fs::write(thumbs_dir.join("IMG_0001_256.jpg"), b"older thumb").expect("write thumb");
+        let mut jpeg = Vec::new();
+        image::DynamicImage::new_rgb8(4, 2)
+            .write_to(
+                &mut std::io::Cursor::new(&mut jpeg),
+                image::ImageFormat::Jpeg,
+            )
+            .expect("encode jpeg");
+        let image = ConvertedImage {
+            bytes: jpeg,
+            sidecars: vec![
+                converter::Sidecar {
+                    kind: SidecarKind::DepthMap,
+                    bytes: b"depth".to_vec(),
+                },
+                converter::Sidecar {
+                    kind: SidecarKind::Thumbnail { size: 256 },
+                    bytes: b"thumb".to_vec(),
+                },
+            ],
+            report: ConvertReport {
+                source_dimensions: (4, 2),
+                output_dimensions: (4, 2),
+                jpeg_quality: 90,
+                missed_target_bytes: None,
+                ultra_hdr: false,
+            },
+        };
+
+        let sidecars = finalize_outputs(
+            &[image],
+            std::slice::from_ref(&output_path),
+            &AppConfig::default(),
+        )
+        .expect("finalize");
+
+        let depth = root.join("IMG_0001_depth (1).png");
+        let thumbnail = thumbs_dir.join("IMG_0001_256 (1).jpg");
+        assert_eq!(
+            sidecars,
+            vec![
+                (SidecarKind::DepthMap, depth.clone()),
+                (SidecarKind::Thumbnail { size: 256 }, thumbnail.clone()),
+            ]
+        );
+        assert_eq!(fs::read(depth).expect("read depth"), b"depth");
+        assert_eq!(fs::read(thumbnail).expect("read thumb"), b"thumb");
+        assert_eq!(
+            fs::read(root.join("IMG_0001_depth.png")).expect("read older depth"),
+            b"older depth"
+        );
+        assert_eq!(
+            fs::read(thumbs_dir.join("IMG_0001_256.jpg")).expect("read older thumb"),
+            b"older thumb"
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn unverified_output_is_never_finalized() {
+        let root = unique_temp_dir_path("verify_outputs");
+        fs::create_dir_all(&root).expect("create root");
+        let output_path = root.join("IMG_0001.jpg");
+        let mut jpeg = Vec::new();
+        image::DynamicImage::new_rgb8(4, 2)
+            .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .expect("encode jpeg");
+        let image = |bytes: &[u8]| ConvertedImage {
+            bytes: bytes.to_vec(),
+            sidecars: Vec::new(),
+            report: ConvertReport {
+                source_dimensions: (4, 2),
+                output_dimensions: (4, 2),
+                jpeg_quality: 90,
+                missed_target_bytes: None,
+                ultra_hdr: false,
+            },
+        };
+        let truncated = [image(&jpeg[..jpeg.len() - 8])];
+        let mut config = AppConfig::default();
+
+        finalize_outputs(&truncated, std::slice::from_ref(&output_path), &config)
+            .expect("written without verification");
+        fs::remove_file(&output_path).expect("remove unverified output");
+
+        config.verify_outputs = true;
+        let err = finalize_outputs(&truncated, std::slice::from_ref(&output_path), &config)
+            .expect_err("truncated output must fail verification");
+        assert_eq!(err.code(), "verify");
+        assert!(!output_path.exists());
+        assert!(!tmp_output_path_for(&output_path).exists());
+
+        finalize_outputs(&[image(&jpeg)], std::slice::from_ref(&output_path), &config)
+            .expect("intact output passes");
+        assert!(output_path.exists());
+        let _ = fs::remove_dir_all(root);
+    }
+
     #[test]
     fn recent_log_buffer_keeps_only_latest_ten_items() {
         let path = PathBuf::from("/tmp/recent.heic");
@@ -893,13 +2756,18 @@ mod tests {
     }
 
     #[test]
-    fn conversion_error_is_classified() {
-        assert_eq!(classify_conversion_error("Permission denied"), "permission");
-        assert_eq!(
-            classify_conversion_error("sips exited with status 1"),
-            "decode"
-        );
-        assert_eq!(classify_conversion_error("failed to finalize output"), "io");
+    fn recent_log_carries_stable_error_code() {
+        let entry = recent_error_entry(
+            Path::new("/inbox/IMG_0001.heic"),
+            "failure",
+            &ConvertError::Decode("sips exited with status 1".to_string()),
+        )
+        .to_recent_log();
+
+        assert_eq!(entry.code.as_deref(), Some("decode"));
+        assert_eq!(entry.reason, "sips exited with status 1");
+        let json = serde_json::to_value(&entry).expect("serialize");
+        assert_eq!(json["code"], "decode");
     }
 
     #[test]
@@ -934,6 +2802,32 @@ mod tests {
         let _ = fs::remove_dir_all(dir);
     }
 
+    #[test]
+    fn permanent_delete_needs_confirmation_and_forces_verification() {
+        let dir = unique_temp_dir_path("permanent_delete");
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let source = dir.join("IMG_0001.heic");
+        fs::write(&source, b"heic bytes").expect("write source");
+        let mut config = AppConfig {
+            output_policy: OutputPolicy::Replace,
+            replace_mode: ReplaceMode::SecureDelete,
+            ..AppConfig::default()
+        };
+        assert!(must_verify_outputs(&config));
+
+        let err = delete_source_permanently(&source, &config).expect_err("unconfirmed");
+        assert_eq!(err.code(), "delete");
+        assert!(source.exists());
+
+        config.confirm_permanent_delete = true;
+        delete_source_permanently(&source, &config).expect("delete");
+        assert!(!source.exists());
+
+        config.replace_mode = ReplaceMode::Trash;
+        assert!(!must_verify_outputs(&config));
+        let _ = fs::remove_dir_all(dir);
+    }
+
     #[test]
     fn unique_destination_path_adds_suffix_on_collision() {
         let dir = unique_temp_dir_path("trash_collision");
@@ -947,20 +2841,4 @@ mod tests {
 
         let _ = fs::remove_dir_all(dir);
     }
-
-    fn unique_temp_file_path(name: &str) -> PathBuf {
-        let stamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("system time")
-            .as_nanos();
-        std::env::temp_dir().join(format!("heic-ready_{stamp}_{name}"))
-    }
-
-    fn unique_temp_dir_path(name: &str) -> PathBuf {
-        let stamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("system time")
-            .as_nanos();
-        std::env::temp_dir().join(format!("heic-ready_dir_{stamp}_{name}"))
-    }
 }